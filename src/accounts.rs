@@ -0,0 +1,239 @@
+use crate::config::{AccountConfig, AccountStrategy, AccountsConfig};
+use crate::pool::{PoolMetrics, ProcessPool};
+use crate::setup::ClaudeSetup;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Name of the only account when clay.yaml lists none
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Snapshot of one account for `--status` and `/admin/accounts`
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountStatus {
+    pub name: String,
+    pub claude_home: String,
+    pub authenticated: bool,
+    pub auth_detail: String,
+    pub requests: u64,
+    pub available: bool,
+    pub cooldown_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub pool: Option<PoolMetrics>,
+}
+
+/// One Claude login with its own home directory, usage counters and cooldown
+pub struct Account {
+    name: String,
+    setup: Arc<ClaudeSetup>,
+    pool: Option<Arc<ProcessPool>>,
+    requests: AtomicU64,
+    authenticated: AtomicBool,
+    /// How the last credential check described the login
+    auth_detail: Mutex<String>,
+    cooldown_until: Mutex<Option<DateTime<Utc>>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl Account {
+    fn new(name: String, setup: Arc<ClaudeSetup>) -> Self {
+        let pool = setup
+            .get_process_config()
+            .pool
+            .filter(|pool| pool.size > 0)
+            .map(|pool| Arc::new(ProcessPool::new(setup.clone(), pool)));
+        let (authenticated, auth_detail) = setup.get_auth_status().unwrap_or_else(|e| (false, e.to_string()));
+
+        Account {
+            name,
+            setup,
+            pool,
+            requests: AtomicU64::new(0),
+            authenticated: AtomicBool::new(authenticated),
+            auth_detail: Mutex::new(auth_detail),
+            cooldown_until: Mutex::new(None),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn setup(&self) -> &Arc<ClaudeSetup> {
+        &self.setup
+    }
+
+    /// Warm process pool for this account, if one is configured
+    pub fn pool(&self) -> Option<&Arc<ProcessPool>> {
+        self.pool.as_ref()
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated.load(Ordering::SeqCst)
+    }
+
+    /// Re-read the account's credentials, e.g. after a login
+    pub fn refresh_authentication(&self) -> bool {
+        let (authenticated, detail) = self.setup.get_auth_status().unwrap_or_else(|e| (false, e.to_string()));
+        self.record_auth_status(authenticated, detail);
+        authenticated
+    }
+
+    /// Remember a credential check made elsewhere, e.g. by the auth monitor
+    pub fn record_auth_status(&self, authenticated: bool, detail: String) {
+        self.authenticated.store(authenticated, Ordering::SeqCst);
+        *self.auth_detail.lock().unwrap() = detail;
+    }
+
+    /// Stop routing requests here until the account is logged in again
+    pub fn mark_unauthenticated(&self, reason: &str) {
+        warn!("Account {} lost its credentials: {}", self.name, reason);
+        // A rotated secret behind a credential command is picked up on the next try
+        self.setup.forget_credentials();
+        self.record_auth_status(false, reason.to_string());
+        *self.last_error.lock().unwrap() = Some(reason.to_string());
+    }
+
+    pub fn cooldown_until(&self) -> Option<DateTime<Utc>> {
+        let mut cooldown = self.cooldown_until.lock().unwrap();
+        if cooldown.is_some_and(|until| until <= Utc::now()) {
+            *cooldown = None;
+        }
+        *cooldown
+    }
+
+    /// Rest the account until `until` after the CLI reported a usage or rate limit
+    pub fn start_cooldown(&self, until: DateTime<Utc>, reason: &str) {
+        warn!("Account {} hit a usage limit, cooling down until {}", self.name, until);
+        *self.cooldown_until.lock().unwrap() = Some(until);
+        *self.last_error.lock().unwrap() = Some(reason.to_string());
+    }
+
+    /// Whether new requests may be sent through this account
+    pub fn is_available(&self) -> bool {
+        self.is_authenticated() && self.cooldown_until().is_none()
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Snapshot from the last credential check; this never runs the CLI or a credential command
+    pub fn status(&self) -> AccountStatus {
+        let authenticated = self.is_authenticated();
+        let cooldown_until = self.cooldown_until();
+
+        AccountStatus {
+            name: self.name.clone(),
+            claude_home: self.setup.get_claude_home().display().to_string(),
+            authenticated,
+            auth_detail: self.auth_detail.lock().unwrap().clone(),
+            requests: self.requests(),
+            available: authenticated && cooldown_until.is_none(),
+            cooldown_until,
+            last_error: self.last_error.lock().unwrap().clone(),
+            pool: self.pool.as_ref().map(|pool| pool.metrics()),
+        }
+    }
+}
+
+/// The accounts requests are spread over; a single default account when none are configured
+pub struct AccountPool {
+    accounts: Vec<Arc<Account>>,
+    strategy: AccountStrategy,
+    cooldown: Duration,
+    next: AtomicUsize,
+}
+
+impl AccountPool {
+    pub fn new(setup: Arc<ClaudeSetup>) -> Self {
+        let Some(config) = setup.get_accounts_config() else {
+            return AccountPool {
+                accounts: vec![Arc::new(Account::new(DEFAULT_ACCOUNT.to_string(), setup))],
+                strategy: AccountStrategy::default(),
+                cooldown: Duration::from_secs(AccountsConfig::default().cooldown_minutes * 60),
+                next: AtomicUsize::new(0),
+            };
+        };
+
+        let accounts = config.list.iter()
+            .map(|account| Arc::new(Account::new(account.name.clone(), Arc::new(Self::prepare(&setup, account)))))
+            .collect::<Vec<_>>();
+        info!("Using {} Claude accounts ({:?})", accounts.len(), config.strategy);
+
+        AccountPool {
+            accounts,
+            strategy: config.strategy,
+            cooldown: Duration::from_secs(config.cooldown_minutes * 60),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Derive an account's setup and make sure its home has Clay's generated config
+    fn prepare(setup: &ClaudeSetup, account: &AccountConfig) -> ClaudeSetup {
+        let account_setup = setup.for_account(account);
+        if let Err(e) = account_setup.setup_claude_home().and_then(|_| account_setup.setup_mcp_config()) {
+            warn!("Failed to prepare Claude home for account {}: {}", account.name, e);
+        }
+        account_setup
+    }
+
+    pub fn accounts(&self) -> &[Arc<Account>] {
+        &self.accounts
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Account>> {
+        self.accounts.iter().find(|account| account.name == name).cloned()
+    }
+
+    /// Account that owns a setup made by `new`
+    pub fn for_setup(&self, setup: &ClaudeSetup) -> Option<Arc<Account>> {
+        self.get(setup.get_account_name().unwrap_or(DEFAULT_ACCOUNT))
+    }
+
+    /// Choose an account for a new request, or `None` if every account is unavailable
+    pub fn pick(&self) -> Option<Arc<Account>> {
+        let available = self.accounts.iter().filter(|a| a.is_available());
+        match self.strategy {
+            AccountStrategy::LeastUsed => available.min_by_key(|a| a.requests()).cloned(),
+            AccountStrategy::RoundRobin => {
+                let available: Vec<_> = available.collect();
+                if available.is_empty() {
+                    return None;
+                }
+                let turn = self.next.fetch_add(1, Ordering::Relaxed);
+                Some(available[turn % available.len()].clone())
+            }
+        }
+    }
+
+    /// Cooldown for a usage limit that came without a reset time
+    pub fn default_cooldown_until(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::from_std(self.cooldown).unwrap_or_default()
+    }
+
+    /// Whether any account still has credentials, cooling down or not
+    pub fn any_authenticated(&self) -> bool {
+        self.accounts.iter().any(|a| a.is_authenticated())
+    }
+
+    /// When the first cooling account becomes usable again
+    pub fn next_available_at(&self) -> Option<DateTime<Utc>> {
+        self.accounts.iter()
+            .filter(|a| a.is_authenticated())
+            .filter_map(|a| a.cooldown_until())
+            .min()
+    }
+
+    pub fn status(&self) -> Vec<AccountStatus> {
+        self.accounts.iter().map(|account| account.status()).collect()
+    }
+}
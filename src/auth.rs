@@ -1,8 +1,83 @@
+use crate::config::CredentialSource;
 use crate::error::{ClaudeRelayError, Result};
 use crate::setup::ClaudeSetup;
+use portable_pty::{native_pty_system, Child as PtyChild, CommandBuilder, MasterPty, PtySize};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::time::Duration;
-use tracing::info;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Environment variable the Claude CLI reads a long-lived OAuth token from
+pub const OAUTH_TOKEN_ENV: &str = "CLAUDE_CODE_OAUTH_TOKEN";
+
+/// Environment variable the Claude CLI reads an Anthropic API key from
+pub const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+
+/// Start of the long-lived OAuth tokens printed by `claude setup-token`
+const OAUTH_TOKEN_PREFIX: &str = "sk-ant-oat";
+/// Start of Anthropic API keys
+pub(crate) const API_KEY_PREFIX: &str = "sk-ant-api";
+
+/// How long `setup-token` gets to print its login URL
+const URL_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long `setup-token` gets to exchange a code for a token
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the verification request may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+const PROBE_PROMPT: &str = "Reply with OK.";
+/// How long a token from `claude setup-token` stays valid
+const SETUP_TOKEN_LIFETIME_DAYS: i64 = 365;
+/// How long the output of a `command:` credential source is reused before the command runs again
+const CREDENTIAL_COMMAND_TTL: Duration = Duration::from_secs(300);
+
+/// Output of `command:` credential sources by command, so spawning a CLI doesn't run
+/// a vault or keychain command every time
+#[derive(Default)]
+pub(crate) struct CredentialCache(Mutex<HashMap<String, (Instant, String)>>);
+
+impl CredentialCache {
+    fn get(&self, command: &str) -> Option<String> {
+        self.0.lock().unwrap()
+            .get(command)
+            .filter(|(read_at, _)| read_at.elapsed() < CREDENTIAL_COMMAND_TTL)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn insert(&self, command: &str, value: &str) {
+        self.0.lock().unwrap().insert(command.to_string(), (Instant::now(), value.to_string()));
+    }
+
+    pub(crate) fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// Outcome of a login attempt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginResult {
+    /// Credentials were stored and a probe request succeeded
+    Authenticated,
+    /// The CLI refused the code or the resulting credentials
+    Rejected { reason: String },
+    /// Credentials were stored but the probe failed for an unrelated reason
+    Unverified { reason: String },
+}
+
+impl LoginResult {
+    /// Whether chat requests can go ahead with the new credentials
+    pub fn is_usable(&self) -> bool {
+        !matches!(self, LoginResult::Rejected { .. })
+    }
+}
 
 impl ClaudeSetup {
     pub fn get_setup_token_instructions(&self) -> String {
@@ -10,55 +85,208 @@ impl ClaudeSetup {
                 self.get_claude_path().display())
     }
     
-    pub fn complete_auth(&self, session_token: &str) -> Result<()> {
-        if session_token.is_empty() {
-            return Err(ClaudeRelayError::Authentication("Session token cannot be empty".into()));
+    /// Finish a login with a token printed by `claude setup-token`.
+    ///
+    /// Authorization codes only work inside the `setup-token` session that
+    /// produced the URL, so those go through `complete_login` instead.
+    pub fn complete_auth(&self, token: &str) -> Result<LoginResult> {
+        self.complete_login(None, token)
+    }
+    
+    /// Start `claude setup-token` in a PTY and wait for its login URL
+    pub fn start_login(&self) -> Result<SetupTokenSession> {
+        SetupTokenSession::start(self)
+    }
+    
+    /// Turn a code (entered into `session`) or a ready-made token into stored
+    /// credentials, then check them with a probe request
+    pub fn complete_login(&self, session: Option<SetupTokenSession>, code: &str) -> Result<LoginResult> {
+        // Anything after '#' is the OAuth state, which the CLI does not want
+        let code = code.split('#').next().unwrap_or(code).trim();
+        if code.is_empty() {
+            return Err(ClaudeRelayError::Authentication("Authorization code cannot be empty".into()));
         }
         
-        // Clean the token - remove everything after # if present
-        let clean_token = session_token.split('#').next().unwrap_or(session_token).trim();
+        // A pasted API key is stored as one, not handed to the CLI as an OAuth token
+        let api_key = is_credential(code, API_KEY_PREFIX);
+        let token = if api_key || is_credential(code, OAUTH_TOKEN_PREFIX) {
+            code.to_string()
+        } else {
+            let Some(session) = session else {
+                return Err(ClaudeRelayError::Authentication(
+                    "No login in progress: start one to get a URL before submitting its code".into()
+                ));
+            };
+            match session.submit_code(code) {
+                Ok(token) => token,
+                Err(reason) => return Ok(LoginResult::Rejected { reason }),
+            }
+        };
         
-        if clean_token.is_empty() {
-            return Err(ClaudeRelayError::Authentication("Token is empty after cleaning".into()));
+        let path = if api_key { self.get_api_key_path() } else { self.get_oauth_token_path() };
+        self.write_secret(&path, &token)?;
+        info!("Stored Claude {} {}", if api_key { "API key" } else { "OAuth token" }, redact_secret(&token));
+        
+        let result = self.verify_authentication();
+        match &result {
+            LoginResult::Authenticated => info!("Claude credentials verified"),
+            LoginResult::Rejected { reason } => {
+                warn!("Claude rejected the new credentials: {}", reason);
+                clear_secret(&path)?;
+            }
+            LoginResult::Unverified { reason } => {
+                warn!("Could not verify Claude credentials: {}", reason);
+            }
+        }
+        Ok(result)
+    }
+    
+    /// Send a one-turn request to check that the CLI accepts its credentials
+    pub fn verify_authentication(&self) -> LoginResult {
+        let mut cmd = Command::new(self.get_claude_path());
+        cmd.args(["--print", "--output-format", "json", "--max-turns", "1"])
+            .current_dir(self.get_base_dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for (key, value) in self.get_claude_env() {
+            cmd.env(key, value);
         }
+        cmd.env("TERM", "dumb").env("NO_COLOR", "1");
         
-        // Ensure config directory exists
-        let config_dir = self.get_claude_home().join(".config").join("claude");
-        fs::create_dir_all(&config_dir)?;
+        let output = match run_with_timeout(cmd, PROBE_PROMPT, PROBE_TIMEOUT) {
+            Ok(Some(output)) => output,
+            Ok(None) => return LoginResult::Unverified {
+                reason: format!("Probe did not finish within {:?}", PROBE_TIMEOUT),
+            },
+            Err(e) => return LoginResult::Unverified { reason: e.to_string() },
+        };
         
-        // Try multiple auth formats to match Claude CLI expectations
-        let auth_formats = vec![
-            // Format 1: Standard session format
-            format!(r#"{{"sessionKey":"{}"}}"#, clean_token),
-            // Format 2: OAuth account format  
-            format!(r#"{{"oauthAccount":{{"sessionKey":"{}"}}}}"#, clean_token),
-            // Format 3: Simple key format
-            format!(r#"{{"key":"{}"}}"#, clean_token),
-            // Format 4: Token format
-            format!(r#"{{"token":"{}","type":"session"}}"#, clean_token),
-        ];
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let is_error = serde_json::from_str::<serde_json::Value>(stdout.trim())
+            .map(|result| result["is_error"].as_bool().unwrap_or(false))
+            .unwrap_or(true);
+        if output.status.success() && !is_error {
+            return LoginResult::Authenticated;
+        }
         
-        // Try each format until one works
-        for (i, auth_data) in auth_formats.iter().enumerate() {
-            // Write to auth.json
-            let auth_file = config_dir.join("auth.json");
-            fs::write(&auth_file, auth_data)?;
-            
-            // Also try writing to .claude.json (where check_authentication looks)
-            let claude_file = self.get_claude_home().join(".claude.json");
-            fs::write(&claude_file, auth_data)?;
-            
-            // Check if authentication worked
-            if self.check_authentication()? {
-                info!("Authentication completed successfully with format {}", i + 1);
-                return Ok(());
-            }
+        let combined = format!("{}\n{}", stdout, stderr);
+        let reason = redact_secrets(combined.trim());
+        if self.is_authentication_needed(&combined) {
+            LoginResult::Rejected { reason }
+        } else {
+            LoginResult::Unverified { reason }
         }
+    }
+    
+    /// Where the token from `setup-token` is kept; the CLI gets it through `CLAUDE_CODE_OAUTH_TOKEN`
+    pub fn get_oauth_token_path(&self) -> PathBuf {
+        self.get_credentials_dir().join("oauth_token")
+    }
+    
+    /// Where an API key saved with `set_auth_token` is kept; the CLI gets it through `ANTHROPIC_API_KEY`
+    pub fn get_api_key_path(&self) -> PathBuf {
+        self.get_credentials_dir().join("api_key")
+    }
+    
+    pub fn stored_oauth_token(&self) -> Option<String> {
+        self.read_secret(&self.get_oauth_token_path())
+    }
+    
+    pub fn stored_api_key(&self) -> Option<String> {
+        self.read_secret(&self.get_api_key_path())
+    }
+    
+    /// When the credential in use stops working, if Clay can tell.
+    ///
+    /// Only tokens saved from `setup-token` have a known lifetime; keys and
+    /// configured or environment credentials are treated as not expiring.
+    pub fn get_auth_expiry(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let overridden = self.get_auth_config().is_some()
+            || env::var(API_KEY_ENV).is_ok_and(|key| !key.trim().is_empty())
+            || self.stored_api_key().is_some()
+            || env::var(OAUTH_TOKEN_ENV).is_ok_and(|token| !token.trim().is_empty());
+        if overridden {
+            return None;
+        }
+        self.stored_oauth_token()?;
+        let issued: chrono::DateTime<chrono::Utc> = fs::metadata(self.get_oauth_token_path())
+            .and_then(|metadata| metadata.modified())
+            .ok()?
+            .into();
+        Some(issued + chrono::Duration::days(SETUP_TOKEN_LIFETIME_DAYS))
+    }
+    
+    pub(crate) fn store_oauth_token(&self, token: &str) -> Result<()> {
+        self.write_secret(&self.get_oauth_token_path(), token)
+    }
+    
+    pub(crate) fn store_api_key(&self, key: &str) -> Result<()> {
+        self.write_secret(&self.get_api_key_path(), key)
+    }
+    
+    /// Read the credential named in clay.yaml's `auth:` section, if there is one
+    pub fn resolve_configured_credential(&self) -> Result<Option<ConfiguredCredential>> {
+        let Some(auth) = self.get_auth_config() else {
+            return Ok(None);
+        };
+        
+        let (name, env_var, source) = match (&auth.api_key, &auth.oauth_token) {
+            (Some(source), _) => ("api_key", API_KEY_ENV, source),
+            (None, Some(source)) => ("oauth_token", OAUTH_TOKEN_ENV, source),
+            (None, None) => return Ok(None),
+        };
+        
+        let value = self.read_credential_source(source).map_err(|reason| {
+            ClaudeRelayError::Authentication(format!("auth.{}: {}", name, reason))
+        })?;
+        
+        Ok(Some(ConfiguredCredential {
+            env_var,
+            value,
+            source: source.describe(),
+        }))
+    }
+    
+    /// Run `command:` credential sources again on next use, e.g. after the CLI rejected a login
+    pub fn forget_credentials(&self) {
+        self.get_credential_cache().clear();
+    }
+
+    pub(crate) fn read_credential_source(&self, source: &CredentialSource) -> std::result::Result<String, String> {
+        if let Some(value) = source.command.as_deref().and_then(|command| self.get_credential_cache().get(command)) {
+            return Ok(value);
+        }
+        let value = if let Some(var) = &source.env {
+            env::var(var).map_err(|_| format!("environment variable {} is not set", var))?
+        } else if let Some(file) = &source.file {
+            let path = self.get_base_dir().join(file);
+            fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?
+        } else if let Some(command) = &source.command {
+            let output = Command::new("sh")
+                .args(["-c", command])
+                .current_dir(self.get_base_dir())
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|e| format!("cannot run `{}`: {}", command, e))?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(format!("`{}` failed ({}): {}", command, output.status, redact_secrets(stderr.trim())));
+            }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        } else {
+            return Err("no env, file or command given".to_string());
+        };
         
-        // If none worked, return error with helpful info
-        Err(ClaudeRelayError::Authentication(
-            format!("Authentication failed with token: {} (tried multiple formats)", clean_token)
-        ))
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(format!("{} is empty", source.describe()));
+        }
+        if let Some(command) = &source.command {
+            self.get_credential_cache().insert(command, value);
+        }
+        Ok(value.to_string())
     }
     
     pub fn get_auth_url(&self) -> String {
@@ -81,106 +309,52 @@ impl ClaudeSetup {
         println!("1. Running setup-token command to get the authentication URL...");
         
         // Run the claude setup-token command in PTY to capture the auth URL
-        match self.capture_setup_token_output() {
-            Some(auth_url) => {
+        let (session, code) = match self.start_login() {
+            Ok(session) => {
                 println!("2. Visit this URL in your browser:");
-                println!("   {}", auth_url);
+                println!("   {}", session.auth_url());
                 println!("3. Complete the authentication process");
                 println!("4. Copy the authorization code you receive");
                 
                 let code = prompt_user("\nPaste the authorization code here: ");
-                if !code.trim().is_empty() {
-                    self.complete_auth(code.trim())?;
-                    println!("✅ Authentication completed successfully!");
-                } else {
-                    return Err(ClaudeRelayError::Authentication("No authentication code provided".into()));
-                }
+                (Some(session), code)
             }
-            None => {
+            Err(e) => {
                 // Fallback to manual instructions
-                println!("Could not automatically capture the authentication URL.");
+                println!("Could not automatically capture the authentication URL: {}", e);
                 println!("Please run this command manually:");
                 println!("   {} setup-token", self.get_claude_path().display());
                 println!("2. Visit the URL shown in your browser");
                 println!("3. Complete the authentication process");
-                println!("4. Copy the authorization code you receive");
+                println!("4. Copy the token that setup-token prints");
                 
-                let code = prompt_user("\nPaste the authorization code here: ");
-                if !code.trim().is_empty() {
-                    self.complete_auth(code.trim())?;
-                    println!("✅ Authentication completed successfully!");
-                } else {
-                    return Err(ClaudeRelayError::Authentication("No authentication code provided".into()));
-                }
+                let token = prompt_user("\nPaste the token here: ");
+                (None, token)
             }
-        }
+        };
         
-        Ok(())
-    }
-    
-    
-    fn capture_setup_token_output(&self) -> Option<String> {
-        use portable_pty::{native_pty_system, PtySize, CommandBuilder};
-        use std::io::Read;
-        use std::time::Duration;
-        
-        let pty_system = native_pty_system();
-        let pty_pair = pty_system.openpty(PtySize {
-            rows: 24,
-            cols: 80,
-            pixel_width: 0,
-            pixel_height: 0,
-        }).ok()?;
-        
-        let mut cmd = CommandBuilder::new(self.get_claude_path());
-        cmd.arg("setup-token");
-        
-        // Set environment to prevent browser opening
-        for (key, value) in self.get_claude_env() {
-            cmd.env(key, value);
+        if code.trim().is_empty() {
+            return Err(ClaudeRelayError::Authentication("No authentication code provided".into()));
         }
-        cmd.env("NO_BROWSER", "1");
-        cmd.env("CLAUDE_NO_BROWSER", "1");
-        
-        let mut child = pty_pair.slave.spawn_command(cmd).ok()?;
-        drop(pty_pair.slave);
         
-        // Read all output with timeout using raw read
-        let mut reader = pty_pair.master.try_clone_reader().ok()?;
-        let mut output = String::new();
-        let mut buffer = [0u8; 1024];
-        
-        // Give it time to output the URL - longer timeout since it needs to open browser
-        for _ in 0..100 { // 10 seconds total, 100ms intervals
-            std::thread::sleep(Duration::from_millis(100));
-            
-            match reader.read(&mut buffer) {
-                Ok(0) => break, // EOF
-                Ok(n) => {
-                    let text = String::from_utf8_lossy(&buffer[..n]);
-                    output.push_str(&text);
-                    
-                    // Check if we have a URL now
-                    if let Some(url) = self.extract_url_from_text(&output) {
-                        let _ = child.kill();
-                        return Some(url);
-                    }
-                }
-                Err(_) => {
-                    // No data available yet, continue
-                    continue;
-                }
+        match self.complete_login(session, &code)? {
+            LoginResult::Authenticated => println!("✅ Authentication completed successfully!"),
+            LoginResult::Unverified { reason } => {
+                println!("✅ Credentials saved, but they could not be verified yet: {}", reason);
+            }
+            LoginResult::Rejected { reason } => {
+                return Err(ClaudeRelayError::Authentication(format!("Login was rejected: {}", reason)));
             }
         }
         
-        // Kill the child process
-        let _ = child.kill();
-        
-        // Final attempt to extract URL from all output
-        self.extract_url_from_text(&output)
+        Ok(())
     }
     
-    fn extract_url_from_line(&self, line: &str) -> Option<String> {
+    pub(crate) fn capture_setup_token_output(&self) -> Option<String> {
+        self.start_login().ok().map(|session| session.auth_url().to_string())
+    }
+    
+    fn extract_url_from_line(line: &str) -> Option<String> {
         // Remove ANSI escape codes first
         let clean_line = Self::strip_ansi_codes(line);
         let clean_line = clean_line.trim();
@@ -210,7 +384,7 @@ impl ClaudeSetup {
                 // Skip escape sequence
                 if chars.next() == Some('[') {
                     // Skip until we find a letter (end of escape sequence)
-                    while let Some(esc_ch) = chars.next() {
+                    for esc_ch in chars.by_ref() {
                         if esc_ch.is_ascii_alphabetic() {
                             break;
                         }
@@ -224,9 +398,9 @@ impl ClaudeSetup {
         result
     }
     
-    fn extract_url_from_text(&self, text: &str) -> Option<String> {
+    fn extract_url_from_text(text: &str) -> Option<String> {
         for line in text.lines() {
-            if let Some(url) = self.extract_url_from_line(line) {
+            if let Some(url) = Self::extract_url_from_line(line) {
                 return Some(url);
             }
         }
@@ -275,4 +449,241 @@ pub fn prompt_user(prompt: &str) -> String {
     let mut response = String::new();
     io::stdin().read_line(&mut response).unwrap();
     response.trim().to_string()
-}
\ No newline at end of file
+}
+
+/// A secret from the `auth:` config, ready to put in a child's environment
+#[derive(Clone)]
+pub struct ConfiguredCredential {
+    pub env_var: &'static str,
+    pub value: String,
+    /// Where the value came from, safe to log
+    pub source: String,
+}
+
+impl std::fmt::Debug for ConfiguredCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfiguredCredential")
+            .field("env_var", &self.env_var)
+            .field("value", &redact_secret(&self.value))
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+/// A running `claude setup-token` waiting for the code from its login URL
+pub struct SetupTokenSession {
+    child: Box<dyn PtyChild + Send + Sync>,
+    writer: Box<dyn Write + Send>,
+    output: Receiver<String>,
+    transcript: String,
+    auth_url: String,
+    // Closing the master would hang up on the child
+    _master: Box<dyn MasterPty + Send>,
+}
+
+impl SetupTokenSession {
+    fn start(setup: &ClaudeSetup) -> Result<Self> {
+        let pty_pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                // Wide enough that the URL and token are never wrapped
+                cols: 1000,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| ClaudeRelayError::Authentication(format!("Failed to open a PTY: {}", e)))?;
+        
+        let mut cmd = CommandBuilder::new(setup.get_claude_path());
+        cmd.arg("setup-token");
+        cmd.cwd(setup.get_base_dir());
+        for (key, value) in setup.get_claude_env() {
+            cmd.env(key, value);
+        }
+        // Set environment to prevent browser opening
+        cmd.env("NO_BROWSER", "1");
+        cmd.env("CLAUDE_NO_BROWSER", "1");
+        
+        let child = pty_pair.slave.spawn_command(cmd)
+            .map_err(|e| ClaudeRelayError::Authentication(format!("Failed to run setup-token: {}", e)))?;
+        drop(pty_pair.slave);
+        
+        let mut reader = pty_pair.master.try_clone_reader()
+            .map_err(|e| ClaudeRelayError::Authentication(format!("Failed to read setup-token: {}", e)))?;
+        let writer = pty_pair.master.take_writer()
+            .map_err(|e| ClaudeRelayError::Authentication(format!("Failed to write to setup-token: {}", e)))?;
+        
+        let (tx, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            while let Ok(n) = reader.read(&mut buffer) {
+                if n == 0 || tx.send(String::from_utf8_lossy(&buffer[..n]).into_owned()).is_err() {
+                    break;
+                }
+            }
+        });
+        
+        let mut session = SetupTokenSession {
+            child,
+            writer,
+            output,
+            transcript: String::new(),
+            auth_url: String::new(),
+            _master: pty_pair.master,
+        };
+        
+        session.auth_url = session
+            .read_until(URL_TIMEOUT, ClaudeSetup::extract_url_from_text)
+            .map_err(|reason| ClaudeRelayError::Authentication(
+                format!("Could not capture the authentication URL: {}", reason)
+            ))?;
+        session.transcript.clear();
+        Ok(session)
+    }
+    
+    pub fn auth_url(&self) -> &str {
+        &self.auth_url
+    }
+    
+    /// Enter the code and wait for the token; errors are safe to show the user
+    fn submit_code(mut self, code: &str) -> std::result::Result<String, String> {
+        write!(self.writer, "{}\r", code)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("Failed to send the code to setup-token: {}", e))?;
+        
+        self.read_until(TOKEN_TIMEOUT, find_oauth_token)
+    }
+    
+    /// Collect output until `find` matches it, the child exits, or time runs out
+    fn read_until(
+        &mut self,
+        timeout: Duration,
+        find: impl Fn(&str) -> Option<String>,
+    ) -> std::result::Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.output.recv_timeout(remaining) {
+                Ok(chunk) => {
+                    self.transcript.push_str(&chunk);
+                    let clean = ClaudeSetup::strip_ansi_codes(&self.transcript);
+                    if let Some(found) = find(&clean) {
+                        return Ok(found);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("setup-token gave no answer within {:?}", timeout));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    let clean = ClaudeSetup::strip_ansi_codes(&self.transcript);
+                    debug!("setup-token exited: {}", redact_secrets(&clean));
+                    return Err(last_line(&clean)
+                        .map(|line| format!("setup-token exited: {}", redact_secrets(line)))
+                        .unwrap_or_else(|| "setup-token exited without output".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SetupTokenSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// Whether the text is a whole credential of the kind that starts with `prefix`
+fn is_credential(text: &str, prefix: &str) -> bool {
+    text.starts_with(prefix) && text.chars().all(is_token_char)
+}
+
+fn clear_secret(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn is_token_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'
+}
+
+fn find_oauth_token(text: &str) -> Option<String> {
+    text.split(|ch: char| !is_token_char(ch))
+        .find(|word| word.starts_with(OAUTH_TOKEN_PREFIX) && word.len() > OAUTH_TOKEN_PREFIX.len() + 8)
+        .map(|word| word.to_string())
+}
+
+fn last_line(text: &str) -> Option<&str> {
+    text.lines().map(str::trim).rfind(|line| !line.is_empty())
+}
+
+/// Show only enough of a secret to tell tokens apart
+pub fn redact_secret(secret: &str) -> String {
+    let visible: String = secret.chars().take(10).collect();
+    format!("{}… ({} chars)", visible, secret.chars().count())
+}
+
+/// Mask anything in CLI output that looks like a credential
+pub fn redact_secrets(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut word = String::new();
+    for ch in text.chars().chain(std::iter::once('\n')) {
+        if is_token_char(ch) {
+            word.push(ch);
+            continue;
+        }
+        if word.starts_with("sk-ant-") {
+            result.push_str(&redact_secret(&word));
+        } else {
+            result.push_str(&word);
+        }
+        word.clear();
+        result.push(ch);
+    }
+    result.pop();
+    result
+}
+
+/// Run a command with `input` on stdin; `None` if it had to be killed after `timeout`
+fn run_with_timeout(mut cmd: Command, input: &str, timeout: Duration) -> Result<Option<Output>> {
+    let mut child = cmd.spawn()
+        .map_err(|e| ClaudeRelayError::Process(format!("Failed to spawn Claude: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = stdin.write_all(input.as_bytes()) {
+            // The child may still be running; don't leave it behind or unreaped
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e.into());
+        }
+    }
+    
+    // Drain both pipes so the child never blocks on a full buffer while we poll it
+    let collect = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = collect(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = collect(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(Output {
+                status,
+                stdout: stdout.join().unwrap_or_default(),
+                stderr: stderr.join().unwrap_or_default(),
+            }));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
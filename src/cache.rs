@@ -0,0 +1,218 @@
+use crate::download::{check_sha256, find_checksum, sha256_file, verify_integrity, verify_sha256, SHASUMS_FILE};
+use crate::error::{ClaudeRelayError, Result};
+use crate::install::{Version, VersionReq, CLAUDE_PACKAGE};
+use crate::setup::ClaudeSetup;
+use serde::Serialize;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Where Bun release zips are downloaded from
+pub const BUN_RELEASES: &str = "https://github.com/oven-sh/bun/releases/latest/download";
+
+/// Registry the Claude CLI tarball is resolved from
+pub const NPM_REGISTRY: &str = "https://registry.npmjs.org";
+
+/// `(os, arch, asset)` for every platform Clay can install Bun on; the asset names
+/// match Bun's GitHub releases, so `<asset>.zip` is both the download and the cached file
+pub const BUN_PLATFORMS: &[(&str, &str, &str)] = &[
+    ("macos", "aarch64", "bun-darwin-aarch64"),
+    ("macos", "x86_64", "bun-darwin-x64"),
+    ("linux", "aarch64", "bun-linux-aarch64"),
+    ("linux", "x86_64", "bun-linux-x64"),
+];
+
+/// Bun release asset for a platform, e.g. `bun-linux-x64`
+pub fn bun_asset(os: &str, arch: &str) -> Result<&'static str> {
+    BUN_PLATFORMS
+        .iter()
+        .find(|(platform_os, platform_arch, _)| *platform_os == os && *platform_arch == arch)
+        .map(|(_, _, asset)| *asset)
+        .ok_or_else(|| ClaudeRelayError::Setup(format!("Unsupported platform: {}/{}", os, arch)))
+}
+
+/// Bun release asset for this machine
+pub fn current_bun_asset() -> Result<&'static str> {
+    bun_asset(env::consts::OS, env::consts::ARCH)
+}
+
+/// File name `npm pack` gives the CLI package at `version`
+pub fn claude_tarball_name(version: &str) -> String {
+    format!("{}{}.tgz", claude_tarball_prefix(), version)
+}
+
+fn claude_tarball_prefix() -> String {
+    format!("{}-", CLAUDE_PACKAGE.trim_start_matches('@').replace('/', "-"))
+}
+
+/// The cached Bun zip for `asset`, or an error saying how to make one
+pub fn cached_bun_zip(cache_dir: &Path, asset: &str) -> Result<PathBuf> {
+    let path = cache_dir.join(format!("{}.zip", asset));
+    if !path.is_file() {
+        return Err(ClaudeRelayError::Setup(format!(
+            "No {}.zip in {}; create the cache with `clay --bundle <dir>` on a connected machine",
+            asset,
+            cache_dir.display()
+        )));
+    }
+    Ok(path)
+}
+
+/// The newest cached CLI tarball that satisfies `req`
+pub fn cached_claude_tarball(cache_dir: &Path, req: Option<&VersionReq>) -> Result<(PathBuf, Version)> {
+    let prefix = claude_tarball_prefix();
+    let entries = fs::read_dir(cache_dir)
+        .map_err(|e| ClaudeRelayError::Setup(format!("Cannot read install cache {}: {}", cache_dir.display(), e)))?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let version = name.strip_prefix(&prefix)?.strip_suffix(".tgz")?.parse::<Version>().ok()?;
+            Some((entry.path(), version))
+        })
+        .filter(|(_, version)| req.is_none_or(|req| req.matches(version)))
+        .max_by_key(|(_, version)| *version)
+        .ok_or_else(|| ClaudeRelayError::Setup(format!(
+            "No {}*.tgz{} in {}; create the cache with `clay --bundle <dir>` on a connected machine",
+            prefix,
+            req.map(|req| format!(" matching claude.version {}", req)).unwrap_or_default(),
+            cache_dir.display()
+        )))
+}
+
+/// A CLI release in the npm registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub version: String,
+    pub tarball: String,
+    /// `dist.integrity`, e.g. `sha512-...`
+    pub integrity: Option<String>,
+}
+
+/// The release `req` selects from a registry document (`GET <registry>/<package>`):
+/// the newest matching stable version, or the `latest` tag without a pin
+pub fn pick_release(packument: &Value, req: Option<&VersionReq>) -> Option<Release> {
+    let versions = packument.get("versions")?.as_object()?;
+    let version = match req {
+        None => packument.pointer("/dist-tags/latest")?.as_str()?.to_string(),
+        Some(req) => versions
+            .keys()
+            .filter(|version| !version.contains('-'))
+            .filter_map(|version| Some((version.parse::<Version>().ok()?, version)))
+            .filter(|(parsed, _)| req.matches(parsed))
+            .max_by_key(|(parsed, _)| *parsed)?
+            .1
+            .clone(),
+    };
+    let dist = versions.get(&version)?.get("dist")?;
+    Some(Release {
+        tarball: dist.get("tarball")?.as_str()?.to_string(),
+        integrity: dist.get("integrity").and_then(Value::as_str).map(str::to_string),
+        version,
+    })
+}
+
+/// What `clay --bundle` wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct Bundle {
+    pub dir: PathBuf,
+    pub claude_version: String,
+    pub files: Vec<PathBuf>,
+}
+
+/// Add `files` to the cache's SHASUMS256.txt, replacing older lines for the same names
+fn write_shasums(dir: &Path, files: &[PathBuf]) -> Result<()> {
+    let path = dir.join(SHASUMS_FILE);
+    let names: Vec<String> = files.iter().map(|file| file.file_name().unwrap_or_default().to_string_lossy().into_owned()).collect();
+    let mut lines: Vec<String> = fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !names.iter().any(|name| find_checksum(line, name).is_some()))
+        .map(str::to_string)
+        .collect();
+    for (file, name) in files.iter().zip(&names) {
+        lines.push(format!("{}  {}", sha256_file(file)?, name));
+    }
+    fs::write(path, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+impl ClaudeSetup {
+    /// Fill `dir` with everything an offline `clay --setup --from <dir>` needs: a verified
+    /// Bun zip for each of `platforms` (Bun asset names), the CLI tarball `claude.version`
+    /// selects, and a SHASUMS256.txt covering them
+    pub async fn bundle(&self, dir: &Path, platforms: &[String]) -> Result<Bundle> {
+        fs::create_dir_all(dir)?;
+        let downloader = self.downloader();
+        let mut files = Vec::new();
+        for asset in platforms {
+            if !BUN_PLATFORMS.iter().any(|(_, _, known)| known == asset) {
+                let known: Vec<_> = BUN_PLATFORMS.iter().map(|(_, _, asset)| *asset).collect();
+                return Err(ClaudeRelayError::Setup(format!("Unknown platform '{}'; use one of {}", asset, known.join(", "))));
+            }
+            let file = format!("{}.zip", asset);
+            let sha256 = self.bun_checksum(&downloader, &file).await?;
+            let path = dir.join(&file);
+            downloader.download(&self.bun_release_url(&file), &path, Some(&sha256)).await?;
+            files.push(path);
+        }
+
+        let req = self.get_claude_version_req();
+        let packument: Value = downloader
+            .client()
+            .get(format!("{}/{}", self.npm_registry(), CLAUDE_PACKAGE))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let release = pick_release(&packument, req.as_ref()).ok_or_else(|| ClaudeRelayError::Setup(format!(
+            "No release of {} in the registry{}",
+            CLAUDE_PACKAGE,
+            req.as_ref().map(|req| format!(" matches claude.version {}", req)).unwrap_or_default()
+        )))?;
+        let file = claude_tarball_name(&release.version);
+        let path = dir.join(&file);
+        downloader.download(&release.tarball, &path, self.pinned_sha256(&file).as_deref()).await?;
+        if let Some(integrity) = &release.integrity {
+            verify_integrity(&path, integrity)?;
+        }
+        files.push(path);
+
+        write_shasums(dir, &files)?;
+        Ok(Bundle { dir: dir.to_path_buf(), claude_version: release.version, files })
+    }
+
+    /// Check a file from the install cache against `install.sha256` or the cache's
+    /// SHASUMS256.txt; files neither lists are used unchecked, with a warning
+    pub(crate) fn verify_cached(&self, path: &Path) -> Result<()> {
+        match self.cached_checksum(path) {
+            Some(expected) => verify_sha256(path, &expected),
+            None => Ok(()),
+        }
+    }
+
+    /// Read a file from the install cache, checking the bytes that are returned
+    pub(crate) fn read_cached(&self, path: &Path) -> Result<Vec<u8>> {
+        let bytes = fs::read(path)?;
+        if let Some(expected) = self.cached_checksum(path) {
+            check_sha256(&bytes, &expected, &path.display().to_string())?;
+        }
+        Ok(bytes)
+    }
+
+    fn cached_checksum(&self, path: &Path) -> Option<String> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let listed = || {
+            let shasums = fs::read_to_string(path.with_file_name(SHASUMS_FILE)).ok()?;
+            find_checksum(&shasums, &name)
+        };
+        let expected = self.pinned_sha256(&name).or_else(listed);
+        if expected.is_none() {
+            warn!("{} has no checksum in install.sha256 or {}; using it unverified", path.display(), SHASUMS_FILE);
+        }
+        expected
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::ClaudeRelayError;
+use chrono::{DateTime, Utc};
+
+/// Kind of failure reported by the Claude CLI on stderr or in its JSON output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliFailure {
+    /// Credentials are missing, expired or revoked
+    Authentication,
+    /// The account's usage or rate limit was hit
+    UsageLimit { reset_at: Option<DateTime<Utc>> },
+    /// Anthropic's API is overloaded (HTTP 529)
+    Overloaded,
+    /// The prompt does not fit in the model's context window
+    ContextTooLong,
+    /// The requested model does not exist or is not available to the account
+    InvalidModel,
+    /// An MCP server failed to start or crashed
+    McpFailure,
+    /// Anything else
+    Other,
+}
+
+const AUTHENTICATION: &[&str] = &[
+    "invalid api key",
+    "not authenticated",
+    "please log in",
+    "please run /login",
+    "claude login",
+    "authentication_error",
+    "invalid x-api-key",
+    "oauth token has expired",
+    "oauth token has been revoked",
+];
+
+const USAGE_LIMIT: &[&str] = &["usage limit", "rate limit", "rate_limit_error"];
+
+const OVERLOADED: &[&str] = &["overloaded_error", "overloaded", "api error: 529"];
+
+const CONTEXT_TOO_LONG: &[&str] = &[
+    "prompt is too long",
+    "context length",
+    "context window",
+    "exceed context limit",
+    "input length and `max_tokens` exceed",
+];
+
+const INVALID_MODEL: &[&str] = &[
+    "invalid model",
+    "unknown model",
+    "model not found",
+    "issue with the selected model",
+    "not_found_error: model",
+];
+
+const MCP_FAILURE: &[&str] = &[
+    "mcp server",
+    "mcp error",
+    "mcp config",
+    "failed to connect to mcp",
+];
+
+/// Classify CLI output; checked in order of how actionable the failure is
+pub fn classify(output: &str) -> CliFailure {
+    // Structured errors are matched on their raw JSON, e.g. `"type":"overloaded_error"`
+    let text = output.to_lowercase();
+    let matches = |patterns: &[&str]| patterns.iter().any(|p| text.contains(p));
+
+    if matches(USAGE_LIMIT) {
+        CliFailure::UsageLimit { reset_at: parse_reset(output) }
+    } else if matches(AUTHENTICATION) {
+        CliFailure::Authentication
+    } else if matches(OVERLOADED) {
+        CliFailure::Overloaded
+    } else if matches(CONTEXT_TOO_LONG) {
+        CliFailure::ContextTooLong
+    } else if matches(INVALID_MODEL) || (text.contains("not_found_error") && text.contains("model")) {
+        CliFailure::InvalidModel
+    } else if matches(MCP_FAILURE) && (text.contains("fail") || text.contains("error") || text.contains("crash")) {
+        CliFailure::McpFailure
+    } else {
+        CliFailure::Other
+    }
+}
+
+/// Typed error for a failed CLI call
+pub fn cli_error(output: &str) -> ClaudeRelayError {
+    let message = error_message(output);
+    match classify(output) {
+        CliFailure::Authentication => ClaudeRelayError::Authentication(
+            format!("Authentication required: {}", message)
+        ),
+        CliFailure::UsageLimit { reset_at } => ClaudeRelayError::UsageLimit { message, reset_at },
+        CliFailure::Overloaded => ClaudeRelayError::Overloaded(message),
+        CliFailure::ContextTooLong => ClaudeRelayError::ContextTooLong(message),
+        CliFailure::InvalidModel => ClaudeRelayError::InvalidModel(message),
+        CliFailure::McpFailure => ClaudeRelayError::McpFailure(message),
+        CliFailure::Other => ClaudeRelayError::Process(format!("Claude command failed: {}", message)),
+    }
+}
+
+/// Reset time from CLI messages like `Claude AI usage limit reached|1760000000`
+pub fn parse_reset(output: &str) -> Option<DateTime<Utc>> {
+    let message = error_message(output);
+    let (_, timestamp) = message.trim().rsplit_once('|')?;
+    DateTime::from_timestamp(timestamp.trim().parse().ok()?, 0)
+}
+
+/// The human-readable part of the output: the `result` or `error.message` of a
+/// JSON line when there is one, otherwise the trimmed text
+pub fn error_message(output: &str) -> String {
+    output.lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line.trim()).ok())
+        .find_map(|value| json_message(&value))
+        .unwrap_or_else(|| output.trim().to_string())
+}
+
+fn json_message(value: &serde_json::Value) -> Option<String> {
+    value.get("result")
+        .or_else(|| value.pointer("/error/message"))
+        .or_else(|| value.get("message"))
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().to_string())
+}
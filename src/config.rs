@@ -1,10 +1,16 @@
-use crate::error::Result;
+use crate::error::{ClaudeRelayError, Result};
+use crate::expand;
+use crate::install::{InstallStrategy, VersionReq};
+use crate::layers;
+use crate::schema;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use tracing::warn;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(default = "default_port")]
     pub port: String,
@@ -33,15 +39,77 @@ pub struct Config {
     
     #[serde(default)]
     pub server: Option<ServerConfig>,
+    
+    #[serde(default)]
+    pub process: Option<ProcessConfig>,
+    
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    
+    #[serde(default)]
+    pub accounts: Option<AccountsConfig>,
+    
+    #[serde(default)]
+    pub credentials: Option<CredentialsConfig>,
+    
+    #[serde(default)]
+    pub install: Option<InstallConfig>,
+    
+    #[serde(default)]
+    pub claude: Option<ClaudeConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How Clay finds or installs the Claude CLI
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct InstallConfig {
+    /// `portable` (Bun and the CLI in `.bun`, the default), `system` (`claude` from PATH)
+    /// or the path of a `claude` binary
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub strategy: Option<InstallStrategy>,
+    /// Directory of Bun zips and CLI tarballs made by `clay --bundle`, used instead of
+    /// downloading (relative to the project)
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+    /// Base URL serving Bun release zips and SHASUMS256.txt, for a mirror or proxy
+    /// (default: Bun's latest GitHub release)
+    #[serde(default)]
+    pub bun_mirror: Option<String>,
+    /// npm registry for the Claude CLI (default: https://registry.npmjs.org)
+    #[serde(default)]
+    pub npm_registry: Option<String>,
+    /// Pinned SHA-256 hashes by file name, e.g. `bun-linux-x64.zip`, checked instead of
+    /// the published SHASUMS256.txt
+    #[serde(default)]
+    pub sha256: HashMap<String, String>,
+}
+
+/// Settings for the Claude CLI itself
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ClaudeConfig {
+    /// Version Clay installs: exact (`1.0.58`), a range (`^1.0`, `~1.2.3`, `>=1.0.0 <2.0.0`) or `latest`
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub version: Option<VersionReq>,
+}
+
+/// A parsed clay.yaml and the problems worth reporting that didn't stop it loading
+#[derive(Debug, Clone)]
+pub struct ConfigReport {
+    pub config: Config,
+    /// Unknown keys, with the line they're on and a suggestion when one is close
+    pub warnings: Vec<String>,
+    /// Files the configuration was read from, including fragments it includes
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpConfig {
     #[serde(default)]
     pub servers: HashMap<String, McpServer>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpServer {
     #[serde(default)]
     pub transport: Option<String>,
@@ -63,6 +131,43 @@ pub struct McpServer {
     pub metadata: Option<McpMetadata>,
 }
 
+impl McpConfig {
+    /// Servers whose transport can't be determined or is missing its command or URL
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        for (name, server) in &self.servers {
+            if server.is_command() {
+                if let Some(command) = &server.command {
+                    if command.is_empty() {
+                        issues.push(format!("MCP server '{}': command cannot be empty", name));
+                    }
+                } else {
+                    issues.push(format!("MCP server '{}': command is required for command transport", name));
+                }
+            } else if server.is_http() {
+                if let Some(url) = &server.url {
+                    if !url.starts_with("http://") && !url.starts_with("https://") {
+                        issues.push(format!("MCP server '{}': invalid HTTP URL '{}'", name, url));
+                    }
+                } else {
+                    issues.push(format!("MCP server '{}': url is required for HTTP transport", name));
+                }
+            } else if server.is_websocket() {
+                if let Some(url) = &server.url {
+                    if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                        issues.push(format!("MCP server '{}': invalid WebSocket URL '{}'", name, url));
+                    }
+                } else {
+                    issues.push(format!("MCP server '{}': url is required for WebSocket transport", name));
+                }
+            } else {
+                issues.push(format!("MCP server '{}': unable to determine transport type", name));
+            }
+        }
+        issues
+    }
+}
+
 impl McpServer {
     pub fn is_command(&self) -> bool {
         self.transport.is_none() && self.command.is_some() && self.url.is_none()
@@ -80,7 +185,7 @@ impl McpServer {
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpMetadata {
     #[serde(default)]
     pub description: Option<String>,
@@ -90,12 +195,264 @@ pub struct McpMetadata {
     pub provider: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ServerConfig {
     #[serde(default = "default_port_u16")]
     pub port: u16,
     #[serde(default = "default_max_processes")]
     pub max_processes: usize,
+    /// Bearer token required for `/admin` endpoints; without one they only answer localhost
+    #[serde(default)]
+    pub admin_token: Option<String>,
+    /// Background credential checks; on with defaults when unset
+    #[serde(default)]
+    pub auth_monitor: Option<AuthMonitorConfig>,
+    /// Reload clay.yaml and its layers when they change, without a restart
+    #[serde(default = "default_true")]
+    pub watch_config: bool,
+}
+
+/// Periodic credential checks that warn before users hit an expired login
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuthMonitorConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Minutes between checks
+    #[serde(default = "default_auth_monitor_interval")]
+    pub interval_minutes: u64,
+    /// Send a one-turn request on each check instead of only looking at stored credentials.
+    /// Off by default: every probe is a billed request per account.
+    #[serde(default)]
+    pub probe: bool,
+    /// Alert this many days before a credential expires
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u64,
+    /// URL that receives a JSON POST when an account's credentials need attention
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+impl Default for AuthMonitorConfig {
+    fn default() -> Self {
+        AuthMonitorConfig {
+            enabled: true,
+            interval_minutes: default_auth_monitor_interval(),
+            probe: false,
+            expiry_warning_days: default_expiry_warning_days(),
+            webhook: None,
+        }
+    }
+}
+
+/// Credentials handed to the Claude CLI instead of an interactive login
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AuthConfig {
+    /// Anthropic API key, passed to the CLI as `ANTHROPIC_API_KEY`
+    #[serde(default)]
+    pub api_key: Option<CredentialSource>,
+    /// Long-lived token from `claude setup-token`, passed as `CLAUDE_CODE_OAUTH_TOKEN`
+    #[serde(default)]
+    pub oauth_token: Option<CredentialSource>,
+}
+
+/// Where logins are kept; per project unless `shared` is on
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CredentialsConfig {
+    /// Keep logins in one store used by every project that opts in
+    #[serde(default)]
+    pub shared: bool,
+    /// Location of the shared store (default `~/.config/clay/credentials`)
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Encrypt the tokens Clay saves with a key read from env, file or command
+    #[serde(default)]
+    pub encryption_key: Option<CredentialSource>,
+}
+
+impl CredentialsConfig {
+    pub fn validate(&self) -> Vec<String> {
+        match &self.encryption_key {
+            Some(source) if source.sources_set() != 1 => {
+                vec!["credentials.encryption_key: set exactly one of env, file or command".to_string()]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Where a secret is read from; set exactly one field
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CredentialSource {
+    /// Environment variable holding the secret
+    #[serde(default)]
+    pub env: Option<String>,
+    /// File holding the secret, e.g. a mounted Docker or Kubernetes secret
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Shell command that prints the secret
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+impl CredentialSource {
+    /// Human-readable origin, safe to log
+    pub fn describe(&self) -> String {
+        match (&self.env, &self.file, &self.command) {
+            (Some(var), _, _) => format!("${}", var),
+            (_, Some(file), _) => format!("file {}", file),
+            (_, _, Some(command)) => format!("command `{}`", command),
+            _ => "an empty source".to_string(),
+        }
+    }
+    
+    fn sources_set(&self) -> usize {
+        [self.env.is_some(), self.file.is_some(), self.command.is_some()]
+            .iter()
+            .filter(|set| **set)
+            .count()
+    }
+}
+
+impl AuthConfig {
+    /// Problems that would stop a credential from ever resolving
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if self.api_key.is_some() && self.oauth_token.is_some() {
+            issues.push("auth: set either api_key or oauth_token, not both".to_string());
+        }
+        for (name, source) in [("api_key", &self.api_key), ("oauth_token", &self.oauth_token)] {
+            if let Some(source) = source {
+                if source.sources_set() != 1 {
+                    issues.push(format!("auth.{}: set exactly one of env, file or command", name));
+                }
+            }
+        }
+        issues
+    }
+}
+
+/// How the server spreads new requests over several Claude accounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStrategy {
+    #[default]
+    RoundRobin,
+    /// The account that has served the fewest requests
+    LeastUsed,
+}
+
+/// Several Claude logins, each with its own isolated home directory
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AccountsConfig {
+    #[serde(default)]
+    pub strategy: AccountStrategy,
+    /// How long to rest an account after a usage or rate limit when the CLI gives no reset time
+    #[serde(default = "default_cooldown_minutes")]
+    pub cooldown_minutes: u64,
+    #[serde(default)]
+    pub list: Vec<AccountConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AccountConfig {
+    /// Also the name of the account's home under `.claude-accounts/`
+    pub name: String,
+    /// Credentials for this account; without them it needs its own login
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+}
+
+impl Default for AccountsConfig {
+    fn default() -> Self {
+        AccountsConfig {
+            strategy: AccountStrategy::default(),
+            cooldown_minutes: default_cooldown_minutes(),
+            list: Vec::new(),
+        }
+    }
+}
+
+impl AccountsConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for account in &self.list {
+            let valid_name = !account.name.is_empty()
+                && account.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid_name {
+                issues.push(format!("accounts: '{}' is not a valid name (use letters, digits, '-' and '_')", account.name));
+            }
+            if !seen.insert(account.name.as_str()) {
+                issues.push(format!("accounts: '{}' is listed twice", account.name));
+            }
+            if let Some(auth) = &account.auth {
+                issues.extend(auth.validate().into_iter().map(|issue| format!("accounts.{}.{}", account.name, issue)));
+            }
+        }
+        issues
+    }
+}
+
+/// How a `ClaudeProcess` carries conversation memory between turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionMode {
+    /// Continue the Claude CLI's own session with `--resume`
+    #[default]
+    Native,
+    /// Replay the text history as a preamble on every call
+    Replay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessConfig {
+    #[serde(default)]
+    pub session_mode: SessionMode,
+    /// Keep one Claude CLI child alive per session in stream-json mode
+    #[serde(default)]
+    pub persistent: bool,
+    /// Seconds to wait for a persistent child to finish a turn
+    #[serde(default = "default_turn_timeout")]
+    pub turn_timeout: u64,
+    /// Pre-spawned children handed to new sessions; implies `persistent`
+    #[serde(default)]
+    pub pool: Option<PoolConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PoolConfig {
+    /// Number of warm Claude children to keep ready
+    #[serde(default = "default_pool_size")]
+    pub size: usize,
+    /// Replace a child after it has served this many turns
+    #[serde(default = "default_pool_max_uses")]
+    pub max_uses: u64,
+    /// Replace a child after it has been alive this many minutes
+    #[serde(default = "default_pool_max_age_minutes")]
+    pub max_age_minutes: u64,
+}
+
+impl Default for ProcessConfig {
+    fn default() -> Self {
+        ProcessConfig {
+            session_mode: SessionMode::default(),
+            persistent: false,
+            turn_timeout: default_turn_timeout(),
+            pool: None,
+        }
+    }
+}
+
+fn default_cooldown_minutes() -> u64 {
+    60
+}
+
+fn default_auth_monitor_interval() -> u64 {
+    30
+}
+
+fn default_expiry_warning_days() -> u64 {
+    14
 }
 
 fn default_port() -> String {
@@ -130,6 +487,22 @@ fn default_port_u16() -> u16 {
     3000
 }
 
+fn default_turn_timeout() -> u64 {
+    600
+}
+
+fn default_pool_size() -> usize {
+    2
+}
+
+fn default_pool_max_uses() -> u64 {
+    100
+}
+
+fn default_pool_max_age_minutes() -> u64 {
+    30
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -146,29 +519,169 @@ impl Default for Config {
             context: None,
             mcp: None,
             server: None,
+            process: None,
+            auth: None,
+            accounts: None,
+            credentials: None,
+            install: None,
+            claude: None,
         }
     }
 }
 
+/// Where the layered configuration comes from, on top of the files and `CLAY_*` variables
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOptions {
+    /// clay.yaml to read instead of the one in the project directory
+    pub file: Option<PathBuf>,
+    /// Entry of `profiles:` to apply; falls back to `CLAY_PROFILE`
+    pub profile: Option<String>,
+    /// Settings from command-line flags as dotted keys, e.g. `("server.port", "3001")`
+    pub overrides: Vec<(String, String)>,
+}
+
+impl ConfigOptions {
+    /// The committed configuration file, `<base_dir>/clay.yaml` unless `file` is set
+    pub fn config_file(&self, base_dir: &Path) -> PathBuf {
+        self.file.clone().unwrap_or_else(|| base_dir.join("clay.yaml"))
+    }
+    
+    /// clay.local.yaml, next to the configuration file
+    pub fn local_file(&self, base_dir: &Path) -> PathBuf {
+        let config_file = self.config_file(base_dir);
+        config_file.parent().unwrap_or(base_dir).join(layers::LOCAL_CONFIG_FILE)
+    }
+}
+
 impl Config {
-    /// Load configuration with priority: clay.yaml > defaults
+    /// Load the layered configuration for `base_dir` with no profile or flags
     /// Note: config.json is Claude CLI's own configuration, not Clay's
     pub fn load_with_priority(base_dir: &Path) -> Result<Self> {
-        // Try clay.yaml first (Clay's configuration)
-        let yaml_path = base_dir.join("clay.yaml");
-        if yaml_path.exists() {
-            return Self::load_yaml(&yaml_path);
+        Self::load_layered(base_dir, &ConfigOptions::default())
+    }
+    
+    /// Merge clay.yaml, clay.local.yaml, `CLAY_*` variables and `options`, logging unknown keys
+    pub fn load_layered(base_dir: &Path, options: &ConfigOptions) -> Result<Self> {
+        let report = Self::check_layered(base_dir, options)?;
+        for warning in &report.warnings {
+            warn!("clay.yaml: {}", warning);
+        }
+        Ok(report.config)
+    }
+    
+    /// Merge every configuration layer, keeping warnings about keys Clay doesn't recognise
+    pub fn check_layered(base_dir: &Path, options: &ConfigOptions) -> Result<ConfigReport> {
+        let vars: Vec<(String, String)> = std::env::vars().collect();
+        Self::check_layered_with(base_dir, options, &vars)
+    }
+    
+    /// Merge, lowest priority first: clay.yaml (after its includes), clay.local.yaml,
+    /// the selected profile, `CLAY_SECTION__KEY` variables from `vars`, then `options.overrides`.
+    /// `${VAR}` references are expanded from `vars` once everything is merged.
+    pub fn check_layered_with(base_dir: &Path, options: &ConfigOptions, vars: &[(String, String)]) -> Result<ConfigReport> {
+        let mut sources = Vec::new();
+        let mut value = serde_yaml::Value::Mapping(Default::default());
+        let files = [
+            (options.config_file(base_dir), None),
+            (options.local_file(base_dir), Some(layers::LOCAL_CONFIG_FILE.to_string())),
+        ];
+        for (path, label) in files {
+            if path.exists() {
+                let layer = layers::read_file(&path, label, &mut sources, &mut Vec::new())?;
+                layers::merge(&mut value, layer);
+            }
+        }
+        
+        let lookup = |name: &str| vars.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+        let profile = options.profile.clone().or_else(|| lookup(layers::PROFILE_ENV)).filter(|p| !p.is_empty());
+        layers::apply_profile(&mut value, profile.as_deref())?;
+        
+        let json_schema = schema::json_schema();
+        let overrides = layers::env_overrides(vars, &json_schema);
+        for (path, raw) in overrides.iter().chain(&options.overrides) {
+            let segments: Vec<&str> = path.split('.').collect();
+            layers::set_path(&mut value, path, raw, schema::accepts_string(&json_schema, &segments));
         }
         
-        // Default configuration (don't look for config.json as that's Claude CLI's file)
-        Ok(Config::default())
+        let files = sources.iter().filter_map(|source| source.path.clone()).collect();
+        if value.as_mapping().is_some_and(|m| m.is_empty()) {
+            return Ok(ConfigReport { config: Config::default(), warnings: Vec::new(), files });
+        }
+        let report = Self::check_value(value, &sources, profile.as_deref(), lookup)?;
+        Ok(ConfigReport { files, ..report })
     }
     
     pub fn load_yaml<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let report = Self::check_yaml(path)?;
+        for warning in &report.warnings {
+            warn!("clay.yaml: {}", warning);
+        }
+        Ok(report.config)
+    }
+    
+    /// Load clay.yaml, keeping warnings about keys it doesn't recognise
+    pub fn check_yaml<P: AsRef<Path>>(path: P) -> Result<ConfigReport> {
         let data = fs::read_to_string(path)?;
-        // TODO: Add back environment variable expansion with proper loop prevention
-        let config: Config = serde_yaml::from_str(&data)?;
-        Ok(config)
+        Self::check_yaml_str(&data, |name| std::env::var(name).ok())
+    }
+    
+    /// Parse clay.yaml text, expanding `${VAR}` references with `lookup`
+    pub fn from_yaml_str<F>(data: &str, lookup: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        Ok(Self::check_yaml_str(data, lookup)?.config)
+    }
+    
+    /// Parse clay.yaml text; syntax and type errors carry their line and column
+    pub fn check_yaml_str<F>(data: &str, lookup: F) -> Result<ConfigReport>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let value: serde_yaml::Value = serde_yaml::from_str(data)?;
+        if value.is_null() {
+            return Ok(ConfigReport { config: Config::default(), warnings: Vec::new(), files: Vec::new() });
+        }
+        let sources = [layers::Source { label: None, path: None, text: data.to_string() }];
+        Self::check_value(value, &sources, None, lookup)
+    }
+    
+    /// Expand and deserialize a merged document, pointing errors and unknown
+    /// keys at the file in `sources` they came from
+    fn check_value<F>(mut value: serde_yaml::Value, sources: &[layers::Source], profile: Option<&str>, lookup: F) -> Result<ConfigReport>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let json_schema = schema::json_schema();
+        // `servers.db.args[0]` is the sequence item `servers.db.args.0`
+        let is_text = |path: &str| {
+            let path = path.replace('[', ".").replace(']', "");
+            schema::accepts_string(&json_schema, &path.split('.').collect::<Vec<_>>())
+        };
+        let mut errors = Vec::new();
+        expand::expand_value(&mut value, "", &lookup, &is_text, &mut errors);
+        if !errors.is_empty() {
+            return Err(ClaudeRelayError::Config(errors.join("; ")));
+        }
+        
+        let mut unknown = Vec::new();
+        // Optional sections show up as `?` segments
+        let mut track_unknown = |path: serde_ignored::Path| {
+            unknown.push(path.to_string().split('.').filter(|s| *s != "?").collect::<Vec<_>>().join("."))
+        };
+        let deserializer = serde_ignored::Deserializer::new(value, &mut track_unknown);
+        let config: Config = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+            let path = e.path().to_string().split('.').filter(|s| *s != "?").collect::<Vec<_>>().join(".");
+            let location = locate_in(sources, &path, profile)
+                .map(|location| format!(" at {}", location.replace(", ", " ")))
+                .unwrap_or_default();
+            ClaudeRelayError::Config(format!("{}{}: {}", path, location, e.inner()))
+        })?;
+        
+        let warnings = unknown.iter()
+            .map(|path| schema::unknown_key_warning(&json_schema, path, locate_in(sources, path, profile).as_deref()))
+            .collect();
+        Ok(ConfigReport { config, warnings, files: Vec::new() })
     }
     
     pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -183,6 +696,47 @@ impl Config {
         Ok(())
     }
     
+    /// `install.strategy`, or the legacy top-level `claude_path` when that names another binary
+    pub fn install_strategy(&self) -> InstallStrategy {
+        if let Some(strategy) = self.install.as_ref().and_then(|install| install.strategy.clone()) {
+            return strategy;
+        }
+        if self.claude_path != default_claude_path() {
+            return InstallStrategy::Path(PathBuf::from(&self.claude_path));
+        }
+        InstallStrategy::Portable
+    }
+    
+    /// `claude.version`, when the CLI version is pinned
+    pub fn claude_version(&self) -> Option<VersionReq> {
+        self.claude.as_ref().and_then(|claude| claude.version.clone())
+    }
+
+    /// Problems that parse fine but would make Clay misbehave
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if let Some(mcp) = &self.mcp {
+            issues.extend(mcp.validate());
+        }
+        if let Some(auth) = &self.auth {
+            issues.extend(auth.validate());
+        }
+        if let Some(accounts) = self.accounts.as_ref().filter(|accounts| !accounts.list.is_empty()) {
+            issues.extend(accounts.validate());
+        }
+        if let Some(credentials) = &self.credentials {
+            issues.extend(credentials.validate());
+        }
+        issues
+    }
+    
+    /// YAML of this configuration with tokens, passwords and other secrets masked
+    pub fn to_masked_yaml(&self) -> Result<String> {
+        let mut value = serde_yaml::to_value(self)?;
+        layers::mask_secrets(&mut value);
+        Ok(serde_yaml::to_string(&value)?)
+    }
+    
     pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let data = serde_json::to_string_pretty(self)?;
         fs::write(path, data)?;
@@ -262,7 +816,103 @@ mcp:
 server:
   port: 3000
   max_processes: 100
+  # Apply edits to this file without restarting (port and accounts need a restart)
+  watch_config: true
+  # Check credentials in the background and alert before they stop working
+  # auth_monitor:
+  #   interval_minutes: 30
+  #   probe: false             # true sends a one-turn request on each check
+  #   expiry_warning_days: 14
+  #   webhook: "https://hooks.example.com/clay"
+
+# Claude CLI process behaviour
+process:
+  # native: continue the CLI's own session with --resume (keeps tool context)
+  # replay: paste the text history into every prompt
+  session_mode: native
+  # Keep one Claude CLI child alive per session instead of spawning per message
+  persistent: false
+  # Pre-spawn warm children for new sessions (implies persistent)
+  # pool:
+  #   size: 2
+  #   max_uses: 100
+  #   max_age_minutes: 30
+
+# Credentials for headless deployments (otherwise Clay asks you to log in)
+# Use api_key or oauth_token, read from one of env, file or command
+# auth:
+#   api_key:
+#     env: ANTHROPIC_API_KEY
+#   oauth_token:
+#     file: /run/secrets/claude_oauth_token
+#     # command: "vault kv get -field=token secret/claude"
+
+# Share one login between every project that turns this on, instead of a
+# separate login per .claude-home (import an existing one with --import-login)
+# credentials:
+#   shared: true
+#   path: ~/.config/clay/credentials
+#   # Encrypt saved tokens at rest
+#   encryption_key:
+#     env: CLAY_CREDENTIALS_KEY
+
+# Pin the Claude CLI version Clay installs; Clay reinstalls when the pin changes
+# and `clay --upgrade` moves to the newest matching release
+# claude:
+#   version: "^1.0"           # or an exact version such as 1.0.58, or latest
+
+# Where the Claude CLI comes from (restart Clay after changing it)
+# install:
+#   strategy: portable        # Bun and the CLI in .bun (default)
+#   # strategy: system        # claude from PATH, installed with npm if missing
+#   # strategy: /usr/local/bin/claude
+#   # Install offline from a cache made with `clay --bundle <dir>`
+#   # cache_dir: /opt/clay-cache
+#   # Download through a mirror (HTTPS_PROXY and NO_PROXY are honored too)
+#   # bun_mirror: https://artifacts.example.com/bun/latest
+#   # npm_registry: https://artifacts.example.com/api/npm/npm
+#   # sha256:
+#   #   bun-linux-x64.zip: 0123abcd...
+
+# Spread requests over several Claude logins, each in .claude-accounts/<name>
+# Log each one in with: clay --account <name> --status
+# accounts:
+#   strategy: round_robin   # or least_used
+#   cooldown_minutes: 60    # rest after a usage limit when no reset time is given
+#   list:
+#     - name: team-1
+#     - name: team-2
+#       auth:
+#         oauth_token:
+#           env: TEAM2_CLAUDE_TOKEN
 "#.to_string()
     }
 }
 
+/// Where the setting at `path` is written, e.g. `line 3, column 1` or
+/// `clay.local.yaml line 3, column 1`. The file matching most of the path wins,
+/// the later file on a tie, and a profile's own entry before the plain key.
+fn locate_in(sources: &[layers::Source], path: &str, profile: Option<&str>) -> Option<String> {
+    let plain: Vec<&str> = path.split('.').collect();
+    let mut candidates = vec![plain.clone()];
+    if let Some(profile) = profile {
+        candidates.insert(0, ["profiles", profile].into_iter().chain(plain.iter().copied()).collect());
+    }
+    
+    let mut best: Option<(usize, &layers::Source, usize, usize)> = None;
+    for segments in &candidates {
+        let bonus = segments.len() - plain.len();
+        for source in sources.iter().rev() {
+            if let Some((depth, line, column)) = schema::locate_deepest(&source.text, segments) {
+                let depth = depth.saturating_sub(bonus);
+                if best.is_none_or(|(best_depth, ..)| depth > best_depth) {
+                    best = Some((depth, source, line, column));
+                }
+            }
+        }
+    }
+    best.map(|(_, source, line, column)| match &source.label {
+        Some(label) => format!("{} line {}, column {}", label, line, column),
+        None => format!("line {}, column {}", line, column),
+    })
+}
@@ -0,0 +1,379 @@
+use crate::error::{ClaudeRelayError, Result};
+use crate::setup::ClaudeSetup;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// Tokens Clay itself saves, relative to its credentials directory
+const CLAY_SECRETS: &[&str] = &["oauth_token", "api_key"];
+
+/// File where an interactive `claude /login` keeps its OAuth credentials
+const CLI_CREDENTIALS: &str = ".credentials.json";
+
+/// Marks a token file encrypted with `credentials.encryption_key`
+const ENCRYPTED_PREFIX: &str = "clay-encrypted:v1:";
+
+/// Installs, logins and per-developer config that must never be committed
+const GITIGNORE_ENTRIES: &[&str] = &[".claude-home/", ".claude-accounts/", ".bun/", crate::layers::LOCAL_CONFIG_FILE];
+
+impl ClaudeSetup {
+    /// Shared store for this setup's account, when `credentials.shared` is on
+    pub fn get_shared_credentials_dir(&self) -> Option<PathBuf> {
+        let credentials = self.get_config()?.credentials.clone()?;
+        if !credentials.shared {
+            return None;
+        }
+        let root = match &credentials.path {
+            Some(path) => self.get_base_dir().join(expand_home(path)),
+            None => dirs::home_dir()?.join(".config").join("clay").join("credentials"),
+        };
+        Some(match self.get_account_name() {
+            Some(account) => root.join("accounts").join(account),
+            None => root,
+        })
+    }
+
+    /// Directory holding the tokens Clay saves: the shared store, or the project's Claude home
+    pub fn get_credentials_dir(&self) -> PathBuf {
+        self.get_shared_credentials_dir()
+            .unwrap_or_else(|| self.get_claude_home().join(".config").join("clay"))
+    }
+
+    /// The CLI's own login file; in the shared store the project's copy is a link to it
+    pub fn get_cli_credentials_path(&self) -> PathBuf {
+        match self.get_shared_credentials_dir() {
+            Some(dir) => dir.join(CLI_CREDENTIALS),
+            None => self.project_cli_credentials_path(),
+        }
+    }
+
+    fn project_cli_credentials_path(&self) -> PathBuf {
+        self.get_claude_home().join(".claude").join(CLI_CREDENTIALS)
+    }
+
+    /// Point the project's CLI login file at the shared store.
+    ///
+    /// A regular file in its place is a login made before sharing was turned on,
+    /// or one the CLI rewrote when refreshing its token; the newer copy wins.
+    pub fn link_shared_credentials(&self) -> Result<()> {
+        let Some(shared_dir) = self.get_shared_credentials_dir() else {
+            return Ok(());
+        };
+        create_private_dir(&shared_dir)?;
+        let shared = shared_dir.join(CLI_CREDENTIALS);
+        let local = self.project_cli_credentials_path();
+
+        let is_link = fs::symlink_metadata(&local).is_ok_and(|metadata| metadata.file_type().is_symlink());
+        if is_link {
+            if fs::read_link(&local).is_ok_and(|target| target == shared) {
+                return Ok(());
+            }
+        } else if local.exists() && (!shared.exists() || modified(&local) > modified(&shared)) {
+            copy_secret(&local, &shared)?;
+            info!("Moved the Claude login from {:?} into the shared store", local);
+        }
+
+        if let Some(parent) = local.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if is_link || local.exists() {
+            fs::remove_file(&local)?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&shared, &local)?;
+        #[cfg(not(unix))]
+        if shared.exists() {
+            fs::copy(&shared, &local)?;
+        }
+        Ok(())
+    }
+
+    /// Copy a login from another Clay project, a Claude home or a `.claude` directory.
+    ///
+    /// Returns the names of the credentials that were copied.
+    pub fn copy_auth_from(&self, source_dir: &Path) -> Result<Vec<String>> {
+        let mut copied = Vec::new();
+        let roots = [source_dir.join(".claude-home"), source_dir.to_path_buf()];
+
+        for name in CLAY_SECRETS {
+            let source = roots.iter()
+                .map(|root| root.join(".config").join("clay").join(name))
+                .find(|path| path.is_file());
+            if let Some(source) = source {
+                let contents = fs::read_to_string(&source)?;
+                let dest = self.get_credentials_dir().join(name);
+                if contents.starts_with(ENCRYPTED_PREFIX) {
+                    write_private_file(&dest, &contents)?;
+                } else {
+                    self.write_secret(&dest, contents.trim())?;
+                }
+                copied.push(name.to_string());
+            }
+        }
+
+        let cli_login = roots.iter()
+            .map(|root| root.join(".claude").join(CLI_CREDENTIALS))
+            .chain(std::iter::once(source_dir.join(CLI_CREDENTIALS)))
+            .find(|path| path.is_file());
+        if let Some(source) = cli_login {
+            copy_secret(&source, &self.get_cli_credentials_path())?;
+            self.link_shared_credentials()?;
+            copied.push(CLI_CREDENTIALS.to_string());
+        }
+
+        if copied.is_empty() {
+            return Err(ClaudeRelayError::Authentication(
+                format!("No Claude login found in {:?}", source_dir)
+            ));
+        }
+        info!("Authentication copied from {:?}: {}", source_dir, copied.join(", "));
+        Ok(copied)
+    }
+
+    /// Cipher for the tokens Clay saves, when `credentials.encryption_key` is set
+    fn credentials_cipher(&self) -> Result<Option<ChaCha20Poly1305>> {
+        let source = self.get_config()
+            .and_then(|c| c.credentials.as_ref().and_then(|c| c.encryption_key.clone()));
+        let Some(source) = source else {
+            return Ok(None);
+        };
+        let material = self.read_credential_source(&source).map_err(|reason| {
+            ClaudeRelayError::Authentication(format!("credentials.encryption_key: {}", reason))
+        })?;
+        // Any passphrase works; hashing it gives the 256-bit key
+        let key = Sha256::digest(material.as_bytes());
+        Ok(Some(ChaCha20Poly1305::new(&key)))
+    }
+
+    /// Read a saved token, decrypting it when it was written encrypted
+    pub(crate) fn read_secret(&self, path: &Path) -> Option<String> {
+        let contents = fs::read_to_string(path).ok()?;
+        let contents = contents.trim();
+        if contents.is_empty() {
+            return None;
+        }
+        let Some(encoded) = contents.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Some(contents.to_string());
+        };
+        let decrypted = match self.credentials_cipher() {
+            Ok(Some(cipher)) => decrypt(&cipher, encoded),
+            Ok(None) => Err("credentials.encryption_key is not set".to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        decrypted.map_err(|reason| warn!("Cannot decrypt {:?}: {}", path, reason)).ok()
+    }
+
+    /// Save a token with owner-only permissions, encrypted when a key is configured
+    pub(crate) fn write_secret(&self, path: &Path, secret: &str) -> Result<()> {
+        let contents = match self.credentials_cipher()? {
+            Some(cipher) => encrypt(&cipher, secret)?,
+            None => secret.to_string(),
+        };
+        write_private_file(path, &contents)
+    }
+
+    /// Re-save plain-text tokens encrypted once an encryption key is configured
+    pub fn encrypt_stored_secrets(&self) -> Result<()> {
+        if self.credentials_cipher()?.is_none() {
+            return Ok(());
+        }
+        for name in CLAY_SECRETS {
+            let path = self.get_credentials_dir().join(name);
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            if !contents.trim().is_empty() && !contents.starts_with(ENCRYPTED_PREFIX) {
+                self.write_secret(&path, contents.trim())?;
+                info!("Encrypted {:?}", path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the Claude home as a private directory, tighten the login files
+    /// in it and keep the project's installs and logins out of git
+    pub fn secure_claude_home(&self) -> Result<()> {
+        if !self.get_claude_home().exists() {
+            create_private_dir(self.get_claude_home())?;
+        }
+        for file in [self.project_cli_credentials_path(), self.get_claude_home().join(".claude.json")] {
+            let is_file = fs::symlink_metadata(&file).is_ok_and(|metadata| metadata.is_file());
+            if is_file && group_or_world_access(&file).is_some() {
+                set_mode(&file, 0o600)?;
+            }
+        }
+        if let Err(e) = self.encrypt_stored_secrets() {
+            warn!("Saved tokens were left unencrypted: {}", e);
+        }
+        self.ensure_gitignore()?;
+        for warning in self.check_credential_permissions() {
+            warn!("{}", warning);
+        }
+        Ok(())
+    }
+
+    /// Add the Claude home, account homes and Bun install to the project's
+    /// `.gitignore` when the project is a git repository
+    pub fn ensure_gitignore(&self) -> Result<()> {
+        let gitignore = self.get_base_dir().join(".gitignore");
+        if !gitignore.exists() && !in_git_repo(self.get_base_dir()) {
+            return Ok(());
+        }
+        let existing = fs::read_to_string(&gitignore).unwrap_or_default();
+        let listed = |entry: &str| existing.lines()
+            .map(|line| line.trim().trim_start_matches('/').trim_end_matches('/'))
+            .any(|line| line == entry.trim_end_matches('/'));
+        let missing: Vec<_> = GITIGNORE_ENTRIES.iter().filter(|entry| !listed(entry)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let mut contents = existing.clone();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str("# Clay: Claude CLI install, logins and local config\n");
+        for entry in missing {
+            contents.push_str(entry);
+            contents.push('\n');
+        }
+        fs::write(&gitignore, contents)?;
+        info!("Added Clay's directories to {:?}", gitignore);
+        Ok(())
+    }
+
+    /// Credential directories and files that other users on this machine can access
+    pub fn check_credential_permissions(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let dirs = [self.get_claude_home().to_path_buf(), self.get_credentials_dir()];
+        for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+            if let Some(mode) = group_or_world_access(dir) {
+                warnings.push(format!(
+                    "{} is accessible by other users (mode {:o}); run: chmod 700 {}",
+                    dir.display(), mode, dir.display()
+                ));
+            }
+        }
+        let files = CLAY_SECRETS.iter()
+            .map(|name| self.get_credentials_dir().join(name))
+            .chain(std::iter::once(self.get_cli_credentials_path()));
+        for file in files.filter(|file| file.is_file()) {
+            if let Some(mode) = group_or_world_access(&file) {
+                warnings.push(format!(
+                    "{} is readable by other users (mode {:o}); run: chmod 600 {}",
+                    file.display(), mode, file.display()
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Import the login of a Claude CLI installed on the system (`~/.claude`)
+    pub fn import_system_login(&self) -> Result<Vec<String>> {
+        let home = dirs::home_dir()
+            .ok_or_else(|| ClaudeRelayError::Authentication("Cannot find the home directory".into()))?;
+        self.copy_auth_from(&home).map_err(|_| ClaudeRelayError::Authentication(format!(
+            "No Claude login found in {:?}. On macOS the CLI keeps its login in the Keychain; \
+             run `claude setup-token` and log Clay in with the token instead.",
+            home.join(".claude")
+        )))
+    }
+}
+
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map(|home| home.join(rest)).unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn copy_secret(source: &Path, dest: &Path) -> Result<()> {
+    write_private_file(dest, &fs::read_to_string(source)?)
+}
+
+/// Create a directory only the current user can enter
+fn create_private_dir(path: &Path) -> Result<()> {
+    fs::create_dir_all(path)?;
+    set_mode(path, 0o700)
+}
+
+/// Write a file readable only by the current user, in a private directory
+pub(crate) fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        // Created private, so the secret is never readable by others, not even briefly
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // An existing file keeps its mode on open; tighten it before the secret goes in
+    set_mode(path, 0o600)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Permission bits other users have on a path, if any
+#[cfg(unix)]
+fn group_or_world_access(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+    (mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn group_or_world_access(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Whether the directory is inside a git work tree
+fn in_git_repo(dir: &Path) -> bool {
+    dir.ancestors().any(|ancestor| ancestor.join(".git").exists())
+}
+
+fn encrypt(cipher: &ChaCha20Poly1305, secret: &str) -> Result<String> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, secret.as_bytes())
+        .map_err(|_| ClaudeRelayError::Authentication("Failed to encrypt credential".into()))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(sealed)))
+}
+
+fn decrypt(cipher: &ChaCha20Poly1305, encoded: &str) -> std::result::Result<String, String> {
+    let sealed = BASE64.decode(encoded.trim()).map_err(|e| format!("not valid base64: {}", e))?;
+    if sealed.len() < 12 {
+        return Err("too short".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "wrong encryption key or corrupted file".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "decrypted data is not text".to_string())
+}
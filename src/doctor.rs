@@ -0,0 +1,284 @@
+use crate::auth::{API_KEY_ENV, OAUTH_TOKEN_ENV};
+use crate::config::McpServer;
+use crate::install::{find_executable, InstallStrategy};
+use crate::layers::is_secret_key;
+use crate::setup::ClaudeSetup;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// How long an MCP server URL gets to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Inherited variables that change how the Claude CLI behaves or which account it uses
+const CLI_ENV_PREFIXES: &[&str] = &["ANTHROPIC_", "CLAUDE_", "AWS_", "GOOGLE_", "VERTEX_", "NODE_OPTIONS"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "✅"),
+            CheckStatus::Warn => write!(f, "⚠️ "),
+            CheckStatus::Fail => write!(f, "❌"),
+        }
+    }
+}
+
+/// One `clay doctor` finding
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl DoctorCheck {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        DoctorCheck { name: name.into(), status: CheckStatus::Pass, detail: detail.into(), fix: None }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        DoctorCheck { name: name.into(), status: CheckStatus::Warn, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        DoctorCheck { name: name.into(), status: CheckStatus::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+}
+
+/// Everything `clay doctor` checked, for the terminal or `--json`
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub clay_version: String,
+    pub base_dir: String,
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    /// False when any check failed; warnings still pass
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.status != CheckStatus::Fail)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DoctorCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Clay {} doctor for {}", self.clay_version, self.base_dir)?;
+        for check in &self.checks {
+            writeln!(f, "{} {}: {}", check.status, check.name, check.detail)?;
+            if let Some(fix) = &check.fix {
+                writeln!(f, "   fix: {}", fix)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First line a binary prints for `--version`, run with the environment the CLI gets
+fn binary_version(path: &Path, env: Vec<(String, String)>) -> Result<String, String> {
+    let output = Command::new(path)
+        .arg("--version")
+        .env_clear()
+        .envs(env)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Whether a new file can be created in `dir`, or in the closest existing parent
+/// when `dir` doesn't exist yet
+fn writable(dir: &Path) -> Result<bool, String> {
+    let existing = dir.ancestors().find(|ancestor| ancestor.exists()).ok_or("no existing parent")?;
+    tempfile::NamedTempFile::new_in(existing).map_err(|e| e.to_string())?;
+    Ok(existing == dir)
+}
+
+/// `host:port` an MCP server URL points at
+fn url_address(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    Some(format!("{}:{}", url.host_str()?, url.port_or_known_default()?))
+}
+
+/// Inherited variables worth knowing about before they reach the CLI: credentials for
+/// other tools and settings that redirect the CLI. Clay's own credentials are excluded.
+pub fn leaked_env_vars(env: &[(String, String)]) -> Vec<String> {
+    let mut names: Vec<String> = env
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| ![API_KEY_ENV, OAUTH_TOKEN_ENV, "CLAUDE_RELAY"].contains(&name.as_str()))
+        .filter(|name| is_secret_key(name) || CLI_ENV_PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+        .cloned()
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+impl ClaudeSetup {
+    /// Check the installation, login, configuration, MCP servers, `port` and the child's
+    /// environment. Changes nothing.
+    pub async fn doctor(&self, port: u16) -> DoctorReport {
+        let mut checks = vec![self.check_bun(), self.check_cli(), self.check_claude_home()];
+        checks.push(match self.get_auth_status() {
+            Ok((true, detail)) => DoctorCheck::pass("auth", detail),
+            Ok((false, detail)) => DoctorCheck::fail("auth", detail, "Run clay --status to log in, or set auth: in clay.yaml"),
+            Err(e) => DoctorCheck::fail("auth", e.to_string(), "Run clay --status to log in, or set auth: in clay.yaml"),
+        });
+        checks.push(self.check_config_file());
+        checks.extend(self.check_mcp_servers().await);
+        checks.push(match std::net::TcpListener::bind(("0.0.0.0", port)) {
+            Ok(_) => DoctorCheck::pass("port", format!("{} is free", port)),
+            Err(e) => DoctorCheck::fail("port", format!("{} is unavailable: {}", port, e), "Stop whatever uses it, or pick another with --port or server.port"),
+        });
+        let leaked = leaked_env_vars(&self.get_claude_env());
+        checks.push(if leaked.is_empty() {
+            DoctorCheck::pass("environment", "No other credentials or Claude settings reach the CLI")
+        } else {
+            DoctorCheck::warn(
+                "environment",
+                format!("The CLI inherits {}", leaked.join(", ")),
+                "Unset them before starting Clay if the CLI shouldn't see them",
+            )
+        });
+
+        DoctorReport {
+            clay_version: env!("CARGO_PKG_VERSION").to_string(),
+            base_dir: self.get_base_dir().display().to_string(),
+            checks,
+        }
+    }
+
+    fn check_bun(&self) -> DoctorCheck {
+        if self.get_install_strategy() != InstallStrategy::Portable {
+            return DoctorCheck::pass("bun", format!("Not used with install.strategy {}", self.get_install_strategy()));
+        }
+        let bun = self.get_install_dir().join("bin").join("bun");
+        if !bun.exists() {
+            return DoctorCheck::fail("bun", format!("{} is missing", bun.display()), "Run clay --setup");
+        }
+        match binary_version(&bun, self.get_claude_env()) {
+            Ok(version) => DoctorCheck::pass("bun", format!("{} (version {})", bun.display(), version)),
+            Err(e) => DoctorCheck::fail("bun", format!("{} doesn't run: {}", bun.display(), e), "Delete .bun and run clay --setup"),
+        }
+    }
+
+    fn check_cli(&self) -> DoctorCheck {
+        let path = self.get_claude_path();
+        if !self.is_installed() {
+            let fix = match self.get_install_strategy() {
+                InstallStrategy::Path(_) => "Fix install.strategy or install the CLI there",
+                _ => "Run clay --setup",
+            };
+            return DoctorCheck::fail("claude_cli", format!("{} is missing", path.display()), fix);
+        }
+        if let Err(e) = binary_version(path, self.get_claude_env()) {
+            return DoctorCheck::fail("claude_cli", format!("{} doesn't run: {}", path.display(), e), "Run clay --reinstall");
+        }
+        match self.check_claude_version() {
+            Ok(Some(version)) => DoctorCheck::pass(
+                "claude_cli",
+                format!("{} ({}, version {})", path.display(), self.get_install_strategy(), version),
+            ),
+            Ok(None) => DoctorCheck::warn("claude_cli", format!("{} runs but reports no version", path.display()), "Run clay --reinstall"),
+            Err(e) => DoctorCheck::fail("claude_cli", e.to_string(), "Run clay --upgrade, or change claude.version"),
+        }
+    }
+
+    fn check_claude_home(&self) -> DoctorCheck {
+        let home = self.get_claude_home();
+        match writable(home) {
+            Ok(true) => DoctorCheck::pass("claude_home", format!("{} is writable", home.display())),
+            Ok(false) => DoctorCheck::pass("claude_home", format!("{} will be created on first run", home.display())),
+            Err(e) => DoctorCheck::fail(
+                "claude_home",
+                format!("{} is not writable: {}", home.display(), e),
+                format!("Make it writable by this user: chown -R $(id -u) {0} && chmod u+rwx {0}", home.display()),
+            ),
+        }
+    }
+
+    fn check_config_file(&self) -> DoctorCheck {
+        let fix = "Run clay --validate-config for details";
+        match self.validate_config() {
+            Ok(issues) if !issues.is_empty() => DoctorCheck::fail("config", issues.join("; "), fix),
+            Ok(_) => {
+                let warnings = self.config_warnings();
+                if warnings.is_empty() {
+                    DoctorCheck::pass("config", "clay.yaml is valid")
+                } else {
+                    DoctorCheck::warn("config", warnings.join("; "), fix)
+                }
+            }
+            Err(e) => DoctorCheck::fail("config", e.to_string(), fix),
+        }
+    }
+
+    async fn check_mcp_servers(&self) -> Vec<DoctorCheck> {
+        let Some(mcp) = self.get_config().and_then(|config| config.mcp.clone()) else {
+            return Vec::new();
+        };
+        let search_path = self.get_claude_env().into_iter().find(|(name, _)| name == "PATH").map(|(_, path)| path);
+        let mut names: Vec<_> = mcp.servers.keys().cloned().collect();
+        names.sort();
+        let mut checks = Vec::new();
+        for name in names {
+            let check_name = format!("mcp:{}", name);
+            checks.push(self.check_mcp_server(check_name, &name, &mcp.servers[&name], search_path.as_deref().map(OsStr::new)).await);
+        }
+        checks
+    }
+
+    async fn check_mcp_server(&self, check: String, name: &str, server: &McpServer, search_path: Option<&OsStr>) -> DoctorCheck {
+        if let Some(command) = server.command.as_deref().filter(|_| server.is_command()) {
+            let path = Path::new(command);
+            let found = if path.components().count() > 1 {
+                Some(self.get_base_dir().join(path)).filter(|path| path.exists())
+            } else {
+                find_executable(command, search_path)
+            };
+            return match found {
+                Some(path) => DoctorCheck::pass(check, format!("{} found at {}", command, path.display())),
+                None => DoctorCheck::fail(
+                    check,
+                    format!("{} is not on PATH", command),
+                    format!("Install {} or fix mcp.servers.{}.command", command, name),
+                ),
+            };
+        }
+        let Some(url) = server.url.as_deref() else {
+            return DoctorCheck::fail(check, "No command or url", format!("Set mcp.servers.{}.command or url", name));
+        };
+        let Some(address) = url_address(url) else {
+            return DoctorCheck::fail(check, format!("{} is not a valid URL", url), format!("Fix mcp.servers.{}.url", name));
+        };
+        match tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect(&address)).await {
+            Ok(Ok(_)) => DoctorCheck::pass(check, format!("{} is reachable", url)),
+            Ok(Err(e)) => DoctorCheck::fail(check, format!("{} is unreachable: {}", url, e), format!("Start the server or fix mcp.servers.{}.url", name)),
+            Err(_) => DoctorCheck::fail(
+                check,
+                format!("{} timed out after {}s", url, CONNECT_TIMEOUT.as_secs()),
+                format!("Check the network path to {}, including HTTPS_PROXY and NO_PROXY", address),
+            ),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::error::{ClaudeRelayError, Result};
+use futures_util::StreamExt;
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Attempts per download before giving up
+pub const DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// Bun's checksum list, published next to every release zip
+pub const SHASUMS_FILE: &str = "SHASUMS256.txt";
+
+/// Lowercase hex SHA-256 of a file
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Fail unless `bytes`, read from `name`, hash to `expected`
+pub fn check_sha256(bytes: &[u8], expected: &str, name: &str) -> Result<()> {
+    let actual: String = Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect();
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(checksum_mismatch(name, expected, &actual));
+    }
+    Ok(())
+}
+
+fn checksum_mismatch(name: &str, expected: &str, actual: &str) -> ClaudeRelayError {
+    ClaudeRelayError::Setup(format!("Checksum mismatch for {}: expected SHA-256 {}, got {}", name, expected.trim(), actual))
+}
+
+/// Hash of `file` in a `SHASUMS256.txt` (`<hex>  <file>` per line)
+pub fn find_checksum(shasums: &str, file: &str) -> Option<String> {
+    shasums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file).then(|| hash.to_lowercase())
+    })
+}
+
+/// Fail, and delete the file, unless it hashes to `expected`
+pub fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        let _ = fs::remove_file(path);
+        return Err(checksum_mismatch(&path.display().to_string(), expected, &actual));
+    }
+    Ok(())
+}
+
+/// Check an npm `dist.integrity` value (`sha512-<base64>`); other algorithms are left to npm
+pub fn verify_integrity(path: &Path, integrity: &str) -> Result<()> {
+    use base64::Engine;
+    let Some(expected) = integrity.strip_prefix("sha512-") else {
+        warn!("Not checking {} against unsupported integrity '{}'", path.display(), integrity);
+        return Ok(());
+    };
+    let mut hasher = Sha512::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    let actual = base64::engine::general_purpose::STANDARD.encode(hasher.finalize());
+    if actual != expected {
+        let _ = fs::remove_file(path);
+        return Err(ClaudeRelayError::Setup(format!(
+            "Integrity mismatch for {}: expected sha512-{}, got sha512-{}",
+            path.display(),
+            expected,
+            actual
+        )));
+    }
+    Ok(())
+}
+
+/// Downloads with retries, resume of partial files and a progress line on a terminal.
+/// reqwest's default client honors HTTPS_PROXY, HTTP_PROXY, ALL_PROXY and NO_PROXY.
+pub struct Downloader {
+    client: reqwest::Client,
+    progress: bool,
+    retry_delay: Duration,
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Downloader::new(io::stderr().is_terminal())
+    }
+}
+
+impl Downloader {
+    pub fn new(progress: bool) -> Self {
+        Downloader { client: reqwest::Client::new(), progress, retry_delay: Duration::from_secs(1) }
+    }
+
+    /// Wait this long before the first retry, doubling each time
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Fetch a small text file, such as a checksum list
+    pub async fn fetch_text(&self, url: &str) -> Result<String> {
+        Ok(self.client.get(url).send().await?.error_for_status()?.text().await?)
+    }
+
+    /// Download `url` to `dest`, continuing from `<dest>.part` when an earlier attempt was
+    /// cut off, and check it against `sha256` when given
+    pub async fn download(&self, url: &str, dest: &Path, sha256: Option<&str>) -> Result<()> {
+        let part = part_path(dest);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.fetch_with_retries(url, &part).await?;
+        if let Some(expected) = sha256 {
+            verify_sha256(&part, expected)?;
+        }
+        fs::rename(&part, dest)?;
+        Ok(())
+    }
+
+    /// Download `url` into `dir` and return its contents once they hash to `sha256`.
+    /// The bytes checked are the bytes returned, so the file can't be swapped in between.
+    pub async fn download_verified(&self, url: &str, dir: &Path, sha256: &str) -> Result<Vec<u8>> {
+        let name = url.rsplit('/').next().unwrap_or(url);
+        let part = part_path(&dir.join(name));
+        self.fetch_with_retries(url, &part).await?;
+        let bytes = fs::read(&part)?;
+        let _ = fs::remove_file(&part);
+        check_sha256(&bytes, sha256, url)?;
+        Ok(bytes)
+    }
+
+    async fn fetch_with_retries(&self, url: &str, part: &Path) -> Result<()> {
+        let mut delay = self.retry_delay;
+        for attempt in 1..=DOWNLOAD_ATTEMPTS {
+            match self.fetch(url, part).await {
+                Ok(()) => break,
+                Err(e) if attempt < DOWNLOAD_ATTEMPTS => {
+                    warn!("Download of {} failed (attempt {}/{}): {}; retrying", url, attempt, DOWNLOAD_ATTEMPTS, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => return Err(ClaudeRelayError::Setup(format!("Failed to download {}: {}", url, e))),
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch(&self, url: &str, part: &Path) -> Result<()> {
+        let offset = fs::metadata(part).map(|metadata| metadata.len()).unwrap_or(0);
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await?;
+        // The partial file already holds everything
+        if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(());
+        }
+        let response = response.error_for_status()?;
+        let resumed = offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+        if resumed {
+            info!("Resuming {} at byte {}", url, offset);
+        }
+        let mut done = if resumed { offset } else { 0 };
+        let total = response.content_length().map(|length| length + done);
+        let mut file = OpenOptions::new().create(true).write(true).append(resumed).truncate(!resumed).open(part)?;
+        let name = url.rsplit('/').next().unwrap_or(url);
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk)?;
+            done += chunk.len() as u64;
+            if self.progress {
+                print_progress(name, done, total);
+            }
+        }
+        if self.progress {
+            eprintln!();
+        }
+        Ok(())
+    }
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+fn print_progress(name: &str, done: u64, total: Option<u64>) {
+    const WIDTH: u64 = 30;
+    let megabytes = |bytes: u64| bytes as f64 / 1_048_576.0;
+    match total.filter(|total| *total > 0) {
+        Some(total) => {
+            let filled = (done.min(total) * WIDTH / total) as usize;
+            eprint!(
+                "\r  {} [{}{}] {:.1}/{:.1} MB",
+                name,
+                "#".repeat(filled),
+                " ".repeat(WIDTH as usize - filled),
+                megabytes(done),
+                megabytes(total)
+            );
+        }
+        None => eprint!("\r  {} {:.1} MB", name, megabytes(done)),
+    }
+    let _ = io::stderr().flush();
+}
@@ -29,12 +29,54 @@ pub enum ClaudeRelayError {
     #[error("Invalid configuration: {0}")]
     Config(String),
     
+    #[error("Conversation error: {0}")]
+    Conversation(String),
+    
+    #[error("Usage limit reached: {message}")]
+    UsageLimit {
+        message: String,
+        /// When the CLI says the limit resets, if it said so
+        reset_at: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    
+    #[error("Claude is overloaded: {0}")]
+    Overloaded(String),
+    
+    #[error("Context too long: {0}")]
+    ContextTooLong(String),
+    
+    #[error("Invalid model: {0}")]
+    InvalidModel(String),
+    
+    #[error("MCP server failure: {0}")]
+    McpFailure(String),
+    
     #[error("{0}")]
     Other(String),
 }
 
 pub type Result<T> = std::result::Result<T, ClaudeRelayError>;
 
+/// Back-off suggested to clients while Claude is overloaded
+pub const OVERLOADED_RETRY_SECS: u64 = 30;
+
+/// Back-off suggested to clients after an MCP server failed
+pub const MCP_FAILURE_RETRY_SECS: u64 = 10;
+
+impl ClaudeRelayError {
+    /// Seconds a client should wait before retrying, for failures that pass on their own
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ClaudeRelayError::UsageLimit { reset_at: Some(reset_at), .. } => {
+                Some((*reset_at - chrono::Utc::now()).num_seconds().max(1) as u64)
+            }
+            ClaudeRelayError::Overloaded(_) => Some(OVERLOADED_RETRY_SECS),
+            ClaudeRelayError::McpFailure(_) => Some(MCP_FAILURE_RETRY_SECS),
+            _ => None,
+        }
+    }
+}
+
 impl From<String> for ClaudeRelayError {
     fn from(s: String) -> Self {
         ClaudeRelayError::Other(s)
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Token accounting reported by the Claude CLI
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+/// Typed event produced while Claude works on a message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaudeEvent {
+    /// A piece of the assistant's reply text
+    TextDelta { text: String },
+    /// Claude started calling a tool
+    ToolUseStarted { id: String, name: String, input: Value },
+    /// A tool call returned
+    ToolUseFinished { id: String, output: String, is_error: bool },
+    /// Token usage for the whole turn
+    Usage(TokenUsage),
+    /// The turn finished; always the last event
+    Result {
+        text: String,
+        session_id: Option<String>,
+        is_error: bool,
+        duration_ms: Option<u64>,
+        total_cost_usd: Option<f64>,
+    },
+}
+
+/// Turns the CLI's `stream-json` output lines into `ClaudeEvent`s
+#[derive(Debug, Default)]
+pub struct EventParser {
+    saw_partial_text: bool,
+}
+
+impl EventParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse one output line; lines that carry nothing of interest yield no events
+    pub fn parse_line(&mut self, line: &str) -> Vec<ClaudeEvent> {
+        let Ok(event) = serde_json::from_str::<Value>(line.trim()) else {
+            return Vec::new();
+        };
+
+        match event.get("type").and_then(|v| v.as_str()) {
+            Some("stream_event") => self.parse_partial(&event),
+            Some("assistant") => self.parse_assistant(&event),
+            Some("user") => Self::parse_tool_results(&event),
+            Some("result") => Self::parse_result(&event),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Incremental deltas, emitted when the CLI runs with partial messages enabled
+    fn parse_partial(&mut self, event: &Value) -> Vec<ClaudeEvent> {
+        let delta = &event["event"]["delta"];
+        if delta["type"] == "text_delta" {
+            if let Some(text) = delta["text"].as_str() {
+                self.saw_partial_text = true;
+                return vec![ClaudeEvent::TextDelta { text: text.to_string() }];
+            }
+        }
+        Vec::new()
+    }
+
+    fn parse_assistant(&mut self, event: &Value) -> Vec<ClaudeEvent> {
+        let mut events = Vec::new();
+        for block in content_blocks(&event["message"]) {
+            match block["type"].as_str() {
+                // Whole text blocks repeat what partial deltas already delivered
+                Some("text") if !self.saw_partial_text => {
+                    if let Some(text) = block["text"].as_str() {
+                        events.push(ClaudeEvent::TextDelta { text: text.to_string() });
+                    }
+                }
+                Some("tool_use") => events.push(ClaudeEvent::ToolUseStarted {
+                    id: block["id"].as_str().unwrap_or_default().to_string(),
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                    input: block["input"].clone(),
+                }),
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn parse_tool_results(event: &Value) -> Vec<ClaudeEvent> {
+        content_blocks(&event["message"])
+            .iter()
+            .filter(|block| block["type"] == "tool_result")
+            .map(|block| ClaudeEvent::ToolUseFinished {
+                id: block["tool_use_id"].as_str().unwrap_or_default().to_string(),
+                output: tool_result_text(&block["content"]),
+                is_error: block["is_error"].as_bool().unwrap_or(false),
+            })
+            .collect()
+    }
+
+    fn parse_result(event: &Value) -> Vec<ClaudeEvent> {
+        let mut events = Vec::new();
+        if let Ok(usage) = serde_json::from_value::<TokenUsage>(event["usage"].clone()) {
+            events.push(ClaudeEvent::Usage(usage));
+        }
+        events.push(ClaudeEvent::Result {
+            text: event["result"].as_str().unwrap_or_default().to_string(),
+            session_id: event["session_id"].as_str().map(|s| s.to_string()),
+            is_error: event["is_error"].as_bool().unwrap_or(false),
+            duration_ms: event["duration_ms"].as_u64(),
+            total_cost_usd: event["total_cost_usd"].as_f64(),
+        });
+        events
+    }
+}
+
+fn content_blocks(message: &Value) -> Vec<Value> {
+    message["content"].as_array().cloned().unwrap_or_default()
+}
+
+/// Tool results are either a plain string or a list of content blocks
+fn tool_result_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
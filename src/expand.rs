@@ -0,0 +1,106 @@
+use crate::layers;
+use serde_yaml::Value;
+use tracing::warn;
+
+/// Expand `${VAR}`, `${VAR:-default}`, `${VAR:?message}` and `$$` in one string.
+///
+/// Substituted values and defaults are inserted as they are, never expanded
+/// again, so a variable that refers to itself cannot loop. A bare `$` that
+/// doesn't start one of these forms is kept, so shell snippets pass through.
+pub fn expand_str<F>(input: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(dollar) = rest.find('$') {
+        output.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+
+        if let Some(after_escape) = after.strip_prefix('$') {
+            output.push('$');
+            rest = after_escape;
+            continue;
+        }
+        let Some(body_start) = after.strip_prefix('{') else {
+            output.push('$');
+            rest = after;
+            continue;
+        };
+        let Some(end) = body_start.find('}') else {
+            return Err(format!("unterminated ${{ in \"{}\"", input));
+        };
+        let body = &body_start[..end];
+        rest = &body_start[end + 1..];
+
+        let (name, operator) = match body.find(':') {
+            Some(colon) => (&body[..colon], Some(&body[colon + 1..])),
+            None => (body, None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid variable name in ${{{}}}", body));
+        }
+        let value = lookup(name).filter(|value| !value.is_empty());
+
+        match (value, operator) {
+            (Some(value), _) => output.push_str(&value),
+            (None, None) => warn!("Environment variable {} is not set; using an empty string", name),
+            (None, Some(operator)) => {
+                if let Some(default) = operator.strip_prefix('-') {
+                    output.push_str(default);
+                } else if let Some(message) = operator.strip_prefix('?') {
+                    return Err(if message.is_empty() {
+                        format!("environment variable {} is required", name)
+                    } else {
+                        format!("{} ({})", message, name)
+                    });
+                } else {
+                    return Err(format!("unsupported expansion ${{{}}}; use :- or :?", body));
+                }
+            }
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Expand every string in a parsed YAML document, collecting errors as
+/// `field.path: reason` so each one names the setting it came from.
+/// `is_text` tells, by path, which settings keep substituted values as strings.
+pub fn expand_value<F, T>(value: &mut Value, path: &str, lookup: &F, is_text: &T, errors: &mut Vec<String>)
+where
+    F: Fn(&str) -> Option<String>,
+    T: Fn(&str) -> bool,
+{
+    match value {
+        Value::String(text) => match expand_str(text, lookup) {
+            // Typed as if the value had been written in place, so `port: ${PORT:-8080}` is a number
+            Ok(expanded) if expanded != *text => *value = layers::scalar(&expanded, is_text(path)),
+            Ok(_) => {}
+            Err(reason) => errors.push(format!("{}: {}", display_path(path), reason)),
+        },
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                expand_value(item, &format!("{}[{}]", path, index), lookup, is_text, errors);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, item) in mapping.iter_mut() {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+                };
+                let child = if path.is_empty() { key } else { format!("{}.{}", path, key) };
+                expand_value(item, &child, lookup, is_text, errors);
+            }
+        }
+        Value::Tagged(tagged) => expand_value(&mut tagged.value, path, lookup, is_text, errors),
+        _ => {}
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "(document)" } else { path }
+}
@@ -0,0 +1,325 @@
+use crate::error::{ClaudeRelayError, Result};
+use crate::process::{ClaudeProcess, ConversationState};
+use crate::server::ChatMessage;
+use crate::setup::ClaudeSetup;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::warn;
+
+const EXPORT_VERSION: u32 = 1;
+
+const USER_PREFIX: &str = "User: ";
+const CLAUDE_PREFIX: &str = "Claude: ";
+const SYSTEM_PREFIX: &str = "System Context: ";
+
+/// Formats a conversation can be exported to and imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Full-fidelity JSON including undo states and their timestamps
+    Json,
+    /// Human-readable Markdown transcript
+    Markdown,
+    /// OpenAI `messages` array, replayable against any OpenAI-compatible endpoint
+    OpenAi,
+}
+
+impl ExportFormat {
+    /// Guess the format from a file extension (`.md` for Markdown, `.json` for JSON)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("md") | Some("markdown") => Some(ExportFormat::Markdown),
+            Some("json") => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of serialized conversation data
+    pub fn detect(data: &str) -> Result<Self> {
+        let trimmed = data.trim_start();
+        if trimmed.starts_with('[') {
+            return Ok(ExportFormat::OpenAi);
+        }
+        if trimmed.starts_with('{') {
+            let value: serde_json::Value = serde_json::from_str(trimmed)?;
+            if value.get("history").is_some() {
+                return Ok(ExportFormat::Json);
+            }
+            if value.get("messages").is_some() {
+                return Ok(ExportFormat::OpenAi);
+            }
+            return Err(ClaudeRelayError::Conversation(
+                "JSON data contains neither 'history' nor 'messages'".into()
+            ));
+        }
+        Ok(ExportFormat::Markdown)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json | ExportFormat::OpenAi => "application/json",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ClaudeRelayError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "openai" | "messages" => Ok(ExportFormat::OpenAi),
+            other => Err(ClaudeRelayError::Conversation(
+                format!("Unknown conversation format '{}' (expected json, markdown or openai)", other)
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Json => write!(f, "json"),
+            ExportFormat::Markdown => write!(f, "markdown"),
+            ExportFormat::OpenAi => write!(f, "openai"),
+        }
+    }
+}
+
+/// Serializable snapshot of a `ClaudeProcess` conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationExport {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub history: Vec<String>,
+    #[serde(default)]
+    pub states: Vec<ConversationState>,
+    #[serde(default)]
+    pub last_undone_history: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OpenAiMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiRequestBody {
+    messages: Vec<ChatMessage>,
+}
+
+impl ConversationExport {
+    /// Snapshot the conversation held by a process
+    pub fn from_process(process: &ClaudeProcess, session_id: Option<&str>) -> Self {
+        ConversationExport {
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            session_id: session_id.map(|s| s.to_string()),
+            history: process.get_conversation_history().to_vec(),
+            states: process.get_conversation_states().to_vec(),
+            last_undone_history: process.get_last_undone_history().map(|h| h.to_vec()),
+        }
+    }
+
+    fn from_history(history: Vec<String>) -> Self {
+        ConversationExport {
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            session_id: None,
+            history,
+            states: Vec::new(),
+            last_undone_history: None,
+        }
+    }
+
+    /// Serialize the conversation in the requested format
+    pub fn render(&self, format: ExportFormat) -> Result<String> {
+        match format {
+            ExportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ExportFormat::Markdown => Ok(self.to_markdown()),
+            ExportFormat::OpenAi => Ok(serde_json::to_string_pretty(&self.to_openai_messages())?),
+        }
+    }
+
+    /// Parse serialized conversation data; the format is detected when not given
+    pub fn parse(data: &str, format: Option<ExportFormat>) -> Result<Self> {
+        let format = match format {
+            Some(format) => format,
+            None => ExportFormat::detect(data)?,
+        };
+
+        match format {
+            ExportFormat::Json => {
+                let export: ConversationExport = serde_json::from_str(data)?;
+                if export.version > EXPORT_VERSION {
+                    return Err(ClaudeRelayError::Conversation(format!(
+                        "Unsupported export version {} (this build reads up to {})",
+                        export.version, EXPORT_VERSION
+                    )));
+                }
+                Ok(export)
+            }
+            ExportFormat::Markdown => Ok(Self::from_history(parse_markdown(data))),
+            ExportFormat::OpenAi => {
+                let trimmed = data.trim_start();
+                let messages: Vec<ChatMessage> = if trimmed.starts_with('{') {
+                    serde_json::from_str::<OpenAiRequestBody>(trimmed)?.messages
+                } else {
+                    serde_json::from_str(trimmed)?
+                };
+                Ok(Self::from_history(openai_messages_to_history(&messages)))
+            }
+        }
+    }
+
+    /// Conversation as OpenAI chat messages
+    pub fn to_chat_messages(&self) -> Vec<ChatMessage> {
+        self.to_openai_messages()
+            .into_iter()
+            .map(|m| ChatMessage {
+                role: m.role,
+                content: Some(m.content),
+                tool_calls: None,
+                tool_call_id: None,
+            })
+            .collect()
+    }
+
+    fn to_openai_messages(&self) -> Vec<OpenAiMessage> {
+        self.history
+            .iter()
+            .map(|entry| {
+                let (role, content) = split_history_entry(entry);
+                OpenAiMessage {
+                    role: role.to_string(),
+                    content: content.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::from("# Claude Conversation\n\n");
+        if let Some(session_id) = &self.session_id {
+            out.push_str(&format!("- Session: `{}`\n", session_id));
+        }
+        out.push_str(&format!("- Exported: {}\n", self.exported_at.to_rfc3339()));
+
+        for entry in &self.history {
+            let (role, content) = split_history_entry(entry);
+            let heading = match role {
+                "system" => "System",
+                "assistant" => "Assistant",
+                _ => "User",
+            };
+            out.push_str(&format!("\n## {}\n\n{}\n", heading, escape_headings(content.trim_end())));
+        }
+
+        out
+    }
+}
+
+/// Split a `ClaudeProcess` history entry into an OpenAI role and its content
+fn split_history_entry(entry: &str) -> (&'static str, &str) {
+    if let Some(content) = entry.strip_prefix(SYSTEM_PREFIX) {
+        ("system", content)
+    } else if let Some(content) = entry.strip_prefix(CLAUDE_PREFIX) {
+        ("assistant", content)
+    } else if let Some(content) = entry.strip_prefix(USER_PREFIX) {
+        ("user", content)
+    } else {
+        ("user", entry)
+    }
+}
+
+fn history_entry(role: &str, content: &str) -> Option<String> {
+    match role {
+        "system" => Some(format!("{}{}", SYSTEM_PREFIX, content)),
+        "user" => Some(format!("{}{}", USER_PREFIX, content)),
+        "assistant" => Some(format!("{}{}", CLAUDE_PREFIX, content)),
+        _ => None,
+    }
+}
+
+fn openai_messages_to_history(messages: &[ChatMessage]) -> Vec<String> {
+    let mut history = Vec::new();
+    for message in messages {
+        let Some(content) = &message.content else {
+            continue;
+        };
+        match history_entry(&message.role, content) {
+            Some(entry) => history.push(entry),
+            None => warn!("Skipping '{}' message during conversation import", message.role),
+        }
+    }
+    history
+}
+
+/// The role a Markdown export heading starts, if `line` is one
+fn role_heading(line: &str) -> Option<&'static str> {
+    match line.trim_end() {
+        "## System" => Some("system"),
+        "## User" => Some("user"),
+        "## Assistant" => Some("assistant"),
+        _ => None,
+    }
+}
+
+/// Whether a line is a role heading behind any number of escaping backslashes
+fn is_escaped_heading(line: &str) -> bool {
+    role_heading(line.trim_start_matches('\\')).is_some()
+}
+
+/// Backslash content lines that would read as role headings; `parse_markdown` removes one again
+fn escape_headings(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| if is_escaped_heading(line) { format!("\\{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_markdown(data: &str) -> Vec<String> {
+    let mut history = Vec::new();
+    let mut current: Option<(&str, Vec<&str>)> = None;
+
+    for line in data.lines() {
+        match role_heading(line) {
+            Some(role) => {
+                if let Some((role, lines)) = current.replace((role, Vec::new())) {
+                    history.extend(history_entry(role, lines.join("\n").trim()));
+                }
+            }
+            None => {
+                if let Some((_, lines)) = current.as_mut() {
+                    let escaped = line.starts_with('\\') && is_escaped_heading(line);
+                    lines.push(if escaped { &line[1..] } else { line });
+                }
+            }
+        }
+    }
+    if let Some((role, lines)) = current {
+        history.extend(history_entry(role, lines.join("\n").trim()));
+    }
+
+    history
+}
+
+/// Export the conversation held by a process in the requested format
+pub fn export_process(process: &ClaudeProcess, session_id: Option<&str>, format: ExportFormat) -> Result<String> {
+    ConversationExport::from_process(process, session_id).render(format)
+}
+
+/// Create a new process whose conversation is seeded from exported data
+pub fn import_process(setup: Arc<ClaudeSetup>, data: &str, format: Option<ExportFormat>) -> Result<ClaudeProcess> {
+    let export = ConversationExport::parse(data, format)?;
+    ClaudeProcess::with_history(setup, export.history, export.states, export.last_undone_history)
+}
@@ -0,0 +1,486 @@
+use crate::cache::{cached_claude_tarball, BUN_RELEASES, NPM_REGISTRY};
+use crate::config::InstallConfig;
+use crate::credentials::expand_home;
+use crate::download::{find_checksum, Downloader, SHASUMS_FILE};
+use crate::error::{ClaudeRelayError, Result};
+use crate::setup::ClaudeSetup;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+/// Oldest Claude CLI with the flags Clay passes (`--output-format stream-json`, `--resume`, `setup-token`)
+pub const MIN_CLAUDE_VERSION: Version = Version { major: 1, minor: 0, patch: 0 };
+
+/// npm package of the Claude CLI
+pub const CLAUDE_PACKAGE: &str = "@anthropic-ai/claude-code";
+
+/// Where the Claude CLI comes from, set with `install.strategy` in clay.yaml
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum InstallStrategy {
+    /// Bun and the CLI installed under the project, in `.bun`
+    #[default]
+    Portable,
+    /// `claude` from PATH; installed with npm into `.bun` when missing, without Bun
+    System,
+    /// This `claude` binary, never installed by Clay
+    Path(PathBuf),
+}
+
+impl From<String> for InstallStrategy {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "portable" => InstallStrategy::Portable,
+            "system" => InstallStrategy::System,
+            _ => InstallStrategy::Path(PathBuf::from(value)),
+        }
+    }
+}
+
+impl From<InstallStrategy> for String {
+    fn from(strategy: InstallStrategy) -> Self {
+        strategy.to_string()
+    }
+}
+
+impl fmt::Display for InstallStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallStrategy::Portable => write!(f, "portable"),
+            InstallStrategy::System => write!(f, "system"),
+            InstallStrategy::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A `major.minor.patch` CLI version; pre-release and build suffixes are ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// First version number in `claude --version` output, e.g. `1.0.58 (Claude Code)`
+    pub fn find_in(text: &str) -> Option<Version> {
+        text.split_whitespace().find_map(|word| word.trim_start_matches('v').parse().ok())
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        let core = text.split(['-', '+']).next().unwrap_or_default();
+        let parts: Vec<&str> = core.split('.').collect();
+        let number = |index: usize| -> std::result::Result<u64, String> {
+            match parts.get(index) {
+                Some(part) => part.parse().map_err(|_| format!("invalid version '{}'", text)),
+                None => Ok(0),
+            }
+        };
+        if parts.len() > 3 || parts[0].is_empty() {
+            return Err(format!("invalid version '{}'", text));
+        }
+        Ok(Version { major: number(0)?, minor: number(1)?, patch: number(2)? })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A version pin from `claude.version`: an exact version or an npm-style range such as
+/// `^1.0`, `~1.2.3`, `1.x`, `>=1.0.0 <2.0.0` or `latest`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionReq {
+    text: String,
+    /// Every range must match; empty matches any version
+    ranges: Vec<(Version, Option<Version>)>,
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.ranges.iter().all(|(low, high)| version >= low && high.is_none_or(|high| *version < high))
+    }
+
+    /// The pin as npm and Bun understand it after `@`
+    pub fn npm_spec(&self) -> String {
+        self.text.split([',', ' ']).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Parse one comparator into a `[low, high)` range
+    fn parse_range(term: &str) -> std::result::Result<(Version, Option<Version>), String> {
+        let invalid = || format!("invalid version requirement '{}'", term);
+        let op_len = term.find(|c: char| c.is_ascii_alphanumeric() || c == '*').ok_or_else(invalid)?;
+        let (op, rest) = term.split_at(op_len);
+        let core = rest.trim_start_matches('v').split(['-', '+']).next().unwrap_or_default();
+        // Components before the first wildcard; `1.2` and `1.2.x` both give two
+        let mut parts = Vec::new();
+        for part in core.split('.') {
+            if matches!(part, "x" | "X" | "*") {
+                break;
+            }
+            parts.push(part.parse::<u64>().map_err(|_| invalid())?);
+        }
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+        let at = |index: usize| parts.get(index).copied().unwrap_or(0);
+        let low = Version { major: at(0), minor: at(1), patch: at(2) };
+        let bump = |index: usize| match index {
+            0 => Version { major: low.major + 1, minor: 0, patch: 0 },
+            1 => Version { major: low.major, minor: low.minor + 1, patch: 0 },
+            _ => Version { major: low.major, minor: low.minor, patch: low.patch + 1 },
+        };
+        // The next version past the given components, e.g. `1.2` covers everything below 1.3.0
+        let partial_high = match parts.len() {
+            0 => None,
+            len => Some(bump(len - 1)),
+        };
+        let zero = Version { major: 0, minor: 0, patch: 0 };
+        Ok(match op {
+            "" | "=" => (low, partial_high),
+            ">=" => (low, None),
+            ">" => (partial_high.ok_or_else(invalid)?, None),
+            "<" => (zero, Some(low)),
+            "<=" => (zero, partial_high),
+            "~" => (low, partial_high.map(|_| bump(parts.len().clamp(1, 2) - 1))),
+            // The first non-zero component given may not change
+            "^" => {
+                let fixed = parts.iter().position(|part| *part != 0).unwrap_or(parts.len().saturating_sub(1));
+                (low, partial_high.map(|_| bump(fixed)))
+            }
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+impl TryFrom<String> for VersionReq {
+    type Error = String;
+
+    fn try_from(text: String) -> std::result::Result<Self, Self::Error> {
+        let trimmed = text.trim();
+        let ranges = match trimmed {
+            "" | "*" | "x" | "latest" => Vec::new(),
+            _ => trimmed
+                .split([',', ' '])
+                .filter(|term| !term.is_empty())
+                .map(VersionReq::parse_range)
+                .collect::<std::result::Result<_, _>>()?,
+        };
+        Ok(VersionReq { text: if trimmed.is_empty() { "latest".to_string() } else { trimmed.to_string() }, ranges })
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        VersionReq::try_from(text.to_string())
+    }
+}
+
+impl From<VersionReq> for String {
+    fn from(req: VersionReq) -> Self {
+        req.text
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// The Claude CLI a setup runs, for `--status` and `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaudeBinary {
+    pub path: PathBuf,
+    pub strategy: String,
+    pub installed: bool,
+    pub version: Option<String>,
+    /// `claude.version` from clay.yaml
+    pub pinned: Option<String>,
+}
+
+/// First executable called `name` in the directories of a PATH-style list
+pub fn find_executable(name: &str, search_path: Option<&OsStr>) -> Option<PathBuf> {
+    let search_path = search_path.map(OsStr::to_os_string).or_else(|| env::var_os("PATH"))?;
+    env::split_paths(&search_path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// The binary a strategy points at, before anything is installed. `System` falls back to
+/// an npm install in `install_dir` when PATH has no `claude`.
+pub fn resolve_claude_path(strategy: &InstallStrategy, base_dir: &Path, install_dir: &Path) -> PathBuf {
+    match strategy {
+        InstallStrategy::Portable => install_dir.join("bin").join("claude"),
+        InstallStrategy::System => find_executable("claude", None)
+            .unwrap_or_else(|| npm_claude_path(install_dir)),
+        InstallStrategy::Path(path) => base_dir.join(expand_home(&path.to_string_lossy())),
+    }
+}
+
+/// Where `npm install --prefix` puts the CLI's launcher
+fn npm_claude_path(install_dir: &Path) -> PathBuf {
+    install_dir.join("node_modules").join(".bin").join("claude")
+}
+
+impl ClaudeSetup {
+    /// Install whatever the strategy needs: Bun and the CLI for `portable`, the CLI
+    /// through npm for `system` when PATH has none, nothing for an explicit path
+    pub async fn install_cli(&self) -> Result<()> {
+        match self.get_install_strategy() {
+            InstallStrategy::Portable => {
+                self.install_bun().await?;
+                self.install_claude()
+            }
+            InstallStrategy::System if self.is_installed() && !self.needs_reinstall() => {
+                info!("Using system Claude CLI at {:?}", self.get_claude_path());
+                Ok(())
+            }
+            InstallStrategy::System => self.install_claude_package(&self.claude_package_spec()?, false),
+            InstallStrategy::Path(_) if self.is_installed() => Ok(()),
+            InstallStrategy::Path(_) => Err(ClaudeRelayError::Setup(format!(
+                "install.strategy points at {}, which is not an executable; fix the path or use `portable`",
+                self.get_claude_path().display()
+            ))),
+        }
+    }
+
+    /// `claude.version` from clay.yaml
+    pub fn get_claude_version_req(&self) -> Option<VersionReq> {
+        self.get_config().and_then(|config| config.claude_version())
+    }
+
+    /// Whether Clay installed the CLI itself (in `.bun`) and may replace it
+    pub fn is_managed(&self) -> bool {
+        match self.get_install_strategy() {
+            InstallStrategy::Portable => true,
+            InstallStrategy::System => self.get_claude_path() == npm_claude_path(self.get_install_dir()),
+            InstallStrategy::Path(_) => false,
+        }
+    }
+
+    /// True when Clay's own CLI no longer satisfies `claude.version`, e.g. after the pin changed
+    pub fn needs_reinstall(&self) -> bool {
+        let Some(req) = self.get_claude_version_req() else {
+            return false;
+        };
+        self.is_managed() && self.is_installed() && self.claude_version().is_ok_and(|version| !req.matches(&version))
+    }
+
+    /// `install:` from clay.yaml, or its defaults
+    pub fn get_install_config(&self) -> InstallConfig {
+        self.get_config().and_then(|config| config.install.clone()).unwrap_or_default()
+    }
+
+    /// Downloader for Bun and the CLI; shows progress unless quiet or not on a terminal
+    pub(crate) fn downloader(&self) -> Downloader {
+        Downloader::new(!self.is_quiet() && io::stderr().is_terminal())
+    }
+
+    /// URL of a Bun release file, from `install.bun_mirror` or GitHub
+    pub fn bun_release_url(&self, file: &str) -> String {
+        let base = self.get_install_config().bun_mirror.unwrap_or_else(|| BUN_RELEASES.to_string());
+        format!("{}/{}", base.trim_end_matches('/'), file)
+    }
+
+    /// `install.npm_registry`, or the public registry
+    pub fn npm_registry(&self) -> String {
+        let registry = self.get_install_config().npm_registry.unwrap_or_else(|| NPM_REGISTRY.to_string());
+        registry.trim_end_matches('/').to_string()
+    }
+
+    /// `install.sha256` entry for a file name
+    pub fn pinned_sha256(&self, file: &str) -> Option<String> {
+        self.get_install_config().sha256.get(file).cloned()
+    }
+
+    /// Expected SHA-256 of a Bun release file: pinned in clay.yaml, or from the
+    /// release's SHASUMS256.txt
+    pub(crate) async fn bun_checksum(&self, downloader: &Downloader, file: &str) -> Result<String> {
+        if let Some(sha256) = self.pinned_sha256(file) {
+            return Ok(sha256);
+        }
+        let url = self.bun_release_url(SHASUMS_FILE);
+        let shasums = downloader.fetch_text(&url).await
+            .map_err(|e| ClaudeRelayError::Setup(format!("Failed to fetch Bun checksums from {}: {}; pin one under install.sha256", url, e)))?;
+        find_checksum(&shasums, file).ok_or_else(|| ClaudeRelayError::Setup(format!(
+            "{} has no checksum for {}; pin one under install.sha256",
+            url, file
+        )))
+    }
+
+    /// `install.cache_dir`, resolved against the project directory
+    pub fn get_install_cache(&self) -> Option<PathBuf> {
+        let cache_dir = self.get_config()?.install.as_ref()?.cache_dir.clone()?;
+        Some(self.get_base_dir().join(expand_home(&cache_dir)))
+    }
+
+    /// The package to install, with the pinned version when there is one, or the
+    /// newest matching tarball from the install cache
+    pub(crate) fn claude_package_spec(&self) -> Result<String> {
+        let req = self.get_claude_version_req();
+        if let Some(cache_dir) = self.get_install_cache() {
+            let (tarball, _) = cached_claude_tarball(&cache_dir, req.as_ref())?;
+            self.verify_cached(&tarball)?;
+            return Ok(tarball.to_string_lossy().into_owned());
+        }
+        Ok(match req {
+            Some(req) => format!("{}@{}", CLAUDE_PACKAGE, req.npm_spec()),
+            None => CLAUDE_PACKAGE.to_string(),
+        })
+    }
+
+    /// Install `spec` with Bun for `portable`, or with the system's npm into the install
+    /// directory for `system`; `force` reinstalls even when it is up to date
+    pub(crate) fn install_claude_package(&self, spec: &str, force: bool) -> Result<()> {
+        let install_dir = self.get_install_dir();
+        let mut cmd = if self.get_install_strategy() == InstallStrategy::Portable {
+            let mut cmd = Command::new(install_dir.join("bin").join("bun"));
+            cmd.args(["install", "-g", spec])
+                .env("BUN_INSTALL", install_dir)
+                .env("PATH", format!("{}:{}", install_dir.join("bin").display(), env::var("PATH").unwrap_or_default()));
+            cmd
+        } else {
+            let Some(npm) = find_executable("npm", None) else {
+                return Err(ClaudeRelayError::Setup(
+                    "No claude or npm on PATH; install Node.js or use install.strategy: portable".to_string()
+                ));
+            };
+            fs::create_dir_all(install_dir)?;
+            let mut cmd = Command::new(npm);
+            cmd.args(["install", "--prefix"]).arg(install_dir).arg(spec);
+            if self.get_install_cache().is_some() {
+                cmd.arg("--offline");
+            }
+            cmd
+        };
+        if force {
+            cmd.arg("--force");
+        }
+        if self.get_install_cache().is_none() {
+            cmd.args(["--registry", &self.npm_registry()]);
+        }
+        info!("Installing {}...", spec);
+        let output = cmd.output()
+            .map_err(|e| ClaudeRelayError::Setup(format!("Failed to install Claude: {}", e)))?;
+        if !output.status.success() {
+            return Err(ClaudeRelayError::Setup(format!(
+                "Failed to install Claude: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        info!("Claude installed successfully at {:?}", self.get_claude_path());
+        Ok(())
+    }
+
+    /// Move Clay's CLI to the newest release `claude.version` allows (`--upgrade`), or
+    /// install the same pin again from scratch (`--reinstall`). Returns the versions
+    /// before and after.
+    pub async fn upgrade_cli(&self, reinstall: bool) -> Result<(Option<Version>, Version)> {
+        if !self.is_managed() {
+            return Err(ClaudeRelayError::Setup(format!(
+                "Clay doesn't manage the Claude CLI at {} (install.strategy: {}); update it with the tool that installed it",
+                self.get_claude_path().display(),
+                self.get_install_strategy()
+            )));
+        }
+        let before = self.is_installed().then(|| self.claude_version().ok()).flatten();
+        if self.get_install_strategy() == InstallStrategy::Portable {
+            self.install_bun().await?;
+        }
+        let spec = match self.get_claude_version_req() {
+            _ if self.get_install_cache().is_some() => self.claude_package_spec()?,
+            Some(req) => format!("{}@{}", CLAUDE_PACKAGE, req.npm_spec()),
+            None => format!("{}@latest", CLAUDE_PACKAGE),
+        };
+        self.install_claude_package(&spec, reinstall)?;
+        Ok((before, self.claude_version()?))
+    }
+
+    /// Version reported by `claude --version`
+    pub fn claude_version(&self) -> Result<Version> {
+        let output = Command::new(self.get_claude_path())
+            .arg("--version")
+            .env_clear()
+            .envs(self.get_claude_env())
+            .output()
+            .map_err(|e| ClaudeRelayError::Setup(format!("Failed to run {}: {}", self.get_claude_path().display(), e)))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Version::find_in(&stdout).ok_or_else(|| ClaudeRelayError::Setup(format!(
+            "Could not read a version from `{} --version`: {}",
+            self.get_claude_path().display(),
+            stdout.trim()
+        )))
+    }
+
+    /// Fail when the CLI is older than `MIN_CLAUDE_VERSION` or outside `claude.version`;
+    /// `None` when its version can't be read
+    pub fn check_claude_version(&self) -> Result<Option<Version>> {
+        let version = match self.claude_version() {
+            Ok(version) => version,
+            Err(e) => {
+                warn!("{}", e);
+                return Ok(None);
+            }
+        };
+        if version < MIN_CLAUDE_VERSION {
+            return Err(ClaudeRelayError::Setup(format!(
+                "Claude CLI {} at {} is too old; Clay needs {} or newer (upgrade it or use install.strategy: portable)",
+                version,
+                self.get_claude_path().display(),
+                MIN_CLAUDE_VERSION
+            )));
+        }
+        if let Some(req) = self.get_claude_version_req().filter(|req| !req.matches(&version)) {
+            let fix = if self.is_managed() { "run clay --upgrade" } else { "install a matching version or use install.strategy: portable" };
+            return Err(ClaudeRelayError::Setup(format!(
+                "Claude CLI {} at {} doesn't match claude.version {}; {}",
+                version,
+                self.get_claude_path().display(),
+                req,
+                fix
+            )));
+        }
+        Ok(Some(version))
+    }
+
+    /// Which CLI this setup runs and how it was chosen
+    pub fn get_claude_binary(&self) -> ClaudeBinary {
+        let installed = self.is_installed();
+        ClaudeBinary {
+            path: self.get_claude_path().to_path_buf(),
+            strategy: self.get_install_strategy().to_string(),
+            installed,
+            version: installed.then(|| self.claude_version().ok()).flatten().map(|v| v.to_string()),
+            pinned: self.get_claude_version_req().map(|req| req.to_string()),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::{ClaudeRelayError, Result};
+use crate::schema;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Per-developer overrides next to clay.yaml, kept out of version control
+pub const LOCAL_CONFIG_FILE: &str = "clay.local.yaml";
+
+/// Prefix of environment variables that override settings, e.g. `CLAY_SERVER__PORT`
+pub const ENV_PREFIX: &str = "CLAY_";
+
+/// Environment variable selecting a profile when `--profile` isn't given
+pub const PROFILE_ENV: &str = "CLAY_PROFILE";
+
+/// Top-level keys handled while layering, never passed on to `Config`
+pub const LAYER_KEYS: &[&str] = &["include", "profiles"];
+
+/// A configuration file that contributed to the merged config, kept to point errors at it
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// File name shown in messages; `None` for clay.yaml itself
+    pub label: Option<String>,
+    pub path: Option<PathBuf>,
+    pub text: String,
+}
+
+/// Read a config file and the fragments it includes, fragments first so the file wins.
+/// `stack` holds the files being read, to reject include cycles.
+pub fn read_file(path: &Path, label: Option<String>, sources: &mut Vec<Source>, stack: &mut Vec<PathBuf>) -> Result<Value> {
+    let display = label.clone().unwrap_or_else(|| "clay.yaml".to_string());
+    let canonical = path.canonicalize()
+        .map_err(|e| ClaudeRelayError::Config(format!("{}: {}", display, e)))?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack.iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+        return Err(ClaudeRelayError::Config(format!("include cycle: {}", chain.join(" -> "))));
+    }
+
+    let text = fs::read_to_string(path)?;
+    let mut value: Value = serde_yaml::from_str(&text)
+        .map_err(|e| ClaudeRelayError::Config(format!("{}: {}", display, e)))?;
+    sources.push(Source { label, path: Some(canonical.clone()), text });
+    if value.is_null() {
+        return Ok(Value::Mapping(Mapping::new()));
+    }
+    let Some(mapping) = value.as_mapping_mut() else {
+        return Err(ClaudeRelayError::Config(format!("{}: expected a mapping at the top level", display)));
+    };
+
+    let includes: Vec<String> = match mapping.remove("include") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(file)) => vec![file],
+        Some(Value::Sequence(files)) => files.into_iter()
+            .map(|file| match file {
+                Value::String(file) => Ok(file),
+                _ => Err(ClaudeRelayError::Config(format!("{}: include entries must be file paths", display))),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => return Err(ClaudeRelayError::Config(format!("{}: include must be a path or a list of paths", display))),
+    };
+
+    stack.push(canonical.clone());
+    let dir = canonical.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Mapping(Mapping::new());
+    for include in includes {
+        let fragment = read_file(&dir.join(&include), Some(include), sources, stack)?;
+        merge(&mut merged, fragment);
+    }
+    stack.pop();
+
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Deep-merge `overlay` into `base`: mappings merge key by key, anything else
+/// (including lists) replaces. Empty values such as `server:` change nothing.
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (_, Value::Null) => {}
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None if value.is_null() => {}
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Take `profiles:` out of the config and merge the selected one over the rest
+pub fn apply_profile(value: &mut Value, profile: Option<&str>) -> Result<()> {
+    let profiles = value.as_mapping_mut().and_then(|m| m.remove("profiles"));
+    let Some(name) = profile else {
+        return Ok(());
+    };
+    let mut profiles = match profiles {
+        Some(Value::Mapping(profiles)) => profiles,
+        _ => Mapping::new(),
+    };
+    match profiles.remove(name) {
+        Some(overlay) => {
+            merge(value, overlay);
+            Ok(())
+        }
+        None => {
+            let known: Vec<&str> = profiles.keys().filter_map(|k| k.as_str()).collect();
+            Err(ClaudeRelayError::Config(if known.is_empty() {
+                format!("profile '{}' not found; clay.yaml defines no profiles", name)
+            } else {
+                format!("profile '{}' not found; available profiles: {}", name, known.join(", "))
+            }))
+        }
+    }
+}
+
+/// Settings from `CLAY_SECTION__KEY=value` variables. Only variables naming a
+/// top-level key of `json_schema` count, so unrelated `CLAY_*` variables are left alone.
+pub fn env_overrides(vars: &[(String, String)], json_schema: &serde_json::Value) -> Vec<(String, String)> {
+    let mut overrides: Vec<(String, String)> = vars.iter()
+        .filter(|(name, _)| name != PROFILE_ENV)
+        .filter_map(|(name, value)| {
+            let segments: Vec<&str> = name.strip_prefix(ENV_PREFIX)?.split("__").collect();
+            let path = schema::key_path(json_schema, &segments);
+            let known = json_schema["properties"].get(&path[0]).is_some();
+            (!LAYER_KEYS.contains(&path[0].as_str()) && known).then(|| (path.join("."), value.clone()))
+        })
+        .collect();
+    // Environment order is arbitrary; keep the result stable
+    overrides.sort();
+    overrides
+}
+
+/// `raw` read as a plain YAML scalar, so `3001` is a number and `true` a boolean,
+/// unless it is for a setting that takes `text`
+pub fn scalar(raw: &str, text: bool) -> Value {
+    match serde_yaml::from_str::<Value>(raw) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_))) if !text => value,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+/// Set the dotted `path` to a scalar written as it would be in YAML
+pub fn set_path(root: &mut Value, path: &str, raw: &str, text: bool) {
+    let scalar = scalar(raw, text);
+    let mut node = root;
+    for segment in path.split('.') {
+        if !node.is_mapping() {
+            *node = Value::Mapping(Mapping::new());
+        }
+        let Value::Mapping(mapping) = node else { unreachable!() };
+        node = mapping.entry(Value::from(segment)).or_insert(Value::Null);
+    }
+    *node = scalar;
+}
+
+const SECRET_KEY_WORDS: &[&str] = &["token", "secret", "password", "passwd", "key", "authorization", "cookie", "webhook"];
+
+/// Whether a setting or variable name such as `admin_token` or `DATABASE_PASSWORD` holds a secret
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEY_WORDS.iter().any(|word| key.contains(word))
+}
+
+/// Mask values that look secret: strings under keys such as `admin_token`,
+/// `Authorization` or `DATABASE_PASSWORD`, and passwords inside URLs
+pub fn mask_secrets(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, item) in mapping.iter_mut() {
+                let secret = key.as_str().is_some_and(is_secret_key);
+                match item {
+                    Value::String(text) if secret && !text.is_empty() => *text = "********".to_string(),
+                    _ => mask_secrets(item),
+                }
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(mask_secrets),
+        Value::String(text) => *text = mask_url_password(text),
+        _ => {}
+    }
+}
+
+fn mask_url_password(text: &str) -> String {
+    let Some(scheme_end) = text.find("://") else {
+        return text.to_string();
+    };
+    let authority_start = scheme_end + 3;
+    let authority_end = text[authority_start..].find('/').map_or(text.len(), |i| authority_start + i);
+    let authority = &text[authority_start..authority_end];
+    let (Some(at), Some(colon)) = (authority.rfind('@'), authority.find(':')) else {
+        return text.to_string();
+    };
+    if colon > at {
+        return text.to_string();
+    }
+    format!("{}{}:********{}", &text[..authority_start], &authority[..colon], &text[authority_start + at..])
+}
@@ -4,12 +4,43 @@ pub mod config;
 pub mod auth;
 pub mod error;
 pub mod server;
+pub mod export;
+pub mod stream;
+pub mod pool;
+pub mod events;
+pub mod accounts;
+pub mod classify;
+pub mod monitor;
+pub mod credentials;
+pub mod expand;
+pub mod schema;
+pub mod layers;
+pub mod reload;
+pub mod install;
+pub mod cache;
+pub mod download;
+pub mod doctor;
+pub mod mcp;
+pub mod proxy;
 
-pub use setup::ClaudeSetup;
-pub use process::{ClaudeProcess, ConversationState};
-pub use config::Config;
+pub use setup::{ClaudeSetup, ClaudeSetupBuilder};
+pub use auth::{LoginResult, SetupTokenSession};
+pub use process::{ClaudeProcess, ConversationState, MessageStream};
+pub use config::{Config, ConfigOptions};
 pub use error::{ClaudeRelayError, Result};
 pub use server::start_server;
+pub use export::{ConversationExport, ExportFormat};
+pub use stream::{StreamHealth, StreamMonitor, StreamingSession};
+pub use pool::{PoolMetrics, ProcessPool};
+pub use events::{ClaudeEvent, TokenUsage};
+pub use accounts::{Account, AccountPool, AccountStatus};
+pub use classify::CliFailure;
+pub use monitor::{AuthHealth, AuthMonitor, AuthMonitorMetrics, AuthState};
+pub use reload::{ConfigReloadStatus, ConfigWatcher};
+pub use install::{ClaudeBinary, InstallStrategy, Version, VersionReq};
+pub use doctor::{CheckStatus, DoctorCheck, DoctorReport};
+pub use mcp::{McpClient, McpError, McpProbe, McpTransport, ProbeStatus};
+pub use proxy::McpProxy;
 
 pub fn new(base_dir: &str) -> Result<ClaudeSetup> {
     ClaudeSetup::new(base_dir)
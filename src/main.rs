@@ -1,6 +1,9 @@
 use anyhow::Result;
-use clap::Parser;
-use clay::{ClaudeProcess, ClaudeSetup, start_server};
+use clap::{Parser, Subcommand};
+use clay::{AccountPool, ClaudeProcess, ClaudeSetup, ConfigOptions, ConversationExport, ExportFormat, McpProxy, start_server};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tracing_subscriber::EnvFilter;
 
@@ -8,15 +11,33 @@ use tracing_subscriber::EnvFilter;
 #[command(name = "claude-relay")]
 #[command(about = "Claude Relay - OpenAI-compatible API server for Claude CLI", long_about = None)]
 struct Args {
-    #[arg(short, long, default_value = ".")]
+    #[command(subcommand)]
+    command: Option<Commands>,
+    
+    #[arg(short, long, default_value = ".", global = true)]
     dir: String,
     
-    #[arg(short, long, help = "Port to run the server on")]
+    #[arg(short, long, global = true, help = "Port to run the server on")]
     port: Option<u16>,
     
     #[arg(long, help = "Run setup to install Claude CLI")]
     setup: bool,
     
+    #[arg(long, value_name = "DIR", help = "Install from a cache made by --bundle instead of downloading (sets install.cache_dir)")]
+    from: Option<String>,
+    
+    #[arg(long, value_name = "DIR", help = "Download Bun and the Claude CLI into DIR for offline installs with --from")]
+    bundle: Option<String>,
+    
+    #[arg(long, value_name = "ASSET", help = "Bun platform to add to --bundle, e.g. bun-linux-x64 (repeatable; default: this machine)")]
+    platform: Vec<String>,
+    
+    #[arg(long, help = "Upgrade Clay's Claude CLI to the newest release claude.version allows")]
+    upgrade: bool,
+    
+    #[arg(long, help = "Reinstall Clay's Claude CLI from scratch")]
+    reinstall: bool,
+    
     #[arg(short, long, help = "Send a message to Claude")]
     message: Option<String>,
     
@@ -26,39 +47,199 @@ struct Args {
     #[arg(long, help = "Force regenerate clay.yaml configuration file")]
     init_config: bool,
     
-    #[arg(long, help = "Path to clay.yaml configuration file")]
+    #[arg(long, global = true, help = "Path to clay.yaml configuration file")]
     config: Option<String>,
     
     #[arg(long, help = "Validate clay.yaml configuration")]
     validate_config: bool,
+
+    #[arg(long, requires = "validate_config", help = "With --validate-config, start or connect to each MCP server and list its tools, resources and prompts")]
+    deep: bool,
+    
+    #[arg(long, help = "Print the JSON Schema for clay.yaml")]
+    print_schema: bool,
+    
+    #[arg(long, help = "Print the merged configuration with secrets masked")]
+    print_config: bool,
+    
+    #[arg(long, global = true, help = "Apply this entry of profiles: in clay.yaml (default: $CLAY_PROFILE)")]
+    profile: Option<String>,
+    
+    #[arg(long, help = "Seed the conversation from an exported file (JSON, Markdown or OpenAI messages)")]
+    import: Option<String>,
+    
+    #[arg(long, help = "Write the conversation to this file (after --message, or converting --import)")]
+    export: Option<String>,
+    
+    #[arg(long, help = "Export format: json, markdown or openai (default: from file extension)")]
+    export_format: Option<String>,
+    
+    #[arg(long, help = "Never prompt for login; authenticate through /admin/auth instead")]
+    headless: bool,
+    
+    #[arg(long, help = "Use this account from clay.yaml for --status (login) and --message")]
+    account: Option<String>,
+    
+    #[arg(long, value_name = "DIR", num_args = 0..=1, help = "Import a Claude login from ~/.claude, or from another Clay project or Claude home")]
+    import_login: Option<Option<String>>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Check the installation, login, clay.yaml, MCP servers, port and environment
+    Doctor {
+        #[arg(long, help = "Print the report as JSON, e.g. for a support ticket")]
+        json: bool,
+    },
+    /// Bridge stdio to an HTTP or WebSocket MCP server; mcp.json runs this for the Claude CLI
+    McpProxy {
+        #[arg(long, default_value = "mcp", help = "Server name from clay.yaml, for logs")]
+        name: String,
+        #[arg(long, help = "URL of the MCP server")]
+        url: String,
+        #[arg(long, default_value = "http", value_parser = ["http", "ws"], help = "Transport of the MCP server")]
+        transport: String,
+        #[arg(long, default_value_t = 30, help = "Seconds each request may wait for an answer")]
+        timeout: u64,
+        #[arg(long, help = "Exit instead of reconnecting when the connection or session is lost")]
+        no_reconnect: bool,
+    },
+}
+
+fn write_export(conversation: &ConversationExport, path: &str, format: Option<&str>) -> Result<()> {
+    let format = match format {
+        Some(format) => format.parse::<ExportFormat>()?,
+        None => ExportFormat::from_path(Path::new(path)).unwrap_or(ExportFormat::Json),
+    };
+    fs::write(path, conversation.render(format)?)?;
+    println!("💾 Conversation exported to {} ({})", path, format);
+    Ok(())
+}
+
+fn print_claude_binary(setup: &ClaudeSetup) {
+    let binary = setup.get_claude_binary();
+    println!("  Claude installed: {}", binary.installed);
+    println!(
+        "  Claude CLI: {} ({}, version {}{})",
+        binary.path.display(),
+        binary.strategy,
+        binary.version.as_deref().unwrap_or("unknown"),
+        binary.pinned.map(|pin| format!(", pinned {}", pin)).unwrap_or_default()
+    );
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
+    let args = Args::parse();
+    
+    // Initialize tracing; the proxy's stdout carries MCP messages, so it logs to stderr
+    let subscriber = tracing_subscriber::fmt()
         .with_env_filter(
             EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| EnvFilter::new("info"))
-        )
-        .init();
+        );
+    if let Some(Commands::McpProxy { name, url, transport, timeout, no_reconnect }) = &args.command {
+        subscriber.with_writer(std::io::stderr).init();
+        let proxy = McpProxy {
+            name: name.clone(),
+            url: url.clone(),
+            websocket: transport == "ws",
+            headers: McpProxy::headers_from_env()?,
+            timeout: std::time::Duration::from_secs((*timeout).max(1)),
+            reconnect: !no_reconnect,
+            retry_delay: std::time::Duration::from_secs(1),
+        };
+        proxy.run(tokio::io::stdin(), tokio::io::stdout()).await?;
+        return Ok(());
+    }
+    subscriber.init();
     
-    let args = Args::parse();
+    if args.print_schema {
+        println!("{}", serde_json::to_string_pretty(&clay::schema::json_schema())?);
+        return Ok(());
+    }
+    
+    // Command-line flags are the last configuration layer
+    let mut overrides = Vec::new();
+    if let Some(port) = args.port {
+        overrides.push(("server.port".to_string(), port.to_string()));
+    }
+    if let Some(dir) = &args.from {
+        // Relative to where clay runs, unlike paths in clay.yaml
+        let dir = fs::canonicalize(dir).map_err(|e| anyhow::anyhow!("--from {}: {}", dir, e))?;
+        overrides.push(("install.cache_dir".to_string(), dir.to_string_lossy().into_owned()));
+    }
+    let config_options = ConfigOptions {
+        file: args.config.as_ref().map(PathBuf::from),
+        profile: args.profile.clone(),
+        overrides,
+    };
     
     // Handle init-config command (force regenerate clay.yaml)
     if args.init_config {
-        let claude_setup = Arc::new(ClaudeSetup::new(&args.dir)?);
+        let claude_setup = Arc::new(ClaudeSetup::builder(&args.dir).config_options(config_options).build()?);
         claude_setup.init_config()?;
         return Ok(());
     }
     
-    // Create Claude setup (this will load clay.yaml if present)
-    let claude_setup = Arc::new(ClaudeSetup::new(&args.dir)?);
+    // Create Claude setup, generating a sample clay.yaml on first run
+    let claude_setup = Arc::new(
+        ClaudeSetup::builder(&args.dir)
+            .config_options(config_options)
+            .generate_config(true)
+            // --validate-config and doctor explain a broken clay.yaml instead of failing on it
+            .require_config(!args.validate_config && !matches!(args.command, Some(Commands::Doctor { .. })))
+            .clay_binary(std::env::current_exe()?)
+            .build()?
+    );
+    
+    if args.print_config {
+        let report = claude_setup.check_config_layers()?;
+        for warning in &report.warnings {
+            eprintln!("⚠️  {}", warning);
+        }
+        print!("{}", report.config.to_masked_yaml()?);
+        return Ok(());
+    }
+    
+    if let Some(Commands::Doctor { json }) = &args.command {
+        let port = args.port.unwrap_or_else(|| claude_setup.get_server_port());
+        let report = claude_setup.doctor(port).await;
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report);
+        }
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    // Build an offline install cache on a connected machine
+    if let Some(dir) = &args.bundle {
+        let platforms = if args.platform.is_empty() {
+            vec![clay::cache::current_bun_asset()?.to_string()]
+        } else {
+            args.platform.clone()
+        };
+        let bundle = claude_setup.bundle(Path::new(dir), &platforms).await?;
+        println!("📦 Bundled Claude CLI {} into {}:", bundle.claude_version, bundle.dir.display());
+        for file in &bundle.files {
+            println!("   {}", file.display());
+        }
+        println!("Install from it with: clay --setup --from {}", dir);
+        return Ok(());
+    }
     
     // Handle config validation
     if args.validate_config {
         println!("Validating clay.yaml configuration...");
-        let issues = claude_setup.validate_mcp_servers()?;
+        let issues = claude_setup.validate_config()?;
+        let warnings = claude_setup.config_warnings();
+        for warning in &warnings {
+            println!("⚠️  {}", warning);
+        }
         if issues.is_empty() {
             println!("✅ Configuration is valid!");
             if let Some(config) = claude_setup.get_config() {
@@ -89,7 +270,52 @@ async fn main() -> Result<()> {
             for issue in &issues {
                 println!("  - {}", issue);
             }
+            std::process::exit(1);
         }
+        if args.deep {
+            println!("\nChecking MCP servers...");
+            let probes = claude_setup.probe_mcp_servers().await;
+            if probes.is_empty() {
+                println!("No MCP servers configured");
+            } else {
+                print!("{}", clay::mcp::probe_table(&probes));
+                if probes.iter().any(|probe| probe.status != clay::ProbeStatus::Ok) {
+                    println!("\n❌ Some MCP servers failed");
+                    std::process::exit(1);
+                }
+                println!("\n✅ All MCP servers answered");
+            }
+        }
+        return Ok(());
+    }
+    
+    // Convert an exported conversation between formats without talking to Claude
+    if args.message.is_none() {
+        if let Some(export_path) = &args.export {
+            let Some(import_path) = &args.import else {
+                anyhow::bail!("--export requires either --message or --import");
+            };
+            let conversation = ConversationExport::parse(&fs::read_to_string(import_path)?, None)?;
+            write_export(&conversation, export_path, args.export_format.as_deref())?;
+            return Ok(());
+        }
+    }
+    
+    // The account to log in or talk through; the server spreads requests over all of them
+    let account_setup = match &args.account {
+        Some(name) => Arc::new(claude_setup.get_account(name)?),
+        None => claude_setup.clone(),
+    };
+    
+    // Reuse an existing login instead of logging in again
+    if let Some(source) = &args.import_login {
+        let copied = match source {
+            Some(dir) => account_setup.copy_auth_from(Path::new(dir))?,
+            None => account_setup.import_system_login()?,
+        };
+        println!("🔑 Imported {}", copied.join(", "));
+        let (authenticated, auth_detail) = account_setup.get_auth_status()?;
+        println!("  Authenticated: {} ({})", authenticated, auth_detail);
         return Ok(());
     }
     
@@ -101,36 +327,84 @@ async fn main() -> Result<()> {
         return Ok(());
     }
     
+    if args.upgrade || args.reinstall {
+        let (before, after) = claude_setup.upgrade_cli(args.reinstall).await?;
+        match before {
+            Some(before) if before != after => println!("Claude CLI upgraded from {} to {}", before, after),
+            _ if args.reinstall => println!("Claude CLI {} reinstalled", after),
+            _ => println!("Claude CLI {} is up to date", after),
+        }
+        return Ok(());
+    }
+    
     // Check if Claude is installed and install automatically if needed
     if !claude_setup.is_installed() {
         println!("Claude CLI is not installed. Installing automatically...");
         claude_setup.setup_with_mcp().await?;
         println!("Claude CLI installed successfully!");
+    } else if claude_setup.needs_reinstall() {
+        // claude.version changed since the CLI was installed
+        println!("Claude CLI doesn't match claude.version. Reinstalling...");
+        claude_setup.setup_with_mcp().await?;
+        println!("Claude CLI installed successfully!");
     }
     
     // Send message if provided
     if let Some(message) = args.message {
+        let claude_setup = account_setup.clone();
+        claude_setup.check_claude_version()?;
         // Check authentication and prompt if needed
         if !claude_setup.check_authentication()? {
             claude_setup.complete_oauth_flow()?;
         }
         
-        let mut process = ClaudeProcess::new(claude_setup.clone())?;
+        let mut process = match &args.import {
+            Some(path) => clay::export::import_process(claude_setup.clone(), &fs::read_to_string(path)?, None)?,
+            None => ClaudeProcess::new(claude_setup.clone())?,
+        };
         let response = process.send_message(&message)?;
         println!("{}", response);
+        
+        if let Some(export_path) = &args.export {
+            let conversation = ConversationExport::from_process(&process, None);
+            write_export(&conversation, export_path, args.export_format.as_deref())?;
+        }
         return Ok(());
     }
     
     // Status mode
+    if args.status && args.account.is_none() {
+        if let Some(accounts) = claude_setup.get_accounts_config() {
+            println!("Claude Relay Status:");
+            println!("  Installation directory: {}", args.dir);
+            print_claude_binary(&claude_setup);
+            println!("  Accounts ({:?}):", accounts.strategy);
+            for account in AccountPool::new(claude_setup.clone()).status() {
+                println!("    {}: authenticated: {} ({})", account.name, account.authenticated, account.auth_detail);
+            }
+            println!();
+            println!("Log an account in with: clay --account <name> --status");
+            return Ok(());
+        }
+    }
+    
     if args.status {
-        let authenticated = claude_setup.check_authentication()?;
+        let claude_setup = account_setup.clone();
+        let (authenticated, auth_detail) = claude_setup.get_auth_status()?;
         
         println!("Claude Relay Status:");
         println!("  Installation directory: {}", args.dir);
-        println!("  Claude installed: {}", claude_setup.is_installed());
-        println!("  Authenticated: {}", authenticated);
+        print_claude_binary(&claude_setup);
+        println!("  Authenticated: {} ({})", authenticated, auth_detail);
+        for warning in claude_setup.check_credential_permissions() {
+            println!("  ⚠️  {}", warning);
+        }
         
-        if !authenticated {
+        if !authenticated && claude_setup.get_auth_config().is_some() {
+            // A broken `auth:` source is fixed in clay.yaml or the environment, not by logging in
+            eprintln!("The credential configured under auth: in clay.yaml is unavailable.");
+            std::process::exit(1);
+        } else if !authenticated {
             println!();
             match claude_setup.complete_oauth_flow() {
                 Ok(()) => {
@@ -150,12 +424,21 @@ async fn main() -> Result<()> {
         return Ok(());
     }
     
-    // Default behavior: Start the OpenAI-compatible server
-    // Check authentication first
-    if !claude_setup.check_authentication()? {
-        println!("Authentication required before starting server.");
-        claude_setup.complete_oauth_flow()?;
-        println!("Authentication complete!");
+    // Default behavior: start the server, once the CLI is known to support the flags Clay relies on
+    claude_setup.check_claude_version()?;
+    if args.account.is_some() {
+        println!("--account only applies to --status and --message; the server uses every account.");
+    }
+    // Accounts are logged in one at a time with --account; the server skips those without credentials
+    if claude_setup.get_accounts_config().is_none() && !claude_setup.check_authentication()? {
+        if args.headless || !std::io::stdin().is_terminal() {
+            println!("Claude is not authenticated. Starting in unauthenticated mode.");
+            println!("Call POST /admin/auth/start for a login URL, then POST the code to /admin/auth/complete.");
+        } else {
+            println!("Authentication required before starting server.");
+            claude_setup.complete_oauth_flow()?;
+            println!("Authentication complete!");
+        }
     }
     
     println!("Starting Claude Relay OpenAI-compatible API server...");
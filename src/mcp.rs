@@ -0,0 +1,516 @@
+use crate::config::McpServer;
+use crate::error::ClaudeRelayError;
+use crate::setup::ClaudeSetup;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// MCP revision Clay speaks
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Variables the MCP SDK passes to stdio servers; everything else, credentials
+/// included, only reaches a server through its `env:`
+pub const SERVER_ENV_VARS: &[&str] = &["HOME", "LOGNAME", "PATH", "SHELL", "TERM", "USER"];
+
+/// Most stderr kept from a server, from the end
+const STDERR_LIMIT: usize = 4096;
+
+/// Why talking to an MCP server failed
+#[derive(Error, Debug)]
+pub enum McpError {
+    #[error("failed to start: {0}")]
+    Start(String),
+    #[error("exited: {0}")]
+    Exited(String),
+    #[error("unreachable: {0}")]
+    Connect(String),
+    #[error("protocol error: {0}")]
+    Protocol(String),
+}
+
+impl From<McpError> for ClaudeRelayError {
+    fn from(e: McpError) -> Self {
+        ClaudeRelayError::McpFailure(e.to_string())
+    }
+}
+
+/// A command server's pipes
+pub struct StdioServer {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    /// Copies stderr until the child closes it
+    stderr_task: Option<JoinHandle<()>>,
+}
+
+/// Where JSON-RPC messages go: a child's stdin and stdout, an HTTP endpoint
+/// (streamable HTTP, answering with JSON or an event stream) or a WebSocket
+pub enum McpTransport {
+    Stdio(Box<StdioServer>),
+    Http {
+        client: reqwest::Client,
+        url: String,
+        headers: Vec<(String, String)>,
+        session_id: Option<String>,
+        /// Messages from responses not yet received
+        pending: VecDeque<Value>,
+    },
+    WebSocket(Box<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>>),
+}
+
+impl McpTransport {
+    /// Start or connect to a server from clay.yaml. A command runs in `base_dir` with
+    /// `env` plus its own `env:`; its stderr is collected into `stderr`.
+    pub async fn connect(server: &McpServer, base_dir: &Path, env: &[(String, String)], stderr: Arc<Mutex<String>>) -> Result<Self, McpError> {
+        if server.is_command() {
+            let command = server.command.as_deref().unwrap_or_default();
+            let mut child = Command::new(command)
+                .args(&server.args)
+                .current_dir(base_dir)
+                .env_clear()
+                .envs(env.iter().cloned())
+                .envs(&server.env)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|e| McpError::Start(format!("{}: {}", command, e)))?;
+            let stderr_task = child.stderr.take().map(|child_stderr| {
+                tokio::spawn(async move {
+                    let mut lines = BufReader::new(child_stderr).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let mut buffer = stderr.lock().unwrap();
+                        buffer.push_str(&line);
+                        buffer.push('\n');
+                        if buffer.len() > STDERR_LIMIT {
+                            let cut = buffer.len() - STDERR_LIMIT;
+                            let cut = (cut..buffer.len()).find(|i| buffer.is_char_boundary(*i)).unwrap_or(0);
+                            buffer.drain(..cut);
+                        }
+                    }
+                })
+            });
+            let stdin = child.stdin.take().ok_or_else(|| McpError::Start("no stdin".to_string()))?;
+            let stdout = child.stdout.take().ok_or_else(|| McpError::Start("no stdout".to_string()))?;
+            return Ok(McpTransport::Stdio(Box::new(StdioServer { child, stdin, stdout: BufReader::new(stdout).lines(), stderr_task })));
+        }
+
+        let url = server.url.clone().unwrap_or_default();
+        let headers: Vec<(String, String)> = server.headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        if server.is_websocket() {
+            let (stream, _) = tokio_tungstenite::connect_async(websocket_request(&url, &headers)?)
+                .await
+                .map_err(|e| McpError::Connect(e.to_string()))?;
+            return Ok(McpTransport::WebSocket(Box::new(stream)));
+        }
+        Ok(McpTransport::Http { client: reqwest::Client::new(), url, headers, session_id: None, pending: VecDeque::new() })
+    }
+
+    pub async fn send(&mut self, message: &Value) -> Result<(), McpError> {
+        match self {
+            McpTransport::Stdio(server) => {
+                let stdin = &mut server.stdin;
+                let mut line = message.to_string();
+                line.push('\n');
+                stdin.write_all(line.as_bytes()).await.map_err(|e| McpError::Exited(e.to_string()))?;
+                stdin.flush().await.map_err(|e| McpError::Exited(e.to_string()))
+            }
+            McpTransport::Http { client, url, headers, session_id, pending } => {
+                let mut request = client
+                    .post(url.as_str())
+                    .header("Accept", "application/json, text/event-stream")
+                    .json(message);
+                for (name, value) in headers.iter() {
+                    request = request.header(name, value);
+                }
+                if let Some(id) = session_id.as_deref() {
+                    request = request.header("Mcp-Session-Id", id);
+                }
+                let response = request.send().await.map_err(|e| McpError::Connect(e.to_string()))?;
+                if let Some(id) = response.headers().get("mcp-session-id").and_then(|id| id.to_str().ok()) {
+                    *session_id = Some(id.to_string());
+                }
+                let status = response.status();
+                let event_stream = response
+                    .headers()
+                    .get("content-type")
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.starts_with("text/event-stream"));
+                let body = response.text().await.map_err(|e| McpError::Connect(e.to_string()))?;
+                if !status.is_success() {
+                    return Err(McpError::Connect(format!("HTTP {}: {}", status, body.trim())));
+                }
+                if body.trim().is_empty() {
+                    return Ok(());
+                }
+                let payloads = if event_stream { sse_data(&body) } else { vec![body] };
+                for payload in payloads {
+                    match parse_message(&payload)? {
+                        Value::Array(batch) => pending.extend(batch),
+                        message => pending.push_back(message),
+                    }
+                }
+                Ok(())
+            }
+            McpTransport::WebSocket(stream) => stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .map_err(|e| McpError::Connect(e.to_string())),
+        }
+    }
+
+    /// The next message from the server
+    pub async fn recv(&mut self) -> Result<Value, McpError> {
+        match self {
+            McpTransport::Stdio(server) => loop {
+                match server.stdout.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => return parse_message(&line),
+                    Ok(None) | Err(_) => {
+                        let status = tokio::time::timeout(Duration::from_secs(1), server.child.wait()).await;
+                        finish_stderr(&mut server.stderr_task).await;
+                        return Err(McpError::Exited(match status {
+                            Ok(Ok(status)) => status.to_string(),
+                            _ => "closed stdout".to_string(),
+                        }));
+                    }
+                }
+            },
+            McpTransport::Http { pending, .. } => pending
+                .pop_front()
+                .ok_or_else(|| McpError::Protocol("the server sent no response".to_string())),
+            McpTransport::WebSocket(stream) => loop {
+                match stream.next().await {
+                    Some(Ok(Message::Text(text))) => return parse_message(&text),
+                    Some(Ok(Message::Binary(bytes))) => return parse_message(&String::from_utf8_lossy(&bytes)),
+                    Some(Ok(Message::Close(_))) | None => return Err(McpError::Exited("connection closed".to_string())),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(McpError::Connect(e.to_string())),
+                }
+            },
+        }
+    }
+
+    /// Stop the server or close the connection
+    pub async fn close(self) {
+        match self {
+            McpTransport::Stdio(server) => {
+                let StdioServer { mut child, stdin, mut stderr_task, .. } = *server;
+                drop(stdin);
+                if tokio::time::timeout(Duration::from_secs(1), child.wait()).await.is_err() {
+                    let _ = child.kill().await;
+                }
+                finish_stderr(&mut stderr_task).await;
+            }
+            McpTransport::Http { .. } => {}
+            McpTransport::WebSocket(mut stream) => {
+                let _ = stream.close().await;
+            }
+        }
+    }
+}
+
+/// Give the stderr copy a moment to catch up with a child that exited
+async fn finish_stderr(task: &mut Option<JoinHandle<()>>) {
+    if let Some(task) = task.take() {
+        let _ = tokio::time::timeout(Duration::from_secs(1), task).await;
+    }
+}
+
+/// Upgrade request for a WebSocket MCP server, offering the `mcp` subprotocol
+pub(crate) fn websocket_request(url: &str, headers: &[(String, String)]) -> Result<Request, McpError> {
+    let mut request = url.into_client_request().map_err(|e| McpError::Connect(e.to_string()))?;
+    request.headers_mut().insert("Sec-WebSocket-Protocol", "mcp".parse().unwrap());
+    for (name, value) in headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|e| McpError::Connect(e.to_string()))?;
+        let value = value.parse().map_err(|_| McpError::Connect(format!("invalid header value for {}", name)))?;
+        request.headers_mut().insert(name, value);
+    }
+    Ok(request)
+}
+
+pub(crate) fn parse_message(text: &str) -> Result<Value, McpError> {
+    serde_json::from_str(text.trim()).map_err(|_| {
+        let preview: String = text.trim().chars().take(200).collect();
+        McpError::Protocol(format!("not JSON-RPC: {}", preview))
+    })
+}
+
+/// The `data:` payload of each event in a server-sent event stream
+pub fn sse_data(body: &str) -> Vec<String> {
+    body.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            (!data.is_empty()).then(|| data.join("\n"))
+        })
+        .collect()
+}
+
+/// JSON-RPC requests over an `McpTransport`
+pub struct McpClient {
+    transport: McpTransport,
+    next_id: u64,
+}
+
+impl McpClient {
+    pub fn new(transport: McpTransport) -> Self {
+        McpClient { transport, next_id: 1 }
+    }
+
+    /// Send a request and wait for its response, skipping notifications and server requests
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.transport.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await?;
+        loop {
+            let message = self.transport.recv().await?;
+            if message.get("id").and_then(Value::as_u64) != Some(id) || message.get("method").is_some() {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(McpError::Protocol(format!(
+                    "{} failed: {}",
+                    method,
+                    error.get("message").and_then(Value::as_str).unwrap_or(&error.to_string())
+                )));
+            }
+            return message
+                .get("result")
+                .cloned()
+                .ok_or_else(|| McpError::Protocol(format!("{} response has no result", method)));
+        }
+    }
+
+    pub async fn notify(&mut self, method: &str, params: Value) -> Result<(), McpError> {
+        self.transport.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params })).await
+    }
+
+    /// The `initialize` handshake; returns the server's result
+    pub async fn initialize(&mut self) -> Result<Value, McpError> {
+        let result = self
+            .request("initialize", json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "clay", "version": env!("CARGO_PKG_VERSION") }
+            }))
+            .await?;
+        self.notify("notifications/initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    /// Names from every page of `tools/list`, `resources/list` or `prompts/list`
+    pub async fn list(&mut self, kind: &str) -> Result<Vec<String>, McpError> {
+        let mut names = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request(&format!("{}/list", kind), params).await?;
+            let items = result
+                .get(kind)
+                .and_then(Value::as_array)
+                .ok_or_else(|| McpError::Protocol(format!("{}/list response has no {}", kind, kind)))?;
+            names.extend(items.iter().filter_map(|item| {
+                item.get("name").or_else(|| item.get("uri")).and_then(Value::as_str).map(str::to_string)
+            }));
+            cursor = result.get("nextCursor").and_then(Value::as_str).map(str::to_string);
+            if cursor.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    pub async fn close(self) {
+        self.transport.close().await;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Ok,
+    Timeout,
+    Crashed,
+    Unreachable,
+    ProtocolError,
+}
+
+/// What a live check of one MCP server found
+#[derive(Debug, Clone, Serialize)]
+pub struct McpProbe {
+    pub name: String,
+    /// `command`, `http` or `ws`
+    pub transport: String,
+    pub status: ProbeStatus,
+    /// `serverInfo` name and version
+    pub server: Option<String>,
+    pub protocol_version: Option<String>,
+    pub tools: Vec<String>,
+    pub resources: Vec<String>,
+    pub prompts: Vec<String>,
+    pub error: Option<String>,
+    /// The end of a command server's stderr
+    pub stderr: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+impl McpProbe {
+    /// Start or connect to `server`, run the handshake and list what it offers, giving up
+    /// after `server.timeout` seconds
+    pub async fn run(name: &str, server: &McpServer, base_dir: &Path, env: &[(String, String)]) -> McpProbe {
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let mut probe = McpProbe {
+            name: name.to_string(),
+            transport: if server.is_command() { "command" } else if server.is_websocket() { "ws" } else { "http" }.to_string(),
+            status: ProbeStatus::Ok,
+            server: None,
+            protocol_version: None,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            error: None,
+            stderr: None,
+            elapsed_ms: 0,
+        };
+        let started = Instant::now();
+        let timeout = Duration::from_secs(server.timeout.max(1));
+        let outcome = tokio::time::timeout(timeout, probe.session(server, base_dir, env, stderr.clone())).await;
+        probe.elapsed_ms = started.elapsed().as_millis() as u64;
+        let error = match outcome {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e),
+            Err(_) => {
+                probe.status = ProbeStatus::Timeout;
+                probe.error = Some(format!("no answer within {}s", timeout.as_secs()));
+                None
+            }
+        };
+        if let Some(error) = error {
+            probe.status = match error {
+                McpError::Start(_) | McpError::Exited(_) => ProbeStatus::Crashed,
+                McpError::Connect(_) => ProbeStatus::Unreachable,
+                McpError::Protocol(_) => ProbeStatus::ProtocolError,
+            };
+            probe.error = Some(error.to_string());
+        }
+        let stderr = stderr.lock().unwrap().trim().to_string();
+        probe.stderr = (!stderr.is_empty()).then_some(stderr);
+        probe
+    }
+
+    async fn session(&mut self, server: &McpServer, base_dir: &Path, env: &[(String, String)], stderr: Arc<Mutex<String>>) -> Result<(), McpError> {
+        let mut client = McpClient::new(McpTransport::connect(server, base_dir, env, stderr).await?);
+        let result = client.initialize().await;
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                client.close().await;
+                return Err(e);
+            }
+        };
+        self.protocol_version = result.get("protocolVersion").and_then(Value::as_str).map(str::to_string);
+        self.server = result.get("serverInfo").map(|info| {
+            let name = info.get("name").and_then(Value::as_str).unwrap_or("unnamed");
+            match info.get("version").and_then(Value::as_str) {
+                Some(version) => format!("{} {}", name, version),
+                None => name.to_string(),
+            }
+        });
+        // Only ask for what the server says it has
+        let capabilities = result.get("capabilities").cloned().unwrap_or_default();
+        let mut listed = Ok(());
+        for kind in ["tools", "resources", "prompts"] {
+            if capabilities.get(kind).is_none() {
+                continue;
+            }
+            match client.list(kind).await {
+                Ok(names) => match kind {
+                    "tools" => self.tools = names,
+                    "resources" => self.resources = names,
+                    _ => self.prompts = names,
+                },
+                Err(e) => {
+                    listed = Err(e);
+                    break;
+                }
+            }
+        }
+        client.close().await;
+        listed
+    }
+}
+
+/// The part of `env` a stdio MCP server inherits, as when the Claude CLI starts it
+pub fn server_env(env: &[(String, String)]) -> Vec<(String, String)> {
+    env.iter().filter(|(name, _)| SERVER_ENV_VARS.contains(&name.as_str())).cloned().collect()
+}
+
+/// Probes as a table, with the error and stderr of each failed server below it
+pub fn probe_table(probes: &[McpProbe]) -> String {
+    let mut rows = vec![["SERVER", "TRANSPORT", "STATUS", "TOOLS", "RESOURCES", "PROMPTS", "TIME"].map(str::to_string)];
+    for probe in probes {
+        rows.push([
+            probe.name.clone(),
+            probe.transport.clone(),
+            serde_json::to_value(probe.status).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default(),
+            probe.tools.len().to_string(),
+            probe.resources.len().to_string(),
+            probe.prompts.len().to_string(),
+            format!("{:.1}s", probe.elapsed_ms as f64 / 1000.0),
+        ]);
+    }
+    let widths: Vec<usize> = (0..7).map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0)).collect();
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    for probe in probes.iter().filter(|probe| probe.status != ProbeStatus::Ok) {
+        table.push_str(&format!("\n{}: {}\n", probe.name, probe.error.as_deref().unwrap_or("failed")));
+        if let Some(stderr) = &probe.stderr {
+            for line in stderr.lines() {
+                table.push_str(&format!("  stderr: {}\n", line));
+            }
+        }
+    }
+    table
+}
+
+impl ClaudeSetup {
+    /// Check every MCP server in clay.yaml at once: start or connect to it, run the
+    /// handshake and list its tools, resources and prompts
+    pub async fn probe_mcp_servers(&self) -> Vec<McpProbe> {
+        let Some(mcp) = self.get_config().and_then(|config| config.mcp.clone()) else {
+            return Vec::new();
+        };
+        let env = server_env(&self.get_claude_env());
+        let mut names: Vec<&String> = mcp.servers.keys().collect();
+        names.sort();
+        let probes = names
+            .into_iter()
+            .map(|name| McpProbe::run(name, &mcp.servers[name], self.get_base_dir(), &env));
+        futures_util::future::join_all(probes).await
+    }
+}
@@ -0,0 +1,246 @@
+use crate::accounts::{Account, AccountPool};
+use crate::auth::LoginResult;
+use crate::config::AuthMonitorConfig;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How an account's credentials looked at the last check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthState {
+    /// Not checked yet
+    Unknown,
+    /// Credentials present and, if probed, accepted
+    Healthy,
+    /// Credentials work but expire within the warning window
+    Expiring,
+    /// Credentials are missing or the CLI rejected them
+    Failing,
+    /// The probe failed for a reason unrelated to credentials
+    Unverified,
+}
+
+impl AuthState {
+    /// States that alert when an account enters them
+    fn needs_attention(self) -> bool {
+        matches!(self, AuthState::Expiring | AuthState::Failing)
+    }
+}
+
+/// Result of the latest check of one account
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthHealth {
+    pub account: String,
+    pub state: AuthState,
+    pub detail: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// Counters for `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthMonitorMetrics {
+    pub checks: u64,
+    pub probes: u64,
+    pub failures: u64,
+    pub alerts: u64,
+    pub webhook_failures: u64,
+}
+
+/// Background task that checks every account's credentials before users hit a failure
+pub struct AuthMonitor {
+    config: AuthMonitorConfig,
+    accounts: Vec<Arc<Account>>,
+    health: Mutex<Vec<AuthHealth>>,
+    client: reqwest::Client,
+    checks: AtomicU64,
+    probes: AtomicU64,
+    failures: AtomicU64,
+    alerts: AtomicU64,
+    webhook_failures: AtomicU64,
+}
+
+impl AuthMonitor {
+    pub fn new(config: AuthMonitorConfig, accounts: &AccountPool) -> Self {
+        let health = accounts.accounts().iter()
+            .map(|account| AuthHealth {
+                account: account.name().to_string(),
+                state: AuthState::Unknown,
+                detail: "Not checked yet".to_string(),
+                expires_at: None,
+                checked_at: None,
+            })
+            .collect();
+
+        AuthMonitor {
+            config,
+            accounts: accounts.accounts().to_vec(),
+            health: Mutex::new(health),
+            client: reqwest::Client::new(),
+            checks: AtomicU64::new(0),
+            probes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            alerts: AtomicU64::new(0),
+            webhook_failures: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Check every account now and then every `interval_minutes`
+    pub fn start(self: &Arc<Self>) {
+        if !self.config.enabled {
+            return;
+        }
+        let interval = Duration::from_secs(self.config.interval_minutes.max(1) * 60);
+        info!("Checking Claude credentials every {} minute(s)", interval.as_secs() / 60);
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            loop {
+                monitor.check_now().await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Check every account once, alerting on accounts that newly need attention
+    pub async fn check_now(&self) {
+        for (index, account) in self.accounts.iter().enumerate() {
+            let health = self.check_account(account).await;
+            let previous = std::mem::replace(&mut self.health.lock().unwrap()[index], health.clone());
+
+            if health.state == AuthState::Failing {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+            }
+            if health.state != previous.state {
+                if health.state.needs_attention() {
+                    self.alert(&health).await;
+                } else if previous.state.needs_attention() && health.state == AuthState::Healthy {
+                    info!("Credentials for account {} are healthy again", health.account);
+                }
+            }
+        }
+    }
+
+    async fn check_account(&self, account: &Arc<Account>) -> AuthHealth {
+        self.checks.fetch_add(1, Ordering::Relaxed);
+        let setup = account.setup().clone();
+        let (authenticated, detail, expires_at) = tokio::task::spawn_blocking(move || {
+            let (authenticated, detail) = setup.get_auth_status().unwrap_or_else(|e| (false, e.to_string()));
+            (authenticated, detail, setup.get_auth_expiry())
+        })
+        .await
+        .unwrap_or_else(|e| (false, format!("Credential check failed: {}", e), None));
+        // Keeps `/health` current without it running credential commands itself; only a probe
+        // brings a rejected account back
+        account.record_auth_status(authenticated && account.is_authenticated(), detail.clone());
+
+        let mut health = AuthHealth {
+            account: account.name().to_string(),
+            state: AuthState::Healthy,
+            detail,
+            expires_at,
+            checked_at: Some(Utc::now()),
+        };
+        if !authenticated {
+            health.state = AuthState::Failing;
+            return health;
+        }
+
+        // A cooling account would only report its usage limit again
+        if self.config.probe && account.cooldown_until().is_none() {
+            self.probes.fetch_add(1, Ordering::Relaxed);
+            let setup = account.setup().clone();
+            let probe = tokio::task::spawn_blocking(move || setup.verify_authentication())
+                .await
+                .unwrap_or_else(|e| LoginResult::Unverified { reason: e.to_string() });
+            debug!("Credential probe for account {}: {:?}", account.name(), probe);
+            match probe {
+                LoginResult::Authenticated => {
+                    if !account.is_authenticated() {
+                        account.refresh_authentication();
+                    }
+                }
+                LoginResult::Rejected { reason } => {
+                    account.mark_unauthenticated(&reason);
+                    health.state = AuthState::Failing;
+                    health.detail = reason;
+                    return health;
+                }
+                LoginResult::Unverified { reason } => {
+                    health.state = AuthState::Unverified;
+                    health.detail = reason;
+                    return health;
+                }
+            }
+        }
+
+        let warning = chrono::Duration::days(self.config.expiry_warning_days as i64);
+        if expires_at.is_some_and(|expires_at| expires_at - Utc::now() <= warning) {
+            health.state = AuthState::Expiring;
+        }
+        health
+    }
+
+    /// Log the problem and tell the webhook, if one is configured
+    async fn alert(&self, health: &AuthHealth) {
+        self.alerts.fetch_add(1, Ordering::Relaxed);
+        let text = match health.state {
+            AuthState::Expiring => format!(
+                "Claude credentials for account {} expire at {}; log in again with `clay --account {} --status`",
+                health.account,
+                health.expires_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                health.account
+            ),
+            _ => format!(
+                "Claude credentials for account {} stopped working: {}",
+                health.account, health.detail
+            ),
+        };
+        warn!("{}", text);
+
+        let Some(webhook) = &self.config.webhook else {
+            return;
+        };
+        // `text` makes the payload readable by Slack-style incoming webhooks as is
+        let payload = serde_json::json!({
+            "event": "auth_alert",
+            "text": text,
+            "account": health.account,
+            "state": health.state,
+            "detail": health.detail,
+            "expires_at": health.expires_at,
+            "checked_at": health.checked_at,
+        });
+        let sent = self.client.post(webhook)
+            .json(&payload)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = sent {
+            self.webhook_failures.fetch_add(1, Ordering::Relaxed);
+            warn!("Failed to deliver auth alert to webhook: {}", e);
+        }
+    }
+
+    pub fn health(&self) -> Vec<AuthHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    pub fn metrics(&self) -> AuthMonitorMetrics {
+        AuthMonitorMetrics {
+            checks: self.checks.load(Ordering::Relaxed),
+            probes: self.probes.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            alerts: self.alerts.load(Ordering::Relaxed),
+            webhook_failures: self.webhook_failures.load(Ordering::Relaxed),
+        }
+    }
+}
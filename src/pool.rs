@@ -0,0 +1,158 @@
+use crate::config::PoolConfig;
+use crate::setup::ClaudeSetup;
+use crate::stream::StreamingSession;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// How often the replenisher re-checks the pool when nobody takes a child
+const REPLENISH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Counters describing how well the pool hides Claude CLI start-up time
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolMetrics {
+    pub size: usize,
+    pub idle: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub spawned: u64,
+    pub recycled: u64,
+    pub spawn_failures: u64,
+}
+
+/// Pre-spawned stream-json Claude children waiting to be handed to new sessions
+pub struct ProcessPool {
+    setup: Arc<ClaudeSetup>,
+    config: PoolConfig,
+    idle: Mutex<VecDeque<StreamingSession>>,
+    wanted: Notify,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    spawned: AtomicU64,
+    recycled: AtomicU64,
+    spawn_failures: AtomicU64,
+}
+
+impl ProcessPool {
+    pub fn new(setup: Arc<ClaudeSetup>, config: PoolConfig) -> Self {
+        ProcessPool {
+            setup,
+            config,
+            idle: Mutex::new(VecDeque::new()),
+            wanted: Notify::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            spawned: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+            spawn_failures: AtomicU64::new(0),
+        }
+    }
+
+    /// Take a warm child, or `None` if the pool is empty; either way a refill is requested
+    pub fn acquire(&self) -> Option<StreamingSession> {
+        let mut taken = None;
+        {
+            let mut idle = self.idle.lock().unwrap();
+            while let Some(mut session) = idle.pop_front() {
+                if session.is_alive() && !self.should_recycle(&session) {
+                    taken = Some(session);
+                    break;
+                }
+                self.recycled.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.wanted.notify_one();
+
+        match taken {
+            Some(session) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(session)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Whether a child has served enough turns or lived long enough to be replaced
+    pub fn should_recycle(&self, session: &StreamingSession) -> bool {
+        session.get_turns() >= self.config.max_uses
+            || session.get_age() >= Duration::from_secs(self.config.max_age_minutes * 60)
+    }
+
+    /// Count a child that a session retired because of `should_recycle`
+    pub fn note_recycled(&self) {
+        self.recycled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drop dead or stale idle children and spawn new ones until the pool is full
+    pub fn replenish(&self) {
+        {
+            let mut idle = self.idle.lock().unwrap();
+            let before = idle.len();
+            idle.retain_mut(|session| session.is_alive() && !self.should_recycle(session));
+            let dropped = before - idle.len();
+            if dropped > 0 {
+                debug!("Recycled {} idle pooled Claude process(es)", dropped);
+                self.recycled.fetch_add(dropped as u64, Ordering::Relaxed);
+            }
+        }
+
+        while self.idle.lock().unwrap().len() < self.config.size {
+            // Spawn outside the lock so requests can keep taking children meanwhile
+            match StreamingSession::spawn(&self.setup, None) {
+                Ok(session) => {
+                    self.spawned.fetch_add(1, Ordering::Relaxed);
+                    self.idle.lock().unwrap().push_back(session);
+                }
+                Err(e) => {
+                    self.spawn_failures.fetch_add(1, Ordering::Relaxed);
+                    warn!("Failed to pre-spawn Claude process: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Retire every idle child, whose Claude config predates a reload, and ask for fresh ones
+    pub fn drain(&self) {
+        let drained = std::mem::take(&mut *self.idle.lock().unwrap());
+        if !drained.is_empty() {
+            info!("Recycling {} idle pooled Claude process(es) after a config reload", drained.len());
+            self.recycled.fetch_add(drained.len() as u64, Ordering::Relaxed);
+        }
+        self.wanted.notify_one();
+    }
+
+    /// Keep the pool topped up in the background for the life of the server
+    pub fn start_replenisher(self: &Arc<Self>) {
+        info!("Warming a pool of {} Claude process(es)", self.config.size);
+        let pool = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let filler = pool.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || filler.replenish()).await {
+                    warn!("Process pool replenisher failed: {}", e);
+                }
+                let _ = tokio::time::timeout(REPLENISH_INTERVAL, pool.wanted.notified()).await;
+            }
+        });
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            size: self.config.size,
+            idle: self.idle.lock().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            spawned: self.spawned.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+            spawn_failures: self.spawn_failures.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,14 +1,31 @@
+use crate::config::SessionMode;
 use crate::error::{ClaudeRelayError, Result};
+use crate::events::{ClaudeEvent, EventParser};
+use crate::pool::ProcessPool;
 use crate::setup::ClaudeSetup;
+use crate::stream::{CliResult, StreamHealth, StreamMonitor, StreamingSession};
 use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::fs;
+use std::future::Future;
 use std::io::Write;
 use std::path::Path;
-use std::process::{Command, Stdio};
-use std::sync::Arc;
+use std::pin::Pin;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::ChildStdout;
+use tracing::{debug, warn};
 
-#[derive(Clone, Debug)]
+/// How long a finished stream waits for the rest of the CLI's stderr
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConversationState {
     pub history: Vec<String>,
     pub timestamp: DateTime<Utc>,
@@ -20,6 +37,14 @@ pub struct ClaudeProcess {
     conversation_states: Vec<ConversationState>,
     last_undone_history: Option<Vec<String>>,
     setup: Arc<ClaudeSetup>,
+    session_mode: SessionMode,
+    cli_session_id: Option<String>,
+    cli_sessions: Vec<String>,
+    last_activity: DateTime<Utc>,
+    persistent: bool,
+    turn_timeout: Duration,
+    streaming: Option<StreamingSession>,
+    pool: Option<Arc<ProcessPool>>,
 }
 
 impl ClaudeProcess {
@@ -31,6 +56,8 @@ impl ClaudeProcess {
         // Generate Claude's configuration from clay.yaml (this includes MCP setup)
         setup.setup_mcp_config()?;
 
+        let process_config = setup.get_process_config();
+
         let temp_dir = TempDir::new()
             .map_err(|e| ClaudeRelayError::Process(format!("Failed to create temp directory: {}", e)))?;
 
@@ -39,7 +66,15 @@ impl ClaudeProcess {
             conversation_history: Vec::new(),
             conversation_states: Vec::new(),
             last_undone_history: None,
+            session_mode: process_config.session_mode,
             setup,
+            cli_session_id: None,
+            cli_sessions: Vec::new(),
+            last_activity: Utc::now(),
+            persistent: process_config.persistent,
+            turn_timeout: Duration::from_secs(process_config.turn_timeout),
+            streaming: None,
+            pool: None,
         };
 
         // Initialize with context override if configured
@@ -48,6 +83,30 @@ impl ClaudeProcess {
         Ok(process)
     }
 
+    /// Create a process whose conversation is seeded from previously saved history
+    pub fn with_history(
+        setup: Arc<ClaudeSetup>,
+        history: Vec<String>,
+        states: Vec<ConversationState>,
+        last_undone_history: Option<Vec<String>>,
+    ) -> Result<Self> {
+        let mut process = Self::new(setup)?;
+        process.conversation_history = history;
+        process.conversation_states = states;
+        process.last_undone_history = last_undone_history;
+        Ok(process)
+    }
+
+    /// Take fresh persistent children from a warm pool instead of spawning them on demand
+    pub fn set_pool(&mut self, pool: Arc<ProcessPool>) {
+        self.persistent = true;
+        self.pool = Some(pool);
+    }
+
+    pub fn get_setup(&self) -> &Arc<ClaudeSetup> {
+        &self.setup
+    }
+
     pub fn get_working_directory(&self) -> &Path {
         self.temp_dir.path()
     }
@@ -65,11 +124,160 @@ impl ClaudeProcess {
     }
 
     pub fn send_message(&mut self, message: &str) -> Result<String> {
+        self.last_activity = Utc::now();
+        
         // Add user message to history
         self.conversation_history.push(format!("User: {}", message));
         
-        // Build context from conversation history
-        let full_prompt = if self.conversation_history.len() > 1 {
+        let sent = match self.session_mode {
+            _ if self.persistent => self.send_streaming(message),
+            SessionMode::Native => self.send_native(message),
+            SessionMode::Replay => self.run_claude(&self.build_replay_prompt(message), false, None)
+                .and_then(|output| {
+                    self.check_output(&output)?;
+                    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+                }),
+        };
+        let response = match sent {
+            Ok(response) => response,
+            Err(e) => {
+                // Leave the history as it was so the message can be retried
+                self.conversation_history.pop();
+                return Err(e);
+            }
+        };
+        
+        // Add Claude's response to history
+        self.conversation_history.push(format!("Claude: {}", response));
+        
+        // Replayed prompts grow with the history, so keep it manageable (last 10 exchanges)
+        if self.session_mode == SessionMode::Replay && self.conversation_history.len() > 20 {
+            self.conversation_history.drain(0..2);
+        }
+        
+        Ok(response)
+    }
+
+    /// Send a turn through the Claude CLI's own session, resuming it when one exists
+    fn send_native(&mut self, message: &str) -> Result<String> {
+        let mut output = match self.cli_session_id.clone() {
+            Some(session_id) => {
+                let output = self.run_claude(message, true, Some(&session_id))?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !output.status.success() && stderr.contains("No conversation found") {
+                    // The CLI lost the session (e.g. its files were removed); rebuild it from our history
+                    warn!("Claude CLI session {} not found, replaying conversation history", session_id);
+                    self.cli_session_id = None;
+                    self.run_claude(&self.build_replay_prompt(message), true, None)?
+                } else {
+                    output
+                }
+            }
+            None => self.run_claude(&self.build_replay_prompt(message), true, None)?,
+        };
+        self.check_output(&output)?;
+        
+        let stdout = String::from_utf8_lossy(&std::mem::take(&mut output.stdout)).to_string();
+        match serde_json::from_str::<CliResult>(stdout.trim()) {
+            Ok(result) => self.finish_result(result),
+            Err(e) => {
+                // Older CLIs without structured output: keep the text and fall back to replay
+                warn!("Claude CLI returned unstructured output ({}), falling back to history replay", e);
+                self.session_mode = SessionMode::Replay;
+                Ok(stdout)
+            }
+        }
+    }
+
+    /// Send a turn through this session's long-lived stream-json child, respawning it if it died
+    fn send_streaming(&mut self, message: &str) -> Result<String> {
+        let mut respawned = false;
+        
+        loop {
+            let alive = self.streaming.as_mut().map(|s| s.is_alive()).unwrap_or(false);
+            if !alive {
+                if let Some(mut dead) = self.streaming.take() {
+                    let health = dead.health();
+                    warn!(
+                        "Streaming Claude process {} exited ({}), respawning",
+                        health.pid,
+                        health.exit_status.unwrap_or_default()
+                    );
+                }
+                self.streaming = Some(self.spawn_streaming()?);
+            }
+            
+            // A child without a CLI session to resume needs the conversation so far
+            let prompt = match self.cli_session_id {
+                Some(_) => message.to_string(),
+                None => self.build_replay_prompt(message),
+            };
+            
+            let timeout = self.turn_timeout;
+            let session = self.streaming.as_mut().expect("streaming session was just spawned");
+            match session.send_turn(&prompt, timeout) {
+                Ok(result) => {
+                    if let Some(pool) = &self.pool {
+                        if pool.should_recycle(session) {
+                            debug!("Recycling persistent Claude process after {} turns", session.get_turns());
+                            pool.note_recycled();
+                            self.streaming = None;
+                        }
+                    }
+                    return self.finish_result(result);
+                }
+                Err(e) => {
+                    let crashed = !session.is_alive();
+                    // Never reuse a child that failed mid-turn; its output stream is out of sync
+                    self.streaming = None;
+                    if crashed && !respawned {
+                        respawned = true;
+                        continue;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Start a persistent child, preferring a warm pooled one when there is no CLI session to resume
+    fn spawn_streaming(&self) -> Result<StreamingSession> {
+        if self.cli_session_id.is_none() {
+            if let Some(session) = self.pool.as_ref().and_then(|pool| pool.acquire()) {
+                return Ok(session);
+            }
+        }
+        StreamingSession::spawn(&self.setup, self.cli_session_id.as_deref())
+    }
+
+    /// Record the CLI session a result belongs to and turn CLI-reported errors into errors
+    fn finish_result(&mut self, result: CliResult) -> Result<String> {
+        if let Some(session_id) = result.session_id {
+            self.record_cli_session(session_id);
+        }
+        
+        let text = result.result.unwrap_or_default();
+        if result.is_error {
+            return Err(self.cli_error(&text));
+        }
+        Ok(text)
+    }
+
+    fn record_cli_session(&mut self, session_id: String) {
+        if self.cli_session_id.as_deref() != Some(session_id.as_str()) {
+            debug!("Claude CLI session started: {}", session_id);
+            self.cli_sessions.push(session_id.clone());
+        }
+        self.cli_session_id = Some(session_id);
+    }
+
+    fn cli_error(&self, output: &str) -> ClaudeRelayError {
+        crate::classify::cli_error(output)
+    }
+
+    /// Build a prompt that carries the conversation as a text preamble
+    fn build_replay_prompt(&self, message: &str) -> String {
+        if self.conversation_history.len() > 1 {
             let mut context = String::from("Previous conversation:\n");
             for msg in &self.conversation_history[..self.conversation_history.len() - 1] {
                 context.push_str(msg);
@@ -87,12 +295,20 @@ impl ClaudeProcess {
             }
             full_message.push_str(message);
             full_message
-        };
-        
-        // Use claude --print mode for this single request
+        }
+    }
+
+    /// Run `claude --print` once with the prompt on stdin
+    fn run_claude(&self, prompt: &str, json_output: bool, resume: Option<&str>) -> Result<Output> {
         let mut cmd = Command::new(self.setup.get_claude_path());
-        cmd.args(&["--print", "--dangerously-skip-permissions"])
-            .current_dir(self.setup.get_base_dir())
+        cmd.args(["--print", "--dangerously-skip-permissions"]);
+        if json_output {
+            cmd.args(["--output-format", "json"]);
+        }
+        if let Some(session_id) = resume {
+            cmd.args(["--resume", session_id]);
+        }
+        cmd.current_dir(self.setup.get_base_dir())
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
@@ -112,43 +328,125 @@ impl ClaudeProcess {
         
         // Write prompt to stdin
         if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(full_prompt.as_bytes())
+            stdin.write_all(prompt.as_bytes())
                 .map_err(|e| ClaudeRelayError::Process(format!("Failed to write to stdin: {}", e)))?;
         }
         
-        let output = child.wait_with_output()
-            .map_err(|e| ClaudeRelayError::Process(format!("Claude command failed: {}", e)))?;
-        
+        child.wait_with_output()
+            .map_err(|e| ClaudeRelayError::Process(format!("Claude command failed: {}", e)))
+    }
+
+    fn check_output(&self, output: &Output) -> Result<()> {
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            // Structured output reports failures on stdout, so use it when stderr is empty
+            let stderr = if output.stderr.is_empty() {
+                String::from_utf8_lossy(&output.stdout)
+            } else {
+                String::from_utf8_lossy(&output.stderr)
+            };
             
-            if self.setup.is_authentication_needed(&stderr) {
-                return Err(ClaudeRelayError::Authentication(
-                    "Authentication required: please restart the server to login".into()
-                ));
-            }
-            return Err(ClaudeRelayError::Process(
-                format!("Claude command failed: {}", stderr)
-            ));
+            return Err(self.cli_error(&stderr));
         }
+        Ok(())
+    }
+
+    /// Stream typed events for one message from a dedicated `claude --print` child.
+    ///
+    /// Must be called within a Tokio runtime. Dropping the stream before its
+    /// `Result` event kills the child and forgets the message.
+    pub fn stream_message(&mut self, message: &str) -> Result<MessageStream<'_>> {
+        self.last_activity = Utc::now();
+        
+        // A persistent child would miss this turn, so let it resume the session afresh later
+        self.streaming = None;
+        
+        let resume = match self.session_mode {
+            SessionMode::Native => self.cli_session_id.clone(),
+            SessionMode::Replay => None,
+        };
+        self.conversation_history.push(format!("User: {}", message));
+        let prompt = match resume {
+            Some(_) => message.to_string(),
+            None => self.build_replay_prompt(message),
+        };
         
-        let response = String::from_utf8_lossy(&output.stdout).to_string();
-        
-        // Add Claude's response to history
-        self.conversation_history.push(format!("Claude: {}", response));
+        let mut cmd = tokio::process::Command::new(self.setup.get_claude_path());
+        // Partial messages make the CLI emit text deltas as they are generated, not per block
+        cmd.args(["--print", "--output-format", "stream-json", "--verbose", "--include-partial-messages", "--dangerously-skip-permissions"]);
+        if let Some(session_id) = &resume {
+            cmd.args(["--resume", session_id]);
+        }
+        cmd.current_dir(self.setup.get_base_dir())
+            .envs(self.setup.get_claude_env())
+            .env("CLAUDE_RELAY", "true")
+            .env("TERM", "dumb")
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                self.conversation_history.pop();
+                return Err(ClaudeRelayError::Process(format!("Failed to spawn Claude: {}", e)));
+            }
+        };
         
-        // Keep history manageable (last 10 exchanges)
-        if self.conversation_history.len() > 20 {
-            self.conversation_history.drain(0..2);
+        if let Some(mut stdin) = child.stdin.take() {
+            tokio::spawn(async move {
+                // Closing stdin tells the CLI the prompt is complete
+                if let Err(e) = stdin.write_all(prompt.as_bytes()).await {
+                    warn!("Failed to write prompt to Claude: {}", e);
+                }
+            });
         }
         
-        Ok(response)
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let stderr_task = child.stderr.take().map(|mut pipe| {
+            let stderr = stderr.clone();
+            tokio::spawn(async move {
+                let mut buffer = String::new();
+                let _ = pipe.read_to_string(&mut buffer).await;
+                stderr.lock().unwrap().push_str(&buffer);
+            })
+        });
+        
+        let stdout = child.stdout.take()
+            .ok_or_else(|| ClaudeRelayError::Process("Claude has no stdout".into()))?;
+        
+        Ok(MessageStream {
+            process: self,
+            _child: child,
+            lines: BufReader::new(stdout).lines(),
+            stderr,
+            stderr_task,
+            stderr_deadline: None,
+            parser: EventParser::new(),
+            pending: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// Async counterpart of `send_message`, built on `stream_message`
+    pub async fn send_message_async(&mut self, message: &str) -> Result<String> {
+        let mut stream = self.stream_message(message)?;
+        while let Some(event) = stream.next().await {
+            if let ClaudeEvent::Result { text, is_error, .. } = event? {
+                if is_error {
+                    return Err(stream.process.cli_error(&text));
+                }
+                return Ok(text);
+            }
+        }
+        Err(ClaudeRelayError::Process("Claude finished without a result".into()))
     }
 
     pub async fn send_message_with_progress<F>(
         &mut self,
         message: &str,
-        _progress_callback: F,
+        mut progress_callback: F,
     ) -> Result<String> 
     where
         F: FnMut(&str),
@@ -156,44 +454,31 @@ impl ClaudeProcess {
         // Save current state before processing (for undo functionality)
         self.save_state();
         
-        // Send progress updates
-        let messages = [
-            "💭 Processing your request...",
-            "🔍 Analyzing context...",
-            "📖 Gathering information...",
-            "🧠 Formulating response...",
-        ];
-        
-        // Start a task to send progress updates
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let progress_task = tokio::spawn(async move {
-            let mut index = 0;
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(2));
-            
-            loop {
-                tokio::select! {
-                    _ = interval.tick() => {
-                        if index < messages.len() {
-                            if tx.send(messages[index]).await.is_err() {
-                                break;
-                            }
-                            index += 1;
-                        }
-                    }
-                    _ = rx.recv() => {
-                        break;
+        progress_callback("💭 Processing your request...");
+        
+        let mut stream = self.stream_message(message)?;
+        let mut writing = false;
+        while let Some(event) = stream.next().await {
+            match event? {
+                ClaudeEvent::ToolUseStarted { name, .. } => {
+                    progress_callback(&format!("🔧 Using {}...", name));
+                    writing = false;
+                }
+                ClaudeEvent::TextDelta { .. } if !writing => {
+                    progress_callback("🧠 Formulating response...");
+                    writing = true;
+                }
+                ClaudeEvent::Result { text, is_error, .. } => {
+                    if is_error {
+                        return Err(stream.process.cli_error(&text));
                     }
+                    return Ok(text);
                 }
+                _ => {}
             }
-        });
-        
-        // Send the actual message
-        let result = self.send_message(message);
-        
-        // Stop progress updates
-        drop(progress_task);
+        }
         
-        result
+        Err(ClaudeRelayError::Process("Claude finished without a result".into()))
     }
 
     pub fn save_state(&mut self) {
@@ -220,6 +505,7 @@ impl ClaudeProcess {
         
         // Restore conversation history to that state
         self.conversation_history = last_state.history;
+        self.invalidate_cli_session();
         
         Ok(())
     }
@@ -238,6 +524,7 @@ impl ClaudeProcess {
         // Save the conversation that will be undone (for restore)
         if history_index < self.conversation_history.len() {
             self.last_undone_history = Some(self.conversation_history.clone());
+            self.invalidate_cli_session();
         }
         
         // Truncate conversation history to this point
@@ -249,6 +536,83 @@ impl ClaudeProcess {
         Ok(())
     }
 
+    pub fn get_conversation_history(&self) -> &[String] {
+        &self.conversation_history
+    }
+
+    pub fn get_conversation_states(&self) -> &[ConversationState] {
+        &self.conversation_states
+    }
+
+    pub fn get_last_undone_history(&self) -> Option<&[String]> {
+        self.last_undone_history.as_deref()
+    }
+
+    /// Claude CLI session id the next turn will resume, if any
+    pub fn get_cli_session_id(&self) -> Option<&str> {
+        self.cli_session_id.as_deref()
+    }
+
+    pub fn get_session_mode(&self) -> SessionMode {
+        self.session_mode
+    }
+
+    pub fn set_session_mode(&mut self, mode: SessionMode) {
+        self.session_mode = mode;
+    }
+
+    pub fn get_last_activity(&self) -> DateTime<Utc> {
+        self.last_activity
+    }
+
+    /// Liveness of this session's persistent Claude child, if one is running
+    pub fn streaming_health(&self) -> Option<StreamHealth> {
+        self.streaming.as_ref().map(|s| s.monitor().health())
+    }
+
+    /// Handle on the persistent child's health that outlives a borrow of this process
+    pub fn stream_monitor(&self) -> Option<Arc<StreamMonitor>> {
+        self.streaming.as_ref().map(|s| s.monitor())
+    }
+
+    /// Remove the Claude CLI's session files for every session this process started
+    pub fn cleanup_session_files(&mut self) -> Result<()> {
+        // Stop the persistent child first so it can't write the transcript back
+        self.streaming = None;
+        
+        let projects_dir = self.setup.get_cli_projects_dir();
+        
+        if projects_dir.exists() {
+            for entry in fs::read_dir(&projects_dir)? {
+                let project_dir = entry?.path();
+                if !project_dir.is_dir() {
+                    continue;
+                }
+                
+                for session_id in &self.cli_sessions {
+                    let transcript = project_dir.join(format!("{}.jsonl", session_id));
+                    if transcript.exists() {
+                        fs::remove_file(&transcript)?;
+                    }
+                    let session_dir = project_dir.join(session_id);
+                    if session_dir.is_dir() {
+                        fs::remove_dir_all(&session_dir)?;
+                    }
+                }
+            }
+        }
+        
+        self.cli_sessions.clear();
+        self.cli_session_id = None;
+        Ok(())
+    }
+
+    /// Forget the CLI session after local history diverged from it; the next turn replays history
+    fn invalidate_cli_session(&mut self) {
+        self.cli_session_id = None;
+        self.streaming = None;
+    }
+
     pub fn can_undo(&self) -> bool {
         !self.conversation_states.is_empty()
     }
@@ -285,6 +649,7 @@ impl ClaudeProcess {
         
         // Restore the conversation history
         self.conversation_history = last_undone.clone();
+        self.invalidate_cli_session();
         
         // Clear the undo buffer since we've restored it
         self.last_undone_history = None;
@@ -335,6 +700,7 @@ impl ClaudeProcess {
         self.conversation_history.clear();
         self.conversation_states.clear();
         self.last_undone_history = None;
+        self.invalidate_cli_session();
         
         if let Some(ctx) = context {
             self.conversation_history.push(format!("System Context: {}", ctx));
@@ -342,4 +708,92 @@ impl ClaudeProcess {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Events for one message, streamed from a dedicated Claude CLI child
+pub struct MessageStream<'a> {
+    process: &'a mut ClaudeProcess,
+    // Held so the child is killed when the stream is dropped
+    _child: tokio::process::Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    stderr: Arc<Mutex<String>>,
+    stderr_task: Option<tokio::task::JoinHandle<()>>,
+    /// Set once stdout closes; how long to wait for the rest of stderr
+    stderr_deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    parser: EventParser,
+    pending: VecDeque<ClaudeEvent>,
+    done: bool,
+}
+
+impl MessageStream<'_> {
+    /// Settle the conversation history once the turn's outcome is known
+    fn complete(&mut self, reply: Option<&str>) {
+        self.done = true;
+        match reply {
+            Some(text) => self.process.conversation_history.push(format!("Claude: {}", text)),
+            None => {
+                self.process.conversation_history.pop();
+            }
+        }
+    }
+}
+
+impl Stream for MessageStream<'_> {
+    type Item = Result<ClaudeEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                if let ClaudeEvent::Result { text, session_id, is_error, .. } = &event {
+                    if let (Some(session_id), SessionMode::Native) = (session_id, this.process.session_mode) {
+                        this.process.record_cli_session(session_id.clone());
+                    }
+                    let reply = (!is_error).then_some(text.as_str());
+                    this.complete(reply);
+                }
+                return Poll::Ready(Some(Ok(event)));
+            }
+            
+            if this.done {
+                return Poll::Ready(None);
+            }
+            
+            match Pin::new(&mut this.lines).poll_next_line(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(Some(line))) => {
+                    let events = this.parser.parse_line(&line);
+                    this.pending.extend(events);
+                }
+                Poll::Ready(Ok(None)) => {
+                    // The CLI may still be writing why it failed
+                    if let Some(task) = this.stderr_task.as_mut() {
+                        let deadline = this.stderr_deadline
+                            .get_or_insert_with(|| Box::pin(tokio::time::sleep(STDERR_DRAIN_TIMEOUT)));
+                        if Pin::new(task).poll(cx).is_pending() && deadline.as_mut().poll(cx).is_pending() {
+                            return Poll::Pending;
+                        }
+                        this.stderr_task = None;
+                    }
+                    this.complete(None);
+                    let stderr = this.stderr.lock().unwrap().clone();
+                    return Poll::Ready(Some(Err(this.process.cli_error(stderr.trim()))));
+                }
+                Poll::Ready(Err(e)) => {
+                    this.complete(None);
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for MessageStream<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            // Cancelled mid-turn: the reply never arrived, so forget the question too
+            self.process.conversation_history.pop();
+        }
+    }
+}
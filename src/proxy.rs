@@ -0,0 +1,479 @@
+use crate::config::McpServer;
+use crate::error::{ClaudeRelayError, Result};
+use crate::mcp::{parse_message, sse_data, websocket_request};
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{info, warn};
+
+/// Carries a proxied server's headers as a JSON object, keeping secrets out of the process list
+pub const HEADERS_ENV: &str = "CLAY_MCP_HEADERS";
+
+/// Connection attempts before a WebSocket server counts as gone
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Id of the `initialize` request replayed on a new connection or session
+const REPLAY_ID: &str = "clay-proxy-reinitialize";
+
+type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+/// What the server side sent
+enum Upstream {
+    Message(Value),
+    Closed(String),
+}
+
+/// Bridges the Claude CLI's stdio to an HTTP or WebSocket MCP server; `clay mcp-proxy`
+#[derive(Debug, Clone)]
+pub struct McpProxy {
+    /// Server name from clay.yaml, for logs
+    pub name: String,
+    pub url: String,
+    pub websocket: bool,
+    pub headers: Vec<(String, String)>,
+    /// How long each request may wait for its answer
+    pub timeout: Duration,
+    /// Open a new connection or session when the old one is lost
+    pub reconnect: bool,
+    /// First wait between reconnect attempts, doubled after each
+    pub retry_delay: Duration,
+}
+
+impl McpProxy {
+    pub fn for_server(name: &str, server: &McpServer) -> Self {
+        let mut headers: Vec<(String, String)> = server.headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        headers.sort();
+        McpProxy {
+            name: name.to_string(),
+            url: server.url.clone().unwrap_or_default(),
+            websocket: server.is_websocket(),
+            headers,
+            timeout: Duration::from_secs(server.timeout.max(1)),
+            reconnect: server.reconnect,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Arguments for `clay mcp-proxy` and the environment it needs, for mcp.json
+    pub fn command_args(&self) -> (Vec<String>, HashMap<String, String>) {
+        let mut args = vec![
+            "mcp-proxy".to_string(),
+            "--name".to_string(),
+            self.name.clone(),
+            "--url".to_string(),
+            self.url.clone(),
+            "--transport".to_string(),
+            if self.websocket { "ws" } else { "http" }.to_string(),
+            "--timeout".to_string(),
+            self.timeout.as_secs().to_string(),
+        ];
+        if !self.reconnect {
+            args.push("--no-reconnect".to_string());
+        }
+        let mut env = HashMap::new();
+        if !self.headers.is_empty() {
+            let headers: serde_json::Map<String, Value> = self.headers.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect();
+            env.insert(HEADERS_ENV.to_string(), Value::Object(headers).to_string());
+        }
+        (args, env)
+    }
+
+    /// Headers passed in `CLAY_MCP_HEADERS`
+    pub fn headers_from_env() -> Result<Vec<(String, String)>> {
+        let Ok(text) = std::env::var(HEADERS_ENV) else {
+            return Ok(Vec::new());
+        };
+        let headers: HashMap<String, String> = serde_json::from_str(&text)
+            .map_err(|e| ClaudeRelayError::Config(format!("{}: {}", HEADERS_ENV, e)))?;
+        let mut headers: Vec<(String, String)> = headers.into_iter().collect();
+        headers.sort();
+        Ok(headers)
+    }
+
+    /// Relay newline-delimited JSON-RPC between `input`/`output` and the server until
+    /// `input` ends. Requests that go unanswered for `timeout`, or whose connection
+    /// drops, are answered with a JSON-RPC error.
+    pub async fn run<R, W>(&self, input: R, mut output: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let (tx, mut rx) = unbounded_channel();
+        let mut lines = BufReader::new(input).lines();
+        let mut connection = self.connect(&tx).await?;
+        // Request ids awaiting an answer, as JSON text, with their deadlines
+        let mut pending: HashMap<String, Instant> = HashMap::new();
+        // Set while a replayed handshake is unanswered; the CLI's messages wait for it
+        let mut replaying: Option<Instant> = None;
+        let mut tick = tokio::time::interval(Duration::from_millis(200));
+        info!("Proxying MCP server '{}' at {}", self.name, self.url);
+
+        loop {
+            tokio::select! {
+                line = lines.next_line(), if replaying.is_none() => {
+                    let Some(line) = line? else { break };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let message = match parse_message(&line) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            write_message(&mut output, &error_response(&Value::Null, -32700, &e.to_string())).await?;
+                            continue;
+                        }
+                    };
+                    if message.get("method").and_then(Value::as_str) == Some("initialize") {
+                        connection.remember_initialize(&message);
+                    }
+                    let request_id = message.get("id").filter(|_| message.get("method").is_some()).cloned();
+                    if let Some(id) = &request_id {
+                        pending.insert(id.to_string(), Instant::now() + self.timeout);
+                    }
+                    if let Err(e) = connection.send(message, &tx).await {
+                        if let Some(id) = request_id.filter(|id| pending.remove(&id.to_string()).is_some()) {
+                            write_message(&mut output, &error_response(&id, -32000, &e)).await?;
+                        }
+                    }
+                }
+                Some(upstream) = rx.recv() => match upstream {
+                    Upstream::Message(message) => {
+                        let id = message.get("id").filter(|_| message.get("method").is_none());
+                        if id.and_then(Value::as_str) == Some(REPLAY_ID) {
+                            connection.initialized().await;
+                            replaying = None;
+                            continue;
+                        }
+                        // An answer nobody waits for anymore has already been answered with an error
+                        if id.is_some_and(|id| pending.remove(&id.to_string()).is_none()) {
+                            continue;
+                        }
+                        write_message(&mut output, &message).await?;
+                    }
+                    Upstream::Closed(reason) => {
+                        warn!("MCP server '{}' connection lost: {}", self.name, reason);
+                        for (id, _) in pending.drain() {
+                            let id: Value = serde_json::from_str(&id).unwrap_or(Value::Null);
+                            write_message(&mut output, &error_response(&id, -32000, &format!("connection to {} lost: {}", self.url, reason))).await?;
+                        }
+                        if !self.reconnect {
+                            return Err(ClaudeRelayError::McpFailure(format!("{}: connection lost: {}", self.name, reason)));
+                        }
+                        let init = connection.take_initialize();
+                        connection = self.connect(&tx).await?;
+                        if let Some(init) = init {
+                            connection.replay_initialize(init, &tx).await;
+                            replaying = Some(Instant::now() + self.timeout);
+                        }
+                    }
+                },
+                _ = tick.tick() => {
+                    let now = Instant::now();
+                    if replaying.is_some_and(|deadline| deadline <= now) {
+                        warn!("MCP server '{}' didn't answer the replayed initialize", self.name);
+                        replaying = None;
+                    }
+                    let expired: Vec<String> = pending.iter().filter(|(_, deadline)| **deadline <= now).map(|(id, _)| id.clone()).collect();
+                    for id in expired {
+                        pending.remove(&id);
+                        let id: Value = serde_json::from_str(&id).unwrap_or(Value::Null);
+                        let message = format!("{} gave no answer within {}s", self.name, self.timeout.as_secs());
+                        write_message(&mut output, &error_response(&id, -32001, &message)).await?;
+                    }
+                }
+            }
+        }
+        connection.close().await;
+        Ok(())
+    }
+
+    async fn connect(&self, tx: &UnboundedSender<Upstream>) -> Result<Connection> {
+        if !self.websocket {
+            let client = reqwest::Client::builder()
+                .connect_timeout(self.timeout)
+                .build()
+                .map_err(|e| ClaudeRelayError::McpFailure(e.to_string()))?;
+            return Ok(Connection::Http(HttpSession {
+                proxy: self.clone(),
+                client,
+                session_id: Arc::new(Mutex::new(None)),
+                init: Arc::new(Mutex::new(None)),
+            }));
+        }
+
+        let attempts = if self.reconnect { RECONNECT_ATTEMPTS } else { 1 };
+        let mut delay = self.retry_delay;
+        let mut last_error = String::new();
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            let request = websocket_request(&self.url, &self.headers)?;
+            match tokio::time::timeout(self.timeout, tokio_tungstenite::connect_async(request)).await {
+                Ok(Ok((stream, _))) => {
+                    let (sink, mut stream) = stream.split();
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        let reason = loop {
+                            let text = match stream.next().await {
+                                Some(Ok(Message::Text(text))) => text,
+                                Some(Ok(Message::Binary(bytes))) => String::from_utf8_lossy(&bytes).into_owned(),
+                                Some(Ok(Message::Close(_))) | None => break "closed by the server".to_string(),
+                                Some(Ok(_)) => continue,
+                                Some(Err(e)) => break e.to_string(),
+                            };
+                            match parse_message(&text) {
+                                Ok(message) => forward(&tx, message),
+                                Err(e) => warn!("Ignoring WebSocket message: {}", e),
+                            }
+                        };
+                        let _ = tx.send(Upstream::Closed(reason));
+                    });
+                    return Ok(Connection::WebSocket { sink, init: None });
+                }
+                Ok(Err(e)) => last_error = e.to_string(),
+                Err(_) => last_error = format!("no connection within {}s", self.timeout.as_secs()),
+            }
+            warn!("Connecting to MCP server '{}' failed (attempt {}/{}): {}", self.name, attempt, attempts, last_error);
+        }
+        Err(ClaudeRelayError::McpFailure(format!("{}: can't connect to {}: {}", self.name, self.url, last_error)))
+    }
+}
+
+/// The server side of the proxy
+enum Connection {
+    Http(HttpSession),
+    WebSocket { sink: WsSink, init: Option<Value> },
+}
+
+impl Connection {
+    fn remember_initialize(&mut self, message: &Value) {
+        match self {
+            Connection::Http(session) => *session.init.lock().unwrap() = Some(message.clone()),
+            Connection::WebSocket { init, .. } => *init = Some(message.clone()),
+        }
+    }
+
+    fn take_initialize(&mut self) -> Option<Value> {
+        match self {
+            Connection::Http(session) => session.init.lock().unwrap().take(),
+            Connection::WebSocket { init, .. } => init.take(),
+        }
+    }
+
+    /// Repeat the CLI's handshake on a new connection; its answer is swallowed
+    async fn replay_initialize(&mut self, mut init: Value, tx: &UnboundedSender<Upstream>) {
+        self.remember_initialize(&init);
+        init["id"] = json!(REPLAY_ID);
+        let _ = self.send(init, tx).await;
+    }
+
+    /// Finish a replayed handshake
+    async fn initialized(&mut self) {
+        if let Connection::WebSocket { sink, .. } = self {
+            let _ = sink.send(Message::Text(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string())).await;
+        }
+    }
+
+    async fn send(&mut self, message: Value, tx: &UnboundedSender<Upstream>) -> std::result::Result<(), String> {
+        match self {
+            Connection::Http(session) => session.send(message, tx).await,
+            Connection::WebSocket { sink, .. } => sink.send(Message::Text(message.to_string())).await.map_err(|e| e.to_string()),
+        }
+    }
+
+    async fn close(self) {
+        match self {
+            Connection::Http(session) => session.close().await,
+            Connection::WebSocket { mut sink, .. } => {
+                let _ = sink.close().await;
+            }
+        }
+    }
+}
+
+/// Streamable HTTP: one POST per message, answered with JSON or an event stream
+#[derive(Clone)]
+struct HttpSession {
+    proxy: McpProxy,
+    client: reqwest::Client,
+    session_id: Arc<Mutex<Option<String>>>,
+    /// The CLI's `initialize`, replayed when the server forgets the session
+    init: Arc<Mutex<Option<Value>>>,
+}
+
+impl HttpSession {
+    /// Notifications and the handshake are sent in order; other requests run concurrently
+    async fn send(&self, message: Value, tx: &UnboundedSender<Upstream>) -> std::result::Result<(), String> {
+        let is_initialize = message.get("method").and_then(Value::as_str) == Some("initialize");
+        let is_request = message.get("id").is_some() && message.get("method").is_some();
+        if is_request && !is_initialize {
+            let session = self.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Err(e) = session.exchange(&message, &tx).await {
+                    forward(&tx, error_response(&message["id"], -32000, &e));
+                }
+            });
+            return Ok(());
+        }
+        self.exchange(&message, tx).await?;
+        if is_initialize {
+            self.listen(tx);
+        }
+        Ok(())
+    }
+
+    /// POST one message and forward whatever comes back
+    async fn exchange(&self, message: &Value, tx: &UnboundedSender<Upstream>) -> std::result::Result<(), String> {
+        let mut response = self.post(message).await?;
+        // The server forgot the session: start a new one and try again
+        if response.status() == reqwest::StatusCode::NOT_FOUND && self.proxy.reconnect && self.session_id.lock().unwrap().is_some() {
+            self.reinitialize().await?;
+            response = self.post(message).await?;
+        }
+        self.forward_response(response, tx).await
+    }
+
+    async fn post(&self, message: &Value) -> std::result::Result<reqwest::Response, String> {
+        let mut request = self
+            .client
+            .post(&self.proxy.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        for (name, value) in &self.proxy.headers {
+            request = request.header(name, value);
+        }
+        if let Some(id) = self.session_id.lock().unwrap().clone() {
+            request = request.header("Mcp-Session-Id", id);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if let Some(id) = response.headers().get("mcp-session-id").and_then(|id| id.to_str().ok()) {
+            *self.session_id.lock().unwrap() = Some(id.to_string());
+        }
+        Ok(response)
+    }
+
+    async fn reinitialize(&self) -> std::result::Result<(), String> {
+        let Some(mut init) = self.init.lock().unwrap().clone() else {
+            return Err("session expired before initialize".to_string());
+        };
+        warn!("MCP server '{}' expired the session; initializing again", self.proxy.name);
+        *self.session_id.lock().unwrap() = None;
+        init["id"] = json!(REPLAY_ID);
+        let response = self.post(&init).await?;
+        if !response.status().is_success() {
+            return Err(format!("initialize failed with HTTP {}", response.status()));
+        }
+        response.bytes().await.map_err(|e| e.to_string())?;
+        self.post(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await?;
+        Ok(())
+    }
+
+    async fn forward_response(&self, response: reqwest::Response, tx: &UnboundedSender<Upstream>) -> std::result::Result<(), String> {
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("HTTP {}: {}", status, body.trim()));
+        }
+        let event_stream = response
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !event_stream {
+            let body = response.text().await.map_err(|e| e.to_string())?;
+            if !body.trim().is_empty() {
+                forward(tx, parse_message(&body).map_err(|e| e.to_string())?);
+            }
+            return Ok(());
+        }
+
+        // Pass events on as they arrive; progress notifications come before the result
+        let mut buffer = String::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            buffer.push_str(&String::from_utf8_lossy(&chunk.map_err(|e| e.to_string())?).replace("\r\n", "\n"));
+            while let Some(end) = buffer.find("\n\n") {
+                let event: String = buffer.drain(..end + 2).collect();
+                for data in sse_data(&event) {
+                    match parse_message(&data) {
+                        Ok(message) => forward(tx, message),
+                        Err(e) => warn!("Ignoring event from MCP server '{}': {}", self.proxy.name, e),
+                    }
+                }
+            }
+        }
+        for data in sse_data(&buffer) {
+            forward(tx, parse_message(&data).map_err(|e| e.to_string())?);
+        }
+        Ok(())
+    }
+
+    /// Messages the server sends on its own, over the optional GET stream
+    fn listen(&self, tx: &UnboundedSender<Upstream>) {
+        let Some(session_id) = self.session_id.lock().unwrap().clone() else {
+            return;
+        };
+        let session = self.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut request = session
+                .client
+                .get(&session.proxy.url)
+                .header("Accept", "text/event-stream")
+                .header("Mcp-Session-Id", session_id);
+            for (name, value) in &session.proxy.headers {
+                request = request.header(name, value);
+            }
+            // Servers without the stream answer 405
+            if let Ok(response) = request.send().await.and_then(|response| response.error_for_status()) {
+                let _ = session.forward_response(response, &tx).await;
+            }
+        });
+    }
+
+    /// End the session, as the spec asks clients to
+    async fn close(self) {
+        let Some(session_id) = self.session_id.lock().unwrap().clone() else {
+            return;
+        };
+        let mut request = self.client.delete(&self.proxy.url).header("Mcp-Session-Id", session_id);
+        for (name, value) in &self.proxy.headers {
+            request = request.header(name, value);
+        }
+        let _ = tokio::time::timeout(Duration::from_secs(2), request.send()).await;
+    }
+}
+
+/// Hand server messages to the main loop, unpacking batches
+fn forward(tx: &UnboundedSender<Upstream>, message: Value) {
+    match message {
+        Value::Array(batch) => batch.into_iter().for_each(|message| {
+            let _ = tx.send(Upstream::Message(message));
+        }),
+        message => {
+            let _ = tx.send(Upstream::Message(message));
+        }
+    }
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(output: &mut W, message: &Value) -> Result<()> {
+    let mut line = message.to_string();
+    line.push('\n');
+    output.write_all(line.as_bytes()).await?;
+    output.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use crate::accounts::AccountPool;
+use crate::config::Config;
+use crate::pool::ProcessPool;
+use crate::setup::ClaudeSetup;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often the configuration files are checked for changes
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings read once at startup; changing them only takes effect after a restart
+const RESTART_REQUIRED: &[&str] = &["server.port", "accounts", "process.pool", "server.auth_monitor", "install", "claude_path", "claude"];
+
+/// Outcome of config reloads, for `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigReloadStatus {
+    pub enabled: bool,
+    pub reloads: u64,
+    pub rejected: u64,
+    pub last_reload: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Background task that applies edits to clay.yaml, clay.local.yaml and their
+/// includes to new requests; running conversations keep the settings they started with
+pub struct ConfigWatcher {
+    setup: Arc<ClaudeSetup>,
+    /// Setups of the accounts listed in clay.yaml, each with its own Claude home
+    accounts: Vec<Arc<ClaudeSetup>>,
+    /// Warm children started with the old config, drained on every reload
+    pools: Vec<Arc<ProcessPool>>,
+    enabled: bool,
+    files: Mutex<Vec<PathBuf>>,
+    contents: Mutex<Vec<Option<String>>>,
+    reloads: AtomicU64,
+    rejected: AtomicU64,
+    last_reload: Mutex<Option<DateTime<Utc>>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ConfigWatcher {
+    pub fn new(setup: Arc<ClaudeSetup>, accounts: &AccountPool) -> Self {
+        let enabled = !setup.get_config_files().is_empty() && setup.get_config()
+            .and_then(|config| config.server.as_ref().map(|server| server.watch_config))
+            .unwrap_or(true);
+        let pools = accounts.accounts().iter().filter_map(|account| account.pool().cloned()).collect();
+        let accounts = accounts.accounts().iter()
+            .map(|account| account.setup().clone())
+            .filter(|account| account.get_account_name().is_some())
+            .collect();
+
+        let watcher = ConfigWatcher {
+            accounts,
+            pools,
+            enabled,
+            files: Mutex::new(Vec::new()),
+            contents: Mutex::new(Vec::new()),
+            reloads: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            last_reload: Mutex::new(None),
+            last_error: Mutex::new(None),
+            setup,
+        };
+        watcher.set_files(watcher.setup.check_config_layers().map(|r| r.files).unwrap_or_default());
+        watcher
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Check for changes every `WATCH_INTERVAL`
+    pub fn start(self: &Arc<Self>) {
+        if !self.enabled {
+            return;
+        }
+        info!("Watching clay.yaml for changes");
+        let watcher = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(WATCH_INTERVAL).await;
+                let check = watcher.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || check.check_now()).await {
+                    warn!("Config watcher failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Reload if any configuration file changed since the last check; true when a new config was applied
+    pub fn check_now(&self) -> bool {
+        let files = self.files.lock().unwrap().clone();
+        let contents = read_all(&files);
+        if *self.contents.lock().unwrap() == contents {
+            return false;
+        }
+        *self.contents.lock().unwrap() = contents;
+        self.reload()
+    }
+
+    /// Re-read the configuration and apply it, keeping the running one if it's invalid
+    pub fn reload(&self) -> bool {
+        let previous = self.setup.get_config();
+        match self.setup.reload_config() {
+            Ok(report) => {
+                for warning in &report.warnings {
+                    warn!("clay.yaml: {}", warning);
+                }
+                for account in &self.accounts {
+                    if let Err(e) = account.apply_config(report.config.clone()) {
+                        warn!("Failed to regenerate Claude config for account {}: {}", account.get_account_name().unwrap_or_default(), e);
+                    }
+                }
+                for pool in &self.pools {
+                    pool.drain();
+                }
+                if let Some(previous) = previous {
+                    for setting in restart_required(&previous, &report.config) {
+                        warn!("{} changed in clay.yaml; restart Clay to apply it", setting);
+                    }
+                }
+                self.set_files(report.files);
+                self.reloads.fetch_add(1, Ordering::Relaxed);
+                *self.last_reload.lock().unwrap() = Some(Utc::now());
+                *self.last_error.lock().unwrap() = None;
+                info!("🔄 Reloaded clay.yaml; new requests use the updated configuration");
+                true
+            }
+            Err(e) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                warn!("Rejected clay.yaml change, keeping the running configuration: {}", e);
+                *self.last_error.lock().unwrap() = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    pub fn status(&self) -> ConfigReloadStatus {
+        ConfigReloadStatus {
+            enabled: self.enabled,
+            reloads: self.reloads.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            last_reload: *self.last_reload.lock().unwrap(),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+
+    /// Watch the files the config came from, plus the two base layers so creating them counts
+    fn set_files(&self, mut files: Vec<PathBuf>) {
+        for path in self.setup.get_config_files() {
+            let path = path.canonicalize().unwrap_or(path);
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+        *self.contents.lock().unwrap() = read_all(&files);
+        *self.files.lock().unwrap() = files;
+    }
+}
+
+fn read_all(files: &[PathBuf]) -> Vec<Option<String>> {
+    files.iter().map(|file| fs::read_to_string(file).ok()).collect()
+}
+
+/// Settings in `RESTART_REQUIRED` that differ between two configs
+fn restart_required(old: &Config, new: &Config) -> Vec<&'static str> {
+    let (old, new) = (serde_json::to_value(old).unwrap_or_default(), serde_json::to_value(new).unwrap_or_default());
+    let at = |value: &serde_json::Value, setting: &str| value.pointer(&format!("/{}", setting.replace('.', "/"))).cloned();
+    RESTART_REQUIRED.iter()
+        .copied()
+        .filter(|setting| at(&old, setting) != at(&new, setting))
+        .collect()
+}
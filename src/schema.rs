@@ -0,0 +1,173 @@
+use crate::config::Config;
+use serde_json::{json, Value};
+
+/// JSON Schema for clay.yaml, for editor completion and validation
+pub fn json_schema() -> Value {
+    let mut schema = schemars::schema_for!(Config).to_value();
+    schema["title"] = Value::from("clay.yaml");
+    // Handled while layering files, before the rest becomes a `Config`
+    schema["properties"]["include"] = json!({
+        "description": "Files merged underneath this one, relative to it",
+        "anyOf": [
+            { "type": "string" },
+            { "type": "array", "items": { "type": "string" } }
+        ]
+    });
+    schema["properties"]["profiles"] = json!({
+        "description": "Named overrides selected with --profile or CLAY_PROFILE",
+        "type": "object",
+        "additionalProperties": { "$ref": "#" }
+    });
+    schema
+}
+
+/// Warning for a key clay.yaml doesn't know, with a suggestion when a known key is close.
+/// `location` is where the key was found, e.g. `line 3, column 1`.
+pub fn unknown_key_warning(schema: &Value, path: &str, location: Option<&str>) -> String {
+    let segments: Vec<&str> = path.split('.').collect();
+    let (key, parent) = segments.split_last().unwrap_or((&"", &[]));
+    let location = location.map(|location| format!(" ({})", location)).unwrap_or_default();
+
+    let suggestion = properties_at(schema, parent)
+        .and_then(|known| closest(key, &known))
+        .map(|known| format!("; did you mean `{}`?", known))
+        .unwrap_or_default();
+    format!("unknown key `{}`{}{}", path, location, suggestion)
+}
+
+/// Line and column (1-based) of the key at `path`, found by following indentation
+pub fn locate(data: &str, path: &[&str]) -> Option<(usize, usize)> {
+    locate_deepest(data, path).map(|(_, line, column)| (line, column))
+}
+
+/// Like `locate`, also returning how many keys of `path` matched, for when
+/// only an enclosing key exists in `data`
+pub fn locate_deepest(data: &str, path: &[&str]) -> Option<(usize, usize, usize)> {
+    // Sequence indices don't appear as keys; the enclosing key is close enough
+    let keys: Vec<&str> = path.iter()
+        .map(|segment| segment.split('[').next().unwrap_or(segment))
+        .filter(|segment| !segment.is_empty() && segment.parse::<usize>().is_err())
+        .collect();
+    let mut stack: Vec<(usize, &str)> = Vec::new();
+    let mut found = None;
+
+    for (number, line) in data.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        // A key opening a sequence item (`- name: x`) sits after the dash
+        let content = content.trim_start_matches("- ");
+        let indent = line.len() - content.len();
+        let Some((key, _)) = content.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().trim_matches(|c| c == '"' || c == '\'');
+
+        while stack.last().is_some_and(|(depth, _)| *depth >= indent) {
+            stack.pop();
+        }
+        stack.push((indent, key));
+
+        let depth = stack.len();
+        if depth <= keys.len() && stack.iter().zip(&keys).all(|((_, a), b)| a == b) {
+            found = Some((depth, number + 1, indent + 1));
+            if depth == keys.len() {
+                return found;
+            }
+        }
+    }
+    found
+}
+
+/// Whether the setting at `path` takes a string, so a value given for it should stay text
+/// even when it looks like a number. Sequence items are numeric segments.
+pub fn accepts_string(schema: &Value, path: &[&str]) -> bool {
+    let Some(node) = node_at(schema, path) else {
+        return false;
+    };
+    match node.get("type") {
+        Some(Value::String(kind)) => kind == "string",
+        Some(Value::Array(kinds)) => kinds.iter().any(|kind| kind == "string"),
+        _ => false,
+    }
+}
+
+/// How clay.yaml spells a `__`-separated environment path: setting names are lowercased,
+/// map keys such as MCP server names and `env` variables keep the case they were written in
+pub fn key_path(schema: &Value, segments: &[&str]) -> Vec<String> {
+    let mut node = Some(resolve(schema, schema));
+    segments
+        .iter()
+        .map(|segment| {
+            let key = segment.to_lowercase();
+            let Some(current) = node else {
+                return key;
+            };
+            if let Some(child) = current.get("properties").and_then(|p| p.get(&key)) {
+                node = Some(resolve(schema, child));
+                key
+            } else if let Some(items) = current.get("items").filter(|_| segment.parse::<usize>().is_ok()) {
+                node = Some(resolve(schema, items));
+                key
+            } else if let Some(values) = current.get("additionalProperties").filter(|v| v.is_object()) {
+                node = Some(resolve(schema, values));
+                segment.to_string()
+            } else {
+                // Unknown settings are reported later; spell them like the known ones
+                node = None;
+                key
+            }
+        })
+        .collect()
+}
+
+/// Keys allowed in the object at `path`
+fn properties_at(schema: &Value, path: &[&str]) -> Option<Vec<String>> {
+    let properties = node_at(schema, path)?.get("properties")?.as_object()?;
+    Some(properties.keys().cloned().collect())
+}
+
+/// Schema of the value at `path`, following `$ref`s, optional values,
+/// maps (any key) and sequences
+fn node_at<'a>(schema: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    let mut node = resolve(schema, schema);
+    for segment in path {
+        node = if let Some(child) = node.get("properties").and_then(|p| p.get(*segment)) {
+            resolve(schema, child)
+        } else if let Some(items) = node.get("items").filter(|_| segment.parse::<usize>().is_ok()) {
+            resolve(schema, items)
+        } else if let Some(values) = node.get("additionalProperties").filter(|v| v.is_object()) {
+            resolve(schema, values)
+        } else {
+            return None;
+        };
+    }
+    Some(node)
+}
+
+/// Follow `$ref` and pick the non-null branch of `anyOf`/`oneOf` (how optional fields are described)
+fn resolve<'a>(root: &'a Value, node: &'a Value) -> &'a Value {
+    if let Some(reference) = node.get("$ref").and_then(|r| r.as_str()) {
+        let pointer = reference.trim_start_matches('#');
+        if let Some(target) = root.pointer(pointer) {
+            return resolve(root, target);
+        }
+    }
+    for combinator in ["anyOf", "oneOf", "allOf"] {
+        if let Some(branches) = node.get(combinator).and_then(|b| b.as_array()) {
+            if let Some(branch) = branches.iter().find(|b| b.get("type") != Some(&Value::from("null"))) {
+                return resolve(root, branch);
+            }
+        }
+    }
+    node
+}
+
+fn closest(key: &str, known: &[String]) -> Option<String> {
+    known.iter()
+        .map(|candidate| (strsim::damerau_levenshtein(key, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.clone())
+}
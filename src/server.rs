@@ -1,16 +1,27 @@
+use crate::export::{self, ExportFormat};
+use crate::auth::{LoginResult, SetupTokenSession};
+use crate::error::ClaudeRelayError;
+use crate::monitor::AuthMonitor;
+use crate::reload::ConfigWatcher;
+use crate::install::ClaudeBinary;
+use crate::accounts::{Account, AccountPool, AccountStatus};
+use crate::stream::StreamMonitor;
 use crate::{ClaudeProcess, ClaudeSetup};
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tower_http::cors::CorsLayer;
+use subtle::ConstantTimeEq;
 use tracing::{info, warn};
 use uuid::Uuid;
 
@@ -120,51 +131,481 @@ pub struct Model {
     pub owned_by: String,
 }
 
+/// Header clients use to select a conversation session
+pub const SESSION_HEADER: &str = "x-session-id";
+
+const DEFAULT_SESSION: &str = "default";
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthCompleteRequest {
+    pub code: String,
+    /// Account to store a pasted token for; a pending login already knows its account
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountQuery {
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Session count and persistent children, copied out of `AppState::processes`
+#[derive(Default)]
+struct SessionHealth {
+    sessions: usize,
+    streams: Vec<(String, Arc<StreamMonitor>)>,
+}
+
+/// One conversation; its own lock lets other sessions run while it waits on Claude
+type Session = Arc<Mutex<ClaudeProcess>>;
+
+/// A `setup-token` run waiting for its code, and the account it logs in
+type PendingLogin = (Arc<Account>, SetupTokenSession);
+
 pub struct AppState {
     claude_setup: Arc<ClaudeSetup>,
-    processes: RwLock<HashMap<String, ClaudeProcess>>,
+    processes: RwLock<HashMap<String, Session>>,
+    accounts: AccountPool,
+    auth_monitor: Arc<AuthMonitor>,
+    config_watcher: Arc<ConfigWatcher>,
+    /// The CLI in use when the server started
+    claude_cli: ClaudeBinary,
+    auth_url: RwLock<Option<String>>,
+    /// What `/health` reports about sessions, refreshed whenever `processes` changes so
+    /// health checks never wait behind a chat turn
+    session_health: std::sync::Mutex<SessionHealth>,
+    // std Mutex: the session is only touched from blocking tasks
+    pending_login: Arc<std::sync::Mutex<Option<PendingLogin>>>,
 }
 
 impl AppState {
     pub fn new(claude_setup: Arc<ClaudeSetup>) -> Self {
+        let accounts = AccountPool::new(claude_setup.clone());
+        let auth_monitor = Arc::new(AuthMonitor::new(claude_setup.get_auth_monitor_config(), &accounts));
+        let config_watcher = Arc::new(ConfigWatcher::new(claude_setup.clone(), &accounts));
         Self {
+            accounts,
+            auth_monitor,
+            config_watcher,
+            claude_cli: claude_setup.get_claude_binary(),
             claude_setup,
             processes: RwLock::new(HashMap::new()),
+            auth_url: RwLock::new(None),
+            session_health: std::sync::Mutex::new(SessionHealth::default()),
+            pending_login: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Whether chat requests can be served; false while the server runs in degraded mode
+    pub fn is_authenticated(&self) -> bool {
+        self.accounts.any_authenticated()
+    }
+
+    pub fn get_accounts(&self) -> &AccountPool {
+        &self.accounts
+    }
+
+    pub fn get_auth_monitor(&self) -> &Arc<AuthMonitor> {
+        &self.auth_monitor
+    }
+
+    pub fn get_config_watcher(&self) -> &Arc<ConfigWatcher> {
+        &self.config_watcher
+    }
+
+    /// Account to log in: the named one, else the first without credentials
+    fn login_account(&self, name: Option<&str>) -> crate::Result<Arc<Account>> {
+        let accounts = self.accounts.accounts();
+        match name {
+            Some(name) => self.accounts.get(name)
+                .ok_or_else(|| ClaudeRelayError::Config(format!("No account named '{}'", name))),
+            None => Ok(accounts.iter()
+                .find(|account| !account.is_authenticated())
+                .unwrap_or(&accounts[0])
+                .clone()),
+        }
+    }
+
+    /// Start `claude setup-token` and keep it waiting for the code; returns the login URL
+    pub async fn start_auth(&self, account: Option<&str>) -> crate::Result<String> {
+        let account = self.login_account(account)?;
+        let pending = self.pending_login.clone();
+        let url = tokio::task::spawn_blocking(move || {
+            // Replacing an older attempt kills its setup-token child
+            let session = account.setup().start_login()?;
+            let url = session.auth_url().to_string();
+            *pending.lock().unwrap() = Some((account, session));
+            Ok::<_, ClaudeRelayError>(url)
+        })
+        .await
+        .map_err(|e| ClaudeRelayError::Authentication(format!("Login task failed: {}", e)))??;
+
+        *self.auth_url.write().await = Some(url.clone());
+        Ok(url)
+    }
+
+    /// Finish the login with the code from the auth URL, or a token from `claude setup-token`
+    pub async fn complete_auth(&self, code: String, account: Option<&str>) -> crate::Result<LoginResult> {
+        let fallback = self.login_account(account)?;
+        let pending = self.pending_login.clone();
+        let (account, result) = tokio::task::spawn_blocking(move || {
+            let (account, session) = match pending.lock().unwrap().take() {
+                Some((account, session)) => (account, Some(session)),
+                None => (fallback, None),
+            };
+            let result = account.setup().complete_login(session, &code);
+            (account, result)
+        })
+        .await
+        .map_err(|e| ClaudeRelayError::Authentication(format!("Login task failed: {}", e)))?;
+        let result = result?;
+
+        // A setup-token session is single use, so a rejected code needs a fresh URL
+        *self.auth_url.write().await = None;
+        if result.is_usable() && account.refresh_authentication() {
+            info!("Account {} authenticated; chat endpoints are now available", account.name());
+        }
+        Ok(result)
+    }
+
+    /// Copy what `/health` needs out of the locked session map; a session in the middle
+    /// of a turn keeps the monitor it last reported
+    fn refresh_session_health(&self, processes: &HashMap<String, Session>) {
+        let mut health = self.session_health.lock().unwrap();
+        let mut streams: Vec<_> = processes
+            .iter()
+            .filter_map(|(id, session)| {
+                let monitor = match session.try_lock() {
+                    Ok(process) => process.stream_monitor(),
+                    Err(_) => health.streams.iter().find(|(known, _)| known == id).map(|(_, monitor)| monitor.clone()),
+                };
+                monitor.map(|monitor| (id.clone(), monitor))
+            })
+            .collect();
+        streams.sort_by(|a, b| a.0.cmp(&b.0));
+        *health = SessionHealth { sessions: processes.len(), streams };
+    }
+
+    /// Setup for a new session: an available account's, else the first account's
+    fn default_setup(&self) -> Arc<ClaudeSetup> {
+        self.accounts.pick()
+            .unwrap_or_else(|| self.accounts.accounts()[0].clone())
+            .setup()
+            .clone()
+    }
+
+    fn attach_pool(&self, process: &mut ClaudeProcess) {
+        if let Some(pool) = self.accounts.for_setup(process.get_setup()).and_then(|a| a.pool().cloned()) {
+            process.set_pool(pool);
+        }
+    }
+
+    /// The session's process, created on an available account if the session is new
+    async fn session(&self, session_id: &str) -> crate::Result<Session> {
+        let mut processes = self.processes.write().await;
+        if let Some(session) = processes.get(session_id) {
+            return Ok(session.clone());
+        }
+
+        let mut process = ClaudeProcess::new(self.default_setup())?;
+        self.attach_pool(&mut process);
+        make_room(&mut processes, self.claude_setup.get_max_processes());
+        let session = Arc::new(Mutex::new(process));
+        processes.insert(session_id.to_string(), session.clone());
+        self.refresh_session_health(&processes);
+        Ok(session)
+    }
+
+    /// Make sure the session runs on an available account, moving it if its account is
+    /// cooling down; returns the account to send through
+    fn bind_session(&self, process: &mut ClaudeProcess, session_id: &str) -> crate::Result<Option<Arc<Account>>> {
+        let current = self.accounts.for_setup(process.get_setup());
+        if let Some(account) = current.filter(|account| account.is_available()) {
+            return Ok(Some(account));
+        }
+
+        let Some(account) = self.accounts.pick() else {
+            return Ok(None);
+        };
+
+        // CLI sessions live in the old account's home, so continue from our own history
+        info!("Moving session {} to account {}", session_id, account.name());
+        let mut moved = ClaudeProcess::with_history(
+            account.setup().clone(),
+            process.get_conversation_history().to_vec(),
+            process.get_conversation_states().to_vec(),
+            process.get_last_undone_history().map(|h| h.to_vec()),
+        )?;
+        self.attach_pool(&mut moved);
+        let mut previous = std::mem::replace(process, moved);
+        cleanup_process(session_id, &mut previous);
+        Ok(Some(account))
+    }
+
+    /// Send one turn, giving each account at most one try; limits and lost logins move the
+    /// session on. `Ok(None)` means no account could take the turn.
+    fn send_turn(&self, process: &mut ClaudeProcess, session_id: &str, prompt: &str) -> crate::Result<Option<String>> {
+        for _ in 0..self.accounts.accounts().len() {
+            let account = match self.bind_session(process, session_id) {
+                Ok(Some(account)) => account,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Failed to create Claude process: {}", e);
+                    return Err(e);
+                }
+            };
+            account.record_request();
+
+            match process.send_message(prompt) {
+                Ok(text) => return Ok(Some(text)),
+                Err(ClaudeRelayError::UsageLimit { message, reset_at }) => {
+                    let until = reset_at.unwrap_or_else(|| self.accounts.default_cooldown_until());
+                    account.start_cooldown(until, &message);
+                }
+                Err(ClaudeRelayError::Authentication(e)) => {
+                    // Credentials expired or were revoked: stop using this account
+                    account.mark_unauthenticated(&e);
+                }
+                Err(e) => {
+                    warn!("Failed to send message to Claude: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Export the conversation of a session, or `None` if the session doesn't exist
+    pub async fn export_session(&self, session_id: &str, format: ExportFormat) -> crate::Result<Option<String>> {
+        let session = self.processes.read().await.get(session_id).cloned();
+        match session {
+            Some(session) => export::export_process(&*session.lock().await, Some(session_id), format).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Seed a session from exported conversation data, replacing any existing session with that id
+    pub async fn import_session(
+        &self,
+        session_id: Option<String>,
+        data: &str,
+        format: Option<ExportFormat>,
+    ) -> crate::Result<String> {
+        let export = export::ConversationExport::parse(data, format)?;
+        let session_id = session_id
+            .or(export.session_id.clone())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let mut process = ClaudeProcess::with_history(
+            self.default_setup(),
+            export.history,
+            export.states,
+            export.last_undone_history,
+        )?;
+        self.attach_pool(&mut process);
+
+        let previous = {
+            let mut processes = self.processes.write().await;
+            let previous = processes.remove(&session_id);
+            make_room(&mut processes, self.claude_setup.get_max_processes());
+            processes.insert(session_id.clone(), Arc::new(Mutex::new(process)));
+            self.refresh_session_health(&processes);
+            previous
+        };
+        if let Some(previous) = previous {
+            cleanup_process(&session_id, &mut *previous.lock().await);
+        }
+        Ok(session_id)
+    }
+
+    /// Drop a session and its Claude CLI session files; returns false if it didn't exist
+    pub async fn evict_session(&self, session_id: &str) -> bool {
+        let removed = {
+            let mut processes = self.processes.write().await;
+            let removed = processes.remove(session_id);
+            self.refresh_session_health(&processes);
+            removed
+        };
+        match removed {
+            Some(session) => {
+                // Wait out a turn in progress before removing its files
+                cleanup_process(session_id, &mut *session.lock().await);
+                true
+            }
+            None => false,
         }
     }
 }
 
-pub async fn start_server(claude_setup: Arc<ClaudeSetup>, port: u16) -> crate::Result<()> {
-    let app_state = Arc::new(AppState::new(claude_setup));
+fn cleanup_process(session_id: &str, process: &mut ClaudeProcess) {
+    if let Err(e) = process.cleanup_session_files() {
+        warn!("Failed to clean up Claude CLI files for session {}: {}", session_id, e);
+    }
+}
 
-    let app = Router::new()
+/// Evict least recently used sessions until there is room for one more; sessions in
+/// the middle of a turn are never idle
+fn make_room(processes: &mut HashMap<String, Session>, max_processes: usize) {
+    while processes.len() >= max_processes.max(1) {
+        let oldest = processes
+            .iter()
+            .filter_map(|(id, session)| session.try_lock().ok().map(|process| (id, process.get_last_activity())))
+            .min_by_key(|(_, last_activity)| *last_activity)
+            .map(|(id, _)| id.clone());
+
+        let Some(id) = oldest else {
+            break;
+        };
+        info!("Evicting idle session {}", id);
+        if let Some(session) = processes.remove(&id) {
+            if let Ok(mut process) = session.try_lock() {
+                cleanup_process(&id, &mut process);
+            }
+        }
+    }
+}
+
+/// All HTTP routes, bound to the given state
+pub fn router(app_state: Arc<AppState>) -> Router {
+    Router::new()
         .route("/v1/chat/completions", post(chat_completions))
         .route("/v1/models", get(list_models))
+        .route("/v1/sessions/:id", delete(delete_session))
+        .route("/v1/sessions/:id/export", get(export_session))
+        .route("/v1/sessions/import", post(import_session))
+        .route("/admin/auth/start", post(auth_start))
+        .route("/admin/auth/complete", post(auth_complete))
+        .route("/admin/accounts", get(list_accounts))
         .route("/health", get(health_check))
         .layer(CorsLayer::permissive())
-        .with_state(app_state);
+        .with_state(app_state)
+}
+
+pub async fn start_server(claude_setup: Arc<ClaudeSetup>, port: u16) -> crate::Result<()> {
+    claude_setup.check_config()?;
+    let app_state = Arc::new(AppState::new(claude_setup));
+    for pool in app_state.get_accounts().accounts().iter().filter_map(|a| a.pool()) {
+        pool.start_replenisher();
+    }
+    for account in app_state.get_accounts().accounts() {
+        for warning in account.setup().check_credential_permissions() {
+            warn!("🔓 {}", warning);
+        }
+    }
+    app_state.auth_monitor.start();
+    app_state.config_watcher.start();
+
+    let app = router(app_state.clone());
 
     let addr = format!("0.0.0.0:{}", port);
     info!("🚀 Claude Relay OpenAI-compatible server starting on {}", addr);
     info!("📡 API endpoints:");
     info!("   POST http://localhost:{}/v1/chat/completions", port);
     info!("   GET  http://localhost:{}/v1/models", port);
+    info!("   DELETE http://localhost:{}/v1/sessions/:id", port);
+    info!("   GET  http://localhost:{}/v1/sessions/:id/export", port);
+    info!("   POST http://localhost:{}/v1/sessions/import", port);
+    info!("   POST http://localhost:{}/admin/auth/start", port);
+    info!("   POST http://localhost:{}/admin/auth/complete", port);
+    info!("   GET  http://localhost:{}/admin/accounts", port);
     info!("   GET  http://localhost:{}/health", port);
+    
+    if app_state.claude_setup.get_admin_token().is_none() {
+        warn!("🔒 server.admin_token is unset; /admin endpoints only answer requests from this machine");
+    }
+    if !app_state.is_authenticated() {
+        warn!("🔒 Claude is not authenticated; chat endpoints return 401 until login completes");
+        warn!("   POST http://localhost:{}/admin/auth/start to get a login URL", port);
+    }
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
 
-async fn health_check() -> Json<serde_json::Value> {
+/// Fields of `/health` that name paths or echo credential errors; only admins see them
+const PRIVATE_HEALTH_FIELDS: [&str; 5] = ["claude_home", "auth_detail", "last_error", "detail", "path"];
+
+/// Serialize `item`, dropping the private fields unless the caller is an admin
+fn health_entry<T: Serialize>(item: T, admin: bool) -> serde_json::Value {
+    let mut value = serde_json::to_value(item).unwrap_or_default();
+    if let (false, Some(fields)) = (admin, value.as_object_mut()) {
+        for field in PRIVATE_HEALTH_FIELDS {
+            fields.remove(field);
+        }
+    }
+    value
+}
+
+async fn health_check(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Json<serde_json::Value> {
+    let admin = is_admin(&state, &headers, peer);
+    let (sessions, streams) = {
+        let health = state.session_health.lock().unwrap();
+        (health.sessions, health.streams.clone())
+    };
+    let streaming: Vec<_> = streams
+        .iter()
+        .map(|(id, monitor)| {
+            let health = monitor.health();
+            serde_json::json!({
+                "session": id,
+                "alive": health.alive,
+                "pid": health.pid,
+                "turns": health.turns,
+                "uptime_secs": health.uptime_secs,
+            })
+        })
+        .collect();
+
     Json(serde_json::json!({
         "status": "ok",
         "service": "clay",
-        "version": "0.1.0"
+        "version": "0.1.0",
+        "authenticated": state.is_authenticated(),
+        "sessions": sessions,
+        "streaming": streaming,
+        "accounts": state.accounts.status().into_iter().map(|account| health_entry(account, admin)).collect::<Vec<_>>(),
+        "auth_monitor": {
+            "enabled": state.auth_monitor.is_enabled(),
+            "accounts": state.auth_monitor.health().into_iter().map(|health| health_entry(health, admin)).collect::<Vec<_>>(),
+            "metrics": state.auth_monitor.metrics()
+        },
+        "config_reload": state.config_watcher.status(),
+        "claude_cli": health_entry(&state.claude_cli, admin)
     }))
 }
 
+async fn list_accounts(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> std::result::Result<Json<Vec<AccountStatus>>, Response> {
+    if !is_admin(&state, &headers, peer) {
+        return Err(admin_denied());
+    }
+    Ok(Json(state.accounts.status()))
+}
+
 async fn list_models() -> Json<ModelsResponse> {
     Json(ModelsResponse {
         object: "list".to_string(),
@@ -191,38 +632,247 @@ async fn list_models() -> Json<ModelsResponse> {
     })
 }
 
+async fn export_session(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> std::result::Result<Response, Response> {
+    if !is_admin(&state, &headers, peer) {
+        return Err(admin_denied());
+    }
+    let format = match query.format.as_deref() {
+        Some(format) => format.parse::<ExportFormat>().map_err(|_| StatusCode::BAD_REQUEST.into_response())?,
+        None => ExportFormat::Json,
+    };
+
+    match state.export_session(&session_id, format).await {
+        Ok(Some(body)) => Ok(([(header::CONTENT_TYPE, format.content_type())], body).into_response()),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            warn!("Failed to export session {}: {}", session_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+async fn delete_session(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Response {
+    if !is_admin(&state, &headers, peer) {
+        return admin_denied();
+    }
+    if state.evict_session(&session_id).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
+
+async fn import_session(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> std::result::Result<Json<serde_json::Value>, Response> {
+    if !is_admin(&state, &headers, peer) {
+        return Err(admin_denied());
+    }
+    let format = match query.format.as_deref() {
+        Some(format) => Some(format.parse::<ExportFormat>().map_err(|_| StatusCode::BAD_REQUEST.into_response())?),
+        None => None,
+    };
+
+    match state.import_session(query.session_id, &body, format).await {
+        Ok(session_id) => Ok(Json(serde_json::json!({ "session_id": session_id }))),
+        Err(e) => {
+            warn!("Failed to import session: {}", e);
+            Err(StatusCode::BAD_REQUEST.into_response())
+        }
+    }
+}
+
+fn error_response(status: StatusCode, error_type: &str, message: &str) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": message,
+            "type": error_type
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+/// 401 telling the client how to finish the login, in OpenAI's error shape
+async fn auth_required_response(state: &AppState) -> Response {
+    let auth_url = state.auth_url.read().await.clone();
+    let body = serde_json::json!({
+        "error": {
+            "message": "Claude is not authenticated. POST /admin/auth/start, open the returned URL, then POST the code to /admin/auth/complete.",
+            "type": "authentication_error",
+            "auth_url": auth_url
+        }
+    });
+    (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+}
+
+/// 429 while every account is cooling down, with `Retry-After` when the reset time is known
+fn usage_limited_response(retry_at: Option<chrono::DateTime<chrono::Utc>>) -> Response {
+    let mut response = error_response(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limit_error",
+        "All Claude accounts have reached their usage limit",
+    );
+    if let Some(retry_at) = retry_at {
+        let seconds = (retry_at - chrono::Utc::now()).num_seconds().max(1);
+        if let Ok(value) = header::HeaderValue::from_str(&seconds.to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+    }
+    response
+}
+
+/// Map a failed Claude call to an HTTP status, with `Retry-After` for failures that pass on their own
+fn cli_error_response(error: &ClaudeRelayError) -> Response {
+    let (status, error_type, code) = match error {
+        ClaudeRelayError::UsageLimit { .. } => (StatusCode::TOO_MANY_REQUESTS, "rate_limit_error", "usage_limit_reached"),
+        ClaudeRelayError::Overloaded(_) => (StatusCode::SERVICE_UNAVAILABLE, "overloaded_error", "overloaded"),
+        ClaudeRelayError::ContextTooLong(_) => (StatusCode::BAD_REQUEST, "invalid_request_error", "context_length_exceeded"),
+        ClaudeRelayError::InvalidModel(_) => (StatusCode::NOT_FOUND, "invalid_request_error", "model_not_found"),
+        ClaudeRelayError::McpFailure(_) => (StatusCode::BAD_GATEWAY, "mcp_error", "mcp_server_failed"),
+        ClaudeRelayError::Authentication(_) => (StatusCode::UNAUTHORIZED, "authentication_error", "invalid_api_key"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "server_error", "claude_error"),
+    };
+    let body = serde_json::json!({
+        "error": {
+            "message": error.to_string(),
+            "type": error_type,
+            "code": code
+        }
+    });
+    let mut response = (status, Json(body)).into_response();
+    if let Some(seconds) = error.retry_after() {
+        response.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from(seconds));
+    }
+    response
+}
+
+/// Check the `Authorization: Bearer` header against `server.admin_token`. Without a
+/// token only clients on this machine are admitted, since the server listens on every interface.
+fn is_admin(state: &AppState, headers: &HeaderMap, peer: Option<ConnectInfo<SocketAddr>>) -> bool {
+    let Some(expected) = state.claude_setup.get_admin_token() else {
+        return peer.is_some_and(|ConnectInfo(addr)| addr.ip().is_loopback());
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Compare digests so neither the contents nor the length leak through timing
+    Sha256::digest(provided.as_bytes()).ct_eq(&Sha256::digest(expected.as_bytes())).into()
+}
+
+fn admin_denied() -> Response {
+    error_response(StatusCode::UNAUTHORIZED, "invalid_request_error", "Invalid admin token")
+}
+
+async fn auth_start(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(query): Query<AccountQuery>,
+) -> std::result::Result<Json<serde_json::Value>, Response> {
+    if !is_admin(&state, &headers, peer) {
+        return Err(admin_denied());
+    }
+
+    match state.start_auth(query.account.as_deref()).await {
+        Ok(url) => Ok(Json(serde_json::json!({
+            "auth_url": url,
+            "next": "Open auth_url, sign in, then POST {\"code\": \"...\"} to /admin/auth/complete"
+        }))),
+        Err(e) => {
+            warn!("Failed to start authentication: {}", e);
+            Err(error_response(StatusCode::BAD_GATEWAY, "authentication_error", &e.to_string()))
+        }
+    }
+}
+
+async fn auth_complete(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(request): Json<AuthCompleteRequest>,
+) -> std::result::Result<Json<serde_json::Value>, Response> {
+    if !is_admin(&state, &headers, peer) {
+        return Err(admin_denied());
+    }
+
+    if request.code.trim().is_empty() {
+        return Err(error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "code is required"));
+    }
+
+    match state.complete_auth(request.code, request.account.as_deref()).await {
+        Ok(LoginResult::Rejected { reason }) => {
+            warn!("Login was rejected: {}", reason);
+            Err(error_response(StatusCode::BAD_REQUEST, "authentication_error", &reason))
+        }
+        Ok(result) => Ok(Json(serde_json::json!({
+            "authenticated": true,
+            "login": result
+        }))),
+        Err(e) => {
+            warn!("Failed to complete authentication: {}", e);
+            Err(error_response(StatusCode::BAD_REQUEST, "authentication_error", &e.to_string()))
+        }
+    }
+}
+
 async fn chat_completions(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(request): Json<ChatCompletionRequest>,
-) -> std::result::Result<Json<ChatCompletionResponse>, StatusCode> {
-    // Get or create a Claude process
-    let process_id = "default"; // For now, use a single process
-    let mut processes = state.processes.write().await;
-    
-    if !processes.contains_key(process_id) {
-        match ClaudeProcess::new(state.claude_setup.clone()) {
-            Ok(process) => {
-                processes.insert(process_id.to_string(), process);
-            }
-            Err(e) => {
-                warn!("Failed to create Claude process: {}", e);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        }
+) -> std::result::Result<Json<ChatCompletionResponse>, Response> {
+    if !state.is_authenticated() {
+        return Err(auth_required_response(&state).await);
     }
 
-    let process = processes.get_mut(process_id).unwrap();
+    let process_id = headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(DEFAULT_SESSION);
 
     // Convert OpenAI messages to Claude prompt
     let prompt = build_claude_prompt(&request.messages, &request.tools);
-    
-    // Send message to Claude
-    let response_text = match process.send_message(&prompt) {
-        Ok(text) => text,
+
+    let session = state.session(process_id).await.map_err(|e| {
+        warn!("Failed to create Claude process: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
+    // The CLI call blocks, so the turn runs on a worker thread and only this session waits for it
+    let mut process = session.lock_owned().await;
+    let (turn_state, session_id, turn_prompt) = (state.clone(), process_id.to_string(), prompt.clone());
+    let turn = tokio::task::spawn_blocking(move || turn_state.send_turn(&mut process, &session_id, &turn_prompt)).await;
+    state.refresh_session_health(&*state.processes.read().await);
+    let response_text = match turn {
+        Ok(result) => result.map_err(|e| cli_error_response(&e))?,
         Err(e) => {
-            warn!("Failed to send message to Claude: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            warn!("Chat turn failed: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    let Some(response_text) = response_text else {
+        if !state.is_authenticated() {
+            // Every account lost its login: drop back to degraded mode
+            return Err(auth_required_response(&state).await);
         }
+        return Err(usage_limited_response(state.accounts.next_available_at()));
     };
 
     // Parse response for tool calls if needed
@@ -314,7 +964,7 @@ fn build_claude_prompt(messages: &[ChatMessage], tools: &Option<Vec<Tool>>) -> S
 
 fn parse_claude_response(response: &str, tools: &Option<Vec<Tool>>) -> (String, Option<Vec<ToolCall>>) {
     // Check if response contains tool calls
-    if let Some(_) = tools {
+    if tools.is_some() {
         if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(response) {
             if let Some(tool_calls_value) = parsed.get("tool_calls") {
                 if let Ok(tool_calls) = serde_json::from_value::<Vec<serde_json::Value>>(tool_calls_value.clone()) {
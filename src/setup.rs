@@ -1,9 +1,15 @@
+use crate::auth::{CredentialCache, API_KEY_ENV, OAUTH_TOKEN_ENV};
+use crate::cache::{cached_bun_zip, current_bun_asset};
+use crate::classify::{classify, CliFailure};
 use crate::error::{ClaudeRelayError, Result};
-use crate::config::{Config, McpConfig};
+use crate::install::{is_executable, resolve_claude_path, InstallStrategy};
+use crate::proxy::McpProxy;
+use crate::config::{AccountConfig, AccountsConfig, AuthConfig, AuthMonitorConfig, Config, ConfigOptions, ConfigReport, McpConfig, ProcessConfig, SessionMode};
 use std::env;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::process::{Command, Stdio};
 use tracing::{info, warn};
 use serde_json::json;
@@ -13,39 +19,213 @@ pub struct ClaudeSetup {
     bun_path: PathBuf,
     claude_path: PathBuf,
     claude_home: PathBuf,
+    install_strategy: InstallStrategy,
+    // Swapped whole when clay.yaml is reloaded
+    config: RwLock<Option<Arc<Config>>>,
+    /// Set when built with `ClaudeSetupBuilder::config`; clay.yaml is then ignored
+    in_memory_config: Option<Config>,
+    config_options: ConfigOptions,
+    quiet: bool,
+    account: Option<String>,
+    clay_binary: Option<PathBuf>,
+    credential_cache: CredentialCache,
+}
+
+/// Options for building a `ClaudeSetup`; nothing is written unless `generate_config` is on
+pub struct ClaudeSetupBuilder {
+    base_dir: PathBuf,
+    config_path: Option<PathBuf>,
     config: Option<Config>,
+    config_options: ConfigOptions,
+    install_dir: Option<PathBuf>,
+    install_strategy: Option<InstallStrategy>,
+    claude_home: Option<PathBuf>,
+    generate_config: bool,
+    require_config: bool,
+    quiet: bool,
+    clay_binary: Option<PathBuf>,
 }
 
-impl ClaudeSetup {
-    pub fn new(base_dir: &str) -> Result<Self> {
-        let base_dir = Path::new(base_dir)
+impl ClaudeSetupBuilder {
+    pub fn new(base_dir: impl AsRef<Path>) -> Self {
+        ClaudeSetupBuilder {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            config_path: None,
+            config: None,
+            config_options: ConfigOptions::default(),
+            install_dir: None,
+            install_strategy: None,
+            claude_home: None,
+            generate_config: false,
+            require_config: false,
+            quiet: false,
+            clay_binary: None,
+        }
+    }
+
+    /// Read this file instead of `<base_dir>/clay.yaml`; clay.local.yaml is looked for next to it
+    pub fn config_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.config_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use this configuration and never read or watch clay.yaml
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Profile and command-line overrides applied over the config files
+    pub fn config_options(mut self, options: ConfigOptions) -> Self {
+        self.config_options = options;
+        self
+    }
+
+    /// Where Bun and the Claude CLI are installed (default `<base_dir>/.bun`)
+    pub fn install_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.install_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// How to find or install the Claude CLI, instead of `install.strategy` from the config
+    pub fn install_strategy(mut self, strategy: InstallStrategy) -> Self {
+        self.install_strategy = Some(strategy);
+        self
+    }
+
+    /// The CLI's isolated home with its login and settings (default `<base_dir>/.claude-home`)
+    pub fn claude_home(mut self, path: impl AsRef<Path>) -> Self {
+        self.claude_home = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Write a sample clay.yaml when there is none, as the `clay` command does
+    pub fn generate_config(mut self, generate: bool) -> Self {
+        self.generate_config = generate;
+        self
+    }
+
+    /// Fail to build when clay.yaml can't be loaded, instead of running without it
+    pub fn require_config(mut self, require: bool) -> Self {
+        self.require_config = require;
+        self
+    }
+
+    /// Log instead of printing to stdout
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// The clay executable mcp.json runs as `clay mcp-proxy` for HTTP and WebSocket
+    /// servers. Only the `clay` command knows it is one; without it such servers are an error.
+    pub fn clay_binary(mut self, path: impl AsRef<Path>) -> Self {
+        self.clay_binary = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build(self) -> Result<ClaudeSetup> {
+        let base_dir = self.base_dir
             .canonicalize()
             .map_err(|e| ClaudeRelayError::Setup(format!("Failed to get absolute path: {}", e)))?;
+        let relative = |path: PathBuf| if path.is_absolute() { path } else { base_dir.join(path) };
+        let mut config_options = self.config_options;
+        if let Some(path) = self.config_path {
+            config_options.file = Some(relative(path));
+        }
         
-        // Auto-generate clay.yaml if it doesn't exist
-        let yaml_path = base_dir.join("clay.yaml");
-        if !yaml_path.exists() {
+        let yaml_path = config_options.config_file(&base_dir);
+        if self.generate_config && self.config.is_none() && !yaml_path.exists() {
             let sample_config = Config::generate_sample_yaml();
             fs::write(&yaml_path, sample_config)?;
             info!("Generated clay.yaml configuration file at {:?}", yaml_path);
-            println!("📝 Generated clay.yaml configuration file");
-            println!("   Edit this file to customize your MCP servers and context settings");
+            if !self.quiet {
+                println!("📝 Generated clay.yaml configuration file");
+                println!("   Edit this file to customize your MCP servers and context settings");
+            }
         }
         
-        // Load configuration with priority
-        let config = Config::load_with_priority(&base_dir).ok();
+        // Merge clay.yaml with its overrides; --validate-config reports why it failed
+        let config = match &self.config {
+            Some(config) => Some(config.clone()),
+            None => match Config::load_layered(&base_dir, &config_options) {
+                Ok(config) => Some(config),
+                Err(e) if self.require_config => return Err(e),
+                Err(e) => {
+                    warn!("Ignoring clay.yaml: {}", e);
+                    None
+                }
+            },
+        };
         
+        let bun_path = self.install_dir.map(relative).unwrap_or_else(|| base_dir.join(".bun"));
+        let install_strategy = self.install_strategy
+            .or_else(|| config.as_ref().map(|config| config.install_strategy()))
+            .unwrap_or_default();
         Ok(ClaudeSetup {
-            bun_path: base_dir.join(".bun"),
-            claude_path: base_dir.join(".bun").join("bin").join("claude"),
-            claude_home: base_dir.join(".claude-home"),
+            claude_path: resolve_claude_path(&install_strategy, &base_dir, &bun_path),
+            install_strategy,
+            bun_path,
+            claude_home: self.claude_home.map(relative).unwrap_or_else(|| base_dir.join(".claude-home")),
             base_dir,
-            config,
+            config: RwLock::new(config.map(Arc::new)),
+            in_memory_config: self.config,
+            config_options,
+            quiet: self.quiet,
+            account: None,
+            clay_binary: self.clay_binary,
+            credential_cache: CredentialCache::default(),
         })
     }
+}
+
+impl ClaudeSetup {
+    /// Open a project directory without changing it: clay.yaml is read if present, never created
+    pub fn new(base_dir: &str) -> Result<Self> {
+        Self::builder(base_dir).build()
+    }
+
+    pub fn builder(base_dir: impl AsRef<Path>) -> ClaudeSetupBuilder {
+        ClaudeSetupBuilder::new(base_dir)
+    }
+
+    /// A setup sharing this installation but using the account's own Claude home and credentials
+    pub fn for_account(&self, account: &AccountConfig) -> ClaudeSetup {
+        let config = self.get_config().map(|config| {
+            // Never fall back to the top-level credentials; that would defeat the isolation
+            Arc::new(Config { auth: account.auth.clone(), ..Config::clone(&config) })
+        });
+
+        ClaudeSetup {
+            base_dir: self.base_dir.clone(),
+            bun_path: self.bun_path.clone(),
+            claude_path: self.claude_path.clone(),
+            install_strategy: self.install_strategy.clone(),
+            claude_home: self.base_dir.join(".claude-accounts").join(&account.name),
+            config: RwLock::new(config),
+            in_memory_config: self.in_memory_config.clone(),
+            config_options: self.config_options.clone(),
+            quiet: self.quiet,
+            account: Some(account.name.clone()),
+            clay_binary: self.clay_binary.clone(),
+            credential_cache: CredentialCache::default(),
+        }
+    }
+
+    pub(crate) fn get_credential_cache(&self) -> &CredentialCache {
+        &self.credential_cache
+    }
+
+    /// Name of the account this setup belongs to, if it was made by `for_account`
+    pub fn get_account_name(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
 
     pub fn is_installed(&self) -> bool {
-        self.bun_path.exists() && self.claude_path.exists()
+        match self.install_strategy {
+            InstallStrategy::Portable => self.bun_path.exists() && self.claude_path.exists(),
+            _ => is_executable(&self.claude_path),
+        }
     }
 
     pub async fn install_bun(&self) -> Result<()> {
@@ -54,16 +234,24 @@ impl ClaudeSetup {
             return Ok(());
         }
 
-        info!("Installing portable Bun...");
-
-        let download_url = self.get_bun_download_url()?;
-        
-        // Download Bun
-        let response = reqwest::get(&download_url).await
-            .map_err(|e| ClaudeRelayError::Setup(format!("Failed to download Bun: {}", e)))?;
-        
-        let bytes = response.bytes().await
-            .map_err(|e| ClaudeRelayError::Setup(format!("Failed to read Bun download: {}", e)))?;
+        // An offline cache replaces the download entirely
+        let asset = current_bun_asset()?;
+        let bytes = match self.get_install_cache() {
+            Some(cache_dir) => {
+                let zip_path = cached_bun_zip(&cache_dir, asset)?;
+                info!("Installing portable Bun from {:?}...", zip_path);
+                self.read_cached(&zip_path)?
+            }
+            None => {
+                info!("Installing portable Bun...");
+                let file = format!("{}.zip", asset);
+                let downloader = self.downloader();
+                let sha256 = self.bun_checksum(&downloader, &file).await?;
+                // Private to this run, so no other user can plant or swap the zip
+                let download_dir = tempfile::tempdir()?;
+                downloader.download_verified(&self.bun_release_url(&file), download_dir.path(), &sha256).await?
+            }
+        };
 
         // Extract the zip
         let reader = std::io::Cursor::new(bytes);
@@ -101,39 +289,18 @@ impl ClaudeSetup {
     }
 
     pub fn install_claude(&self) -> Result<()> {
-        if self.claude_path.exists() {
+        if self.claude_path.exists() && !self.needs_reinstall() {
             info!("Claude already installed at {:?}", self.claude_path);
             return Ok(());
         }
 
         info!("Installing Claude Code CLI...");
-
-        let bun_exe = self.bun_path.join("bin").join("bun");
-        
-        let mut cmd = Command::new(&bun_exe);
-        cmd.args(&["install", "-g", "@anthropic-ai/claude-code"])
-            .env("BUN_INSTALL", &self.bun_path)
-            .env("PATH", format!("{}:{}", 
-                self.bun_path.join("bin").display(), 
-                env::var("PATH").unwrap_or_default()));
-
-        let output = cmd.output()
-            .map_err(|e| ClaudeRelayError::Setup(format!("Failed to install Claude: {}", e)))?;
-
-        if !output.status.success() {
-            return Err(ClaudeRelayError::Setup(format!(
-                "Failed to install Claude: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )));
-        }
-
-        info!("Claude installed successfully at {:?}", self.claude_path);
-        Ok(())
+        self.install_claude_package(&self.claude_package_spec()?, false)
     }
 
     pub fn setup_claude_home(&self) -> Result<()> {
-        // Create isolated Claude home directory
-        fs::create_dir_all(&self.claude_home)?;
+        // Create isolated Claude home directory, private to this user and kept out of git
+        self.secure_claude_home()?;
 
         // Create .config/claude directory for Claude's configuration
         let config_dir = self.claude_home.join(".config").join("claude");
@@ -142,12 +309,19 @@ impl ClaudeSetup {
         // Generate Claude's config.json from clay.yaml settings
         self.generate_claude_config()?;
 
+        // Use the shared login store when clay.yaml opts in
+        self.link_shared_credentials()?;
+
         info!("Claude home directory set up at {:?}", self.claude_home);
         Ok(())
     }
 
     /// Generate Claude CLI's config.json file from clay.yaml configuration
     pub fn generate_claude_config(&self) -> Result<()> {
+        self.write_claude_config(self.get_config().as_deref())
+    }
+
+    fn write_claude_config(&self, config: Option<&Config>) -> Result<()> {
         let config_dir = self.claude_home.join(".config").join("claude");
         fs::create_dir_all(&config_dir)?;
         
@@ -161,7 +335,7 @@ impl ClaudeSetup {
         });
         
         // Apply clay.yaml overrides if available
-        if let Some(config) = &self.config {
+        if let Some(config) = config {
             // If we have server config, we can add Claude-specific settings
             if let Some(server_config) = &config.server {
                 // Claude CLI doesn't directly use port config, but we could add other settings
@@ -179,37 +353,54 @@ impl ClaudeSetup {
     }
 
     pub fn check_authentication(&self) -> Result<bool> {
-        // Check if Claude is authenticated by looking for the .claude.json file
-        let claude_config_file = self.claude_home.join(".claude.json");
-        
-        if claude_config_file.exists() {
-            let data = fs::read_to_string(&claude_config_file)?;
-            if data.contains("oauthAccount") {
-                return Ok(true);
+        Ok(self.get_auth_status()?.0)
+    }
+
+    /// Which credentials the CLI will use, if any; this does not contact Anthropic
+    pub fn get_auth_status(&self) -> Result<(bool, String)> {
+        // Credentials named in clay.yaml take precedence over everything else
+        match self.resolve_configured_credential() {
+            Ok(Some(credential)) => {
+                let kind = if credential.env_var == API_KEY_ENV { "API key" } else { "OAuth token" };
+                return Ok((true, format!("{} from {}", kind, credential.source)));
             }
+            Ok(None) => {}
+            Err(e) => return Ok((false, e.to_string())),
         }
 
-        Ok(false)
-    }
+        if env::var(API_KEY_ENV).is_ok_and(|key| !key.trim().is_empty()) {
+            return Ok((true, format!("API key from ${}", API_KEY_ENV)));
+        }
 
-    pub fn get_auth_status(&self) -> Result<(bool, String)> {
-        let claude_config_file = self.claude_home.join(".claude.json");
-        
-        if !claude_config_file.exists() {
-            return Ok((false, "No authentication file found".to_string()));
+        if self.stored_api_key().is_some() {
+            return Ok((true, "Saved API key".to_string()));
         }
-        
-        let metadata = fs::metadata(&claude_config_file)?;
-        if metadata.len() == 0 {
-            return Ok((false, "Authentication file is empty".to_string()));
+
+        if env::var(OAUTH_TOKEN_ENV).is_ok_and(|token| !token.trim().is_empty()) {
+            return Ok((true, format!("OAuth token from ${}", OAUTH_TOKEN_ENV)));
         }
-        
-        let data = fs::read_to_string(&claude_config_file)?;
-        if data.len() < 10 || !data.contains("oauthAccount") {
-            return Ok((false, "Authentication file appears invalid".to_string()));
+
+        if self.stored_oauth_token().is_some() {
+            return Ok((true, "OAuth token from claude setup-token".to_string()));
+        }
+
+        // Credentials written by an interactive `claude /login`
+        let credentials_file = self.get_cli_credentials_path();
+        if read_json(&credentials_file)?.is_some_and(|creds| creds.get("claudeAiOauth").is_some()) {
+            return Ok((true, "Claude account login".to_string()));
+        }
+
+        let claude_config_file = self.claude_home.join(".claude.json");
+        match read_json(&claude_config_file)? {
+            Some(config) if config.get("oauthAccount").is_some() => {
+                Ok((true, "Claude account login".to_string()))
+            }
+            Some(_) => Ok((false, "Authentication file has no account".to_string())),
+            None if claude_config_file.exists() => {
+                Ok((false, "Authentication file appears invalid".to_string()))
+            }
+            None => Ok((false, "No authentication file found".to_string())),
         }
-        
-        Ok((true, "Authenticated".to_string()))
     }
 
     pub fn run_claude_login(&self) -> Result<()> {
@@ -257,6 +448,26 @@ impl ClaudeSetup {
             env::var("PATH").unwrap_or_default());
         env.push(("PATH".to_string(), path));
         
+        // Saved credentials win over whatever the parent process had
+        if let Some(token) = self.stored_oauth_token() {
+            env.retain(|(k, _)| k != OAUTH_TOKEN_ENV);
+            env.push((OAUTH_TOKEN_ENV.to_string(), token));
+        }
+        if let Some(key) = self.stored_api_key() {
+            env.retain(|(k, _)| k != API_KEY_ENV);
+            env.push((API_KEY_ENV.to_string(), key));
+        }
+        
+        // Re-read on every spawn so rotated secrets are picked up; command output is reused for a few minutes
+        match self.resolve_configured_credential() {
+            Ok(Some(credential)) => {
+                env.retain(|(k, _)| k != API_KEY_ENV && k != OAUTH_TOKEN_ENV);
+                env.push((credential.env_var.to_string(), credential.value));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("{}", e),
+        }
+        
         env
     }
 
@@ -264,6 +475,15 @@ impl ClaudeSetup {
         &self.claude_path
     }
 
+    /// Where Bun and a Clay-installed Claude CLI live
+    pub fn get_install_dir(&self) -> &Path {
+        &self.bun_path
+    }
+
+    pub fn get_install_strategy(&self) -> InstallStrategy {
+        self.install_strategy.clone()
+    }
+
     pub fn get_claude_home(&self) -> &Path {
         &self.claude_home
     }
@@ -272,96 +492,158 @@ impl ClaudeSetup {
         &self.base_dir
     }
 
+    /// Whether this setup was built with `quiet`, keeping its own output off the terminal
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
     pub async fn setup(&self) -> Result<()> {
         info!("Setting up isolated Claude environment...");
 
-        self.install_bun().await?;
-        self.install_claude()?;
+        self.install_cli().await?;
         self.setup_claude_home()?;
 
         info!("Claude setup completed successfully");
         Ok(())
     }
 
-    pub fn is_authentication_needed(&self, output: &str) -> bool {
-        output.contains("Invalid API key") 
-            || output.contains("not authenticated")
-            || output.contains("Please log in")
-            || output.contains("claude login")
+    /// Whether the CLI refused to work because the account hit a usage or rate limit
+    pub fn is_usage_limited(&self, output: &str) -> bool {
+        matches!(classify(output), CliFailure::UsageLimit { .. })
     }
 
-    pub fn copy_auth_from(&self, source_dir: &Path) -> Result<()> {
-        let source_auth_file = source_dir.join("auth.json");
-        
-        if !source_auth_file.exists() {
-            return Err(ClaudeRelayError::Authentication(
-                format!("No auth.json found in {:?}", source_dir)
-            ));
-        }
-        
-        let auth_data = fs::read(&source_auth_file)?;
-        
-        let config_dir = self.claude_home.join(".config").join("claude");
-        fs::create_dir_all(&config_dir)?;
-        
-        let dest_auth_file = config_dir.join("auth.json");
-        fs::write(&dest_auth_file, auth_data)?;
-        
-        info!("Authentication copied from {:?}", source_dir);
-        Ok(())
+    /// Reset time from CLI messages like `Claude AI usage limit reached|1760000000`
+    pub fn parse_usage_limit_reset(&self, output: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::classify::parse_reset(output)
+    }
+
+    pub fn is_authentication_needed(&self, output: &str) -> bool {
+        classify(output) == CliFailure::Authentication
     }
 
+    /// Save a credential for the CLI: API keys become `ANTHROPIC_API_KEY`,
+    /// anything else is treated as a `setup-token` OAuth token
     pub fn set_auth_token(&self, auth_token: &str) -> Result<()> {
+        let auth_token = auth_token.trim();
         if auth_token.is_empty() {
             return Err(ClaudeRelayError::Authentication("Auth token cannot be empty".into()));
         }
         
-        let config_dir = self.claude_home.join(".config").join("claude");
-        fs::create_dir_all(&config_dir)?;
-        
-        let auth_file = config_dir.join("auth.json");
-        let auth_data = format!(r#"{{"key":"{}"}}"#, auth_token);
-        
-        fs::write(&auth_file, auth_data)?;
+        if auth_token.starts_with(crate::auth::API_KEY_PREFIX) {
+            self.store_api_key(auth_token)?;
+        } else {
+            self.store_oauth_token(auth_token)?;
+        }
         
         info!("Authentication token saved successfully");
         Ok(())
     }
 
-    fn get_bun_download_url(&self) -> Result<String> {
-        let os = env::consts::OS;
-        let arch = env::consts::ARCH;
-        
-        let url = match (os, arch) {
-            ("macos", "aarch64") => "https://github.com/oven-sh/bun/releases/latest/download/bun-darwin-aarch64.zip",
-            ("macos", "x86_64") => "https://github.com/oven-sh/bun/releases/latest/download/bun-darwin-x64.zip",
-            ("linux", "aarch64") => "https://github.com/oven-sh/bun/releases/latest/download/bun-linux-aarch64.zip",
-            ("linux", "x86_64") => "https://github.com/oven-sh/bun/releases/latest/download/bun-linux-x64.zip",
-            _ => return Err(ClaudeRelayError::Setup(
-                format!("Unsupported platform: {}/{}", os, arch)
-            )),
-        };
-        
-        Ok(url.to_string())
-    }
-
-    /// Get the configuration loaded from clay.yaml or defaults
-    pub fn get_config(&self) -> &Option<Config> {
-        &self.config
+    /// The configuration in use; a reload swaps in a new one and leaves this snapshot as it was
+    pub fn get_config(&self) -> Option<Arc<Config>> {
+        self.config.read().unwrap().clone()
     }
 
     /// Get initial context from configuration
     pub fn get_initial_context(&self) -> Option<String> {
-        self.config.as_ref().and_then(|c| c.context.clone())
+        self.get_config().as_ref().and_then(|c| c.context.clone())
+    }
+
+    /// Get the Claude CLI process settings from configuration
+    pub fn get_process_config(&self) -> ProcessConfig {
+        self.get_config().as_ref()
+            .and_then(|c| c.process.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get how processes should carry conversation memory between turns
+    pub fn get_session_mode(&self) -> SessionMode {
+        self.get_process_config().session_mode
+    }
+
+    /// Get the maximum number of concurrent conversation processes
+    pub fn get_max_processes(&self) -> usize {
+        self.get_config().as_ref()
+            .map(|c| c.server.as_ref().map(|s| s.max_processes).unwrap_or(c.max_processes))
+            .unwrap_or_else(|| Config::default().max_processes)
+    }
+
+    /// Port the server listens on: `server.port`, after any `--port` override
+    pub fn get_server_port(&self) -> u16 {
+        self.get_config()
+            .and_then(|c| c.server.as_ref().map(|s| s.port))
+            .unwrap_or(3000)
+    }
+
+    /// Accounts listed in clay.yaml; `None` when there are none
+    pub fn get_accounts_config(&self) -> Option<AccountsConfig> {
+        self.get_config().as_ref()
+            .and_then(|c| c.accounts.clone())
+            .filter(|accounts| !accounts.list.is_empty())
+    }
+
+    /// Setup for one of the accounts listed in clay.yaml
+    pub fn get_account(&self, name: &str) -> Result<ClaudeSetup> {
+        self.get_accounts_config()
+            .and_then(|accounts| accounts.list.into_iter().find(|a| a.name == name))
+            .map(|account| self.for_account(&account))
+            .ok_or_else(|| ClaudeRelayError::Config(format!("No account named '{}' in clay.yaml", name)))
+    }
+
+    /// Problems in the `accounts:` section of clay.yaml
+    pub fn validate_accounts_config(&self) -> Vec<String> {
+        self.get_accounts_config().map(|accounts| accounts.validate()).unwrap_or_default()
+    }
+
+    pub fn get_auth_config(&self) -> Option<AuthConfig> {
+        self.get_config().as_ref().and_then(|c| c.auth.clone())
+    }
+
+    /// Problems in the `auth:` section of clay.yaml
+    pub fn validate_auth_config(&self) -> Vec<String> {
+        self.get_auth_config().map(|auth| auth.validate()).unwrap_or_default()
+    }
+
+    /// Problems in the `credentials:` section of clay.yaml
+    pub fn validate_credentials_config(&self) -> Vec<String> {
+        self.get_config().as_ref()
+            .and_then(|c| c.credentials.as_ref())
+            .map(|credentials| credentials.validate())
+            .unwrap_or_default()
+    }
+
+    /// Bearer token guarding the server's `/admin` endpoints, if configured
+    pub fn get_admin_token(&self) -> Option<String> {
+        self.get_config().as_ref()
+            .and_then(|c| c.server.as_ref())
+            .and_then(|s| s.admin_token.clone())
+    }
+
+    /// Background credential checks from `server.auth_monitor`, defaults when unset
+    pub fn get_auth_monitor_config(&self) -> AuthMonitorConfig {
+        self.get_config().as_ref()
+            .and_then(|c| c.server.as_ref())
+            .and_then(|s| s.auth_monitor.clone())
+            .unwrap_or_default()
+    }
+
+    /// Directory where the Claude CLI stores its per-project session transcripts
+    pub fn get_cli_projects_dir(&self) -> PathBuf {
+        self.claude_home.join(".claude").join("projects")
     }
 
     /// Setup MCP configuration file for Claude CLI and regenerate Claude's config
     pub fn setup_mcp_config(&self) -> Result<()> {
+        self.write_cli_configs(self.get_config().as_deref())
+    }
+
+    /// Write Claude's config.json and mcp.json for `config`
+    fn write_cli_configs(&self, config: Option<&Config>) -> Result<()> {
         // Always regenerate Claude's base configuration
-        self.generate_claude_config()?;
+        self.write_claude_config(config)?;
         
         // Generate MCP configuration if available
-        if let Some(config) = &self.config {
+        if let Some(config) = config {
             if let Some(mcp_config) = &config.mcp {
                 self.write_mcp_config(mcp_config)?;
                 info!("MCP configuration written successfully");
@@ -391,21 +673,19 @@ impl ClaudeSetup {
                         "env": server.env
                     });
                 }
-            } else if server.is_http() {
-                // For HTTP MCP servers, we create a proxy command that Clay can handle
-                warn!("HTTP MCP server '{}' will be proxied through Clay", name);
-                claude_mcp_config["mcpServers"][name] = json!({
-                    "command": "clay-mcp-proxy",
-                    "args": ["--type", "http", "--name", name],
-                    "env": {}
-                });
-            } else if server.is_websocket() {
-                // For WebSocket MCP servers, we create a proxy command that Clay can handle
-                warn!("WebSocket MCP server '{}' will be proxied through Clay", name);
+            } else if server.is_http() || server.is_websocket() {
+                // The CLI only speaks stdio, so it reaches remote servers through clay mcp-proxy
+                let Some(clay_binary) = &self.clay_binary else {
+                    return Err(ClaudeRelayError::Setup(format!(
+                        "MCP server '{}' needs the clay executable to run `clay mcp-proxy`; set it with ClaudeSetupBuilder::clay_binary",
+                        name
+                    )));
+                };
+                let (args, env) = McpProxy::for_server(name, server).command_args();
                 claude_mcp_config["mcpServers"][name] = json!({
-                    "command": "clay-mcp-proxy",
-                    "args": ["--type", "ws", "--name", name], 
-                    "env": {}
+                    "command": clay_binary,
+                    "args": args,
+                    "env": env
                 });
             }
         }
@@ -421,52 +701,99 @@ impl ClaudeSetup {
         Ok(())
     }
 
+    /// The merged configuration layers, re-read from disk
+    pub fn check_config_layers(&self) -> Result<ConfigReport> {
+        if let Some(config) = &self.in_memory_config {
+            return Ok(ConfigReport { config: config.clone(), warnings: Vec::new(), files: Vec::new() });
+        }
+        Config::check_layered(&self.base_dir, &self.config_options)
+    }
+
+    /// clay.yaml and clay.local.yaml, whether or not they exist; none for an in-memory config
+    pub fn get_config_files(&self) -> Vec<PathBuf> {
+        if self.in_memory_config.is_some() {
+            return Vec::new();
+        }
+        vec![self.config_options.config_file(&self.base_dir), self.config_options.local_file(&self.base_dir)]
+    }
+
+    /// Errors that stopped clay.yaml from loading, such as a missing required `${VAR}`
+    pub fn validate_config_file(&self) -> Vec<String> {
+        match self.check_config_layers() {
+            Ok(_) => Vec::new(),
+            Err(e) => vec![e.to_string()],
+        }
+    }
+
+    /// Keys in clay.yaml that Clay doesn't recognise, with suggestions
+    pub fn config_warnings(&self) -> Vec<String> {
+        self.check_config_layers().map(|report| report.warnings).unwrap_or_default()
+    }
+
+    /// Every problem that makes clay.yaml unusable, as it is on disk now
+    pub fn validate_config(&self) -> Result<Vec<String>> {
+        Ok(match self.check_config_layers() {
+            Ok(report) => report.config.validate(),
+            Err(e) => vec![e.to_string()],
+        })
+    }
+
+    /// Re-read clay.yaml and its layers and use them from the next request on.
+    /// An invalid config is rejected and the running one kept. Returns the new
+    /// config and its warnings.
+    pub fn reload_config(&self) -> Result<ConfigReport> {
+        let report = self.check_config_layers()?;
+        let issues = report.config.validate();
+        if !issues.is_empty() {
+            return Err(ClaudeRelayError::Config(issues.join("; ")));
+        }
+        self.apply_config(report.config.clone())?;
+        Ok(report)
+    }
+
+    /// Regenerate Claude's config files for `config`, then swap it in so requests never
+    /// see the new configuration with the old files; an account setup keeps its own credentials
+    pub fn apply_config(&self, mut config: Config) -> Result<()> {
+        if let Some(name) = &self.account {
+            // Never fall back to the top-level credentials; that would defeat the isolation
+            let listed = config.accounts.as_ref()
+                .and_then(|accounts| accounts.list.iter().find(|a| &a.name == name));
+            config.auth = match listed {
+                Some(account) => account.auth.clone(),
+                None => self.get_config().and_then(|c| c.auth.clone()),
+            };
+        }
+        self.write_cli_configs(Some(&config))?;
+        *self.config.write().unwrap() = Some(Arc::new(config));
+        self.forget_credentials();
+        Ok(())
+    }
+
+    /// Fail when clay.yaml is invalid, instead of running without it
+    pub fn check_config(&self) -> Result<()> {
+        let issues = self.validate_config()?;
+        if issues.is_empty() {
+            return Ok(());
+        }
+        Err(ClaudeRelayError::Config(format!(
+            "clay.yaml has {} problem(s): {}. Run with --validate-config for details",
+            issues.len(),
+            issues.join("; ")
+        )))
+    }
+
     /// Validate MCP server configurations
     pub fn validate_mcp_servers(&self) -> Result<Vec<String>> {
-        let mut issues = Vec::new();
-        
-        if let Some(config) = &self.config {
-            if let Some(mcp_config) = &config.mcp {
-                for (name, server) in &mcp_config.servers {
-                    if server.is_command() {
-                        if let Some(command) = &server.command {
-                            if command.is_empty() {
-                                issues.push(format!("MCP server '{}': command cannot be empty", name));
-                            }
-                        } else {
-                            issues.push(format!("MCP server '{}': command is required for command transport", name));
-                        }
-                    } else if server.is_http() {
-                        if let Some(url) = &server.url {
-                            if !url.starts_with("http://") && !url.starts_with("https://") {
-                                issues.push(format!("MCP server '{}': invalid HTTP URL '{}'", name, url));
-                            }
-                        } else {
-                            issues.push(format!("MCP server '{}': url is required for HTTP transport", name));
-                        }
-                    } else if server.is_websocket() {
-                        if let Some(url) = &server.url {
-                            if !url.starts_with("ws://") && !url.starts_with("wss://") {
-                                issues.push(format!("MCP server '{}': invalid WebSocket URL '{}'", name, url));
-                            }
-                        } else {
-                            issues.push(format!("MCP server '{}': url is required for WebSocket transport", name));
-                        }
-                    } else {
-                        issues.push(format!("MCP server '{}': unable to determine transport type", name));
-                    }
-                }
-            }
-        }
-        
-        Ok(issues)
+        Ok(self.get_config()
+            .and_then(|c| c.mcp.as_ref().map(|mcp| mcp.validate()))
+            .unwrap_or_default())
     }
 
     /// Force regenerate clay.yaml file in the base directory
     pub fn init_config(&self) -> Result<()> {
-        let clay_yaml_path = self.base_dir.join("clay.yaml");
+        let clay_yaml_path = self.config_options.config_file(&self.base_dir);
         
-        if clay_yaml_path.exists() {
+        if clay_yaml_path.exists() && !self.quiet {
             println!("⚠️  clay.yaml already exists. Overwriting with new template...");
         }
         
@@ -474,8 +801,10 @@ impl ClaudeSetup {
         fs::write(&clay_yaml_path, sample_config)?;
         
         info!("Clay.yaml configuration created at {:?}", clay_yaml_path);
-        println!("📝 Generated clay.yaml configuration file");
-        println!("   Edit this file to customize your MCP servers and context settings");
+        if !self.quiet {
+            println!("📝 Generated clay.yaml configuration file");
+            println!("   Edit this file to customize your MCP servers and context settings");
+        }
         
         Ok(())
     }
@@ -484,8 +813,7 @@ impl ClaudeSetup {
     pub async fn setup_with_mcp(&self) -> Result<()> {
         info!("Setting up isolated Claude environment with MCP support...");
 
-        self.install_bun().await?;
-        self.install_claude()?;
+        self.install_cli().await?;
         self.setup_claude_home()?;
         self.setup_mcp_config()?;
 
@@ -501,4 +829,12 @@ impl ClaudeSetup {
         info!("Claude setup with MCP completed successfully");
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Parse a JSON file, treating a missing or malformed file as absent
+fn read_json(path: &Path) -> Result<Option<serde_json::Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?).ok())
+}
@@ -0,0 +1,279 @@
+use crate::error::{ClaudeRelayError, Result};
+use crate::classify::{classify, CliFailure};
+use crate::setup::ClaudeSetup;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How much of the child's stderr to keep for diagnostics
+const STDERR_TAIL_BYTES: usize = 8 * 1024;
+
+/// Final `result` event printed by the Claude CLI in `json` and `stream-json` output modes
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct CliResult {
+    #[serde(default)]
+    pub result: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+/// Snapshot of a streaming child's liveness
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamHealth {
+    pub alive: bool,
+    pub pid: u32,
+    pub session_id: Option<String>,
+    pub turns: u64,
+    pub uptime_secs: u64,
+    pub exit_status: Option<String>,
+    pub stderr_tail: String,
+}
+
+/// A Claude CLI child kept alive in bidirectional `stream-json` mode.
+///
+/// User turns are written to its stdin as JSON lines and events are read from
+/// its stdout until the CLI reports the turn's `result`.
+pub struct StreamingSession {
+    stdin: ChildStdin,
+    // Mutex only so sessions can live in shared server state; turns already take `&mut self`
+    events: Mutex<Receiver<String>>,
+    monitor: Arc<StreamMonitor>,
+}
+
+/// The child and counters of a streaming session, readable through their own locks
+/// while a turn holds the session
+pub struct StreamMonitor {
+    child: Mutex<Child>,
+    pid: u32,
+    stderr_tail: Mutex<String>,
+    session_id: Mutex<Option<String>>,
+    started_at: Instant,
+    turns: AtomicU64,
+    stdout_closed: AtomicBool,
+}
+
+impl StreamMonitor {
+    /// Liveness probe for monitoring
+    pub fn health(&self) -> StreamHealth {
+        let exit_status = match self.child.lock().unwrap().try_wait() {
+            Ok(Some(status)) => Some(status.to_string()),
+            Ok(None) => None,
+            Err(e) => Some(format!("unknown ({})", e)),
+        };
+
+        StreamHealth {
+            alive: !self.stdout_closed.load(Ordering::SeqCst) && exit_status.is_none(),
+            pid: self.pid,
+            session_id: self.session_id.lock().unwrap().clone(),
+            turns: self.turns.load(Ordering::SeqCst),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            exit_status,
+            stderr_tail: self.stderr_tail.lock().map(|t| t.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn exit_status(&self) -> Option<String> {
+        self.child.lock().unwrap().try_wait().ok().flatten().map(|s| s.to_string())
+    }
+}
+
+impl StreamingSession {
+    /// Spawn a streaming child, resuming an existing CLI session if given
+    pub fn spawn(setup: &ClaudeSetup, resume: Option<&str>) -> Result<Self> {
+        let mut cmd = Command::new(setup.get_claude_path());
+        cmd.args([
+            "--print",
+            "--input-format", "stream-json",
+            "--output-format", "stream-json",
+            "--verbose",
+            "--dangerously-skip-permissions",
+        ]);
+        if let Some(session_id) = resume {
+            cmd.args(["--resume", session_id]);
+        }
+        cmd.current_dir(setup.get_base_dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        for (key, value) in setup.get_claude_env() {
+            cmd.env(key, value);
+        }
+        cmd.env("CLAUDE_RELAY", "true")
+            .env("TERM", "dumb")
+            .env("NO_COLOR", "1");
+
+        let mut child = cmd.spawn()
+            .map_err(|e| ClaudeRelayError::Process(format!("Failed to spawn streaming Claude: {}", e)))?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| ClaudeRelayError::Process("Streaming Claude has no stdin".into()))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| ClaudeRelayError::Process("Streaming Claude has no stdout".into()))?;
+        let stderr = child.stderr.take()
+            .ok_or_else(|| ClaudeRelayError::Process("Streaming Claude has no stderr".into()))?;
+
+        // Read stdout on its own thread so turns can time out and EOF shows up as a disconnect
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let monitor = Arc::new(StreamMonitor {
+            pid: child.id(),
+            child: Mutex::new(child),
+            stderr_tail: Mutex::new(String::new()),
+            session_id: Mutex::new(resume.map(|s| s.to_string())),
+            started_at: Instant::now(),
+            turns: AtomicU64::new(0),
+            stdout_closed: AtomicBool::new(false),
+        });
+
+        // Drain stderr continuously so the child never blocks on a full pipe
+        let tail = monitor.clone();
+        thread::spawn(move || {
+            let mut stderr = stderr;
+            let mut buffer = [0u8; 1024];
+            while let Ok(n) = stderr.read(&mut buffer) {
+                if n == 0 {
+                    break;
+                }
+                let mut tail = tail.stderr_tail.lock().unwrap();
+                tail.push_str(&String::from_utf8_lossy(&buffer[..n]));
+                if tail.len() > STDERR_TAIL_BYTES {
+                    let mut cut = tail.len() - STDERR_TAIL_BYTES;
+                    while !tail.is_char_boundary(cut) {
+                        cut += 1;
+                    }
+                    tail.drain(..cut);
+                }
+            }
+        });
+
+        info!("Spawned streaming Claude process (pid {})", monitor.pid);
+
+        Ok(StreamingSession {
+            stdin,
+            events: Mutex::new(events),
+            monitor,
+        })
+    }
+
+    /// Whether the child is still running
+    pub fn is_alive(&mut self) -> bool {
+        !self.monitor.stdout_closed.load(Ordering::SeqCst) && self.monitor.exit_status().is_none()
+    }
+
+    /// Health of this session, shareable without borrowing it
+    pub fn monitor(&self) -> Arc<StreamMonitor> {
+        self.monitor.clone()
+    }
+
+    /// Liveness probe for monitoring
+    pub fn health(&mut self) -> StreamHealth {
+        self.monitor.health()
+    }
+
+    pub fn get_session_id(&self) -> Option<String> {
+        self.monitor.session_id.lock().unwrap().clone()
+    }
+
+    pub fn get_turns(&self) -> u64 {
+        self.monitor.turns.load(Ordering::SeqCst)
+    }
+
+    pub fn get_age(&self) -> Duration {
+        self.monitor.started_at.elapsed()
+    }
+
+    pub fn stderr_tail(&self) -> String {
+        self.monitor.stderr_tail.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    /// Send one user turn and block until the CLI reports its result
+    pub(crate) fn send_turn(&mut self, message: &str, timeout: Duration) -> Result<CliResult> {
+        let line = serde_json::json!({
+            "type": "user",
+            "message": {
+                "role": "user",
+                "content": [{ "type": "text", "text": message }]
+            }
+        });
+
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| self.crashed(format!("Failed to write to streaming Claude: {}", e)))?;
+        self.monitor.turns.fetch_add(1, Ordering::SeqCst);
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let received = self.events.get_mut()
+                .map_err(|_| ClaudeRelayError::Process("Streaming Claude reader poisoned".into()))?
+                .recv_timeout(remaining);
+            let line = match received {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(ClaudeRelayError::Process(format!(
+                        "Streaming Claude did not answer within {:?}", timeout
+                    )));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.monitor.stdout_closed.store(true, Ordering::SeqCst);
+                    return Err(self.crashed("Streaming Claude exited mid-turn".to_string()));
+                }
+            };
+
+            let event: serde_json::Value = match serde_json::from_str(&line) {
+                Ok(event) => event,
+                Err(_) => {
+                    debug!("Ignoring non-JSON line from streaming Claude: {}", line);
+                    continue;
+                }
+            };
+
+            if let Some(session_id) = event.get("session_id").and_then(|v| v.as_str()) {
+                let mut current = self.monitor.session_id.lock().unwrap();
+                if current.as_deref() != Some(session_id) {
+                    *current = Some(session_id.to_string());
+                }
+            }
+
+            if event.get("type").and_then(|v| v.as_str()) == Some("result") {
+                return Ok(serde_json::from_value(event)?);
+            }
+        }
+    }
+
+    fn crashed(&mut self, context: String) -> ClaudeRelayError {
+        let status = self.monitor.exit_status()
+            .unwrap_or_else(|| "still running".to_string());
+        warn!("{} ({})", context, status);
+        let stderr = self.stderr_tail();
+        match classify(&stderr) {
+            CliFailure::Other => ClaudeRelayError::Process(format!("{} ({}): {}", context, status, stderr.trim())),
+            _ => crate::classify::cli_error(&stderr),
+        }
+    }
+}
+
+impl Drop for StreamingSession {
+    fn drop(&mut self) {
+        let mut child = self.monitor.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
@@ -0,0 +1,184 @@
+#![cfg(unix)]
+
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{header, Request, StatusCode};
+use clay::server::{router, AppState};
+use clay::ClaudeSetup;
+use common::install_fake_claude;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Stand-in for the Claude CLI where the "limited" account is always over its usage limit
+const FAKE_LIMITED_CLAUDE: &str = r#"#!/bin/sh
+cat > /dev/null
+if [ "$CLAUDE_CODE_OAUTH_TOKEN" = "limited" ]; then
+  printf '{"type":"result","result":"Claude AI usage limit reached|%s","is_error":true}\n' "$(( $(date +%s) + 3600 ))"
+  exit 0
+fi
+printf '{"type":"result","result":"served by %s","session_id":"s-%s","is_error":false}\n' "$CLAUDE_CODE_OAUTH_TOKEN" "$$"
+"#;
+
+fn accounts_yaml(dir: &Path, tokens: &[&str]) {
+    let mut yaml = String::from("accounts:\n  strategy: round_robin\n  list:\n");
+    for (i, token) in tokens.iter().enumerate() {
+        fs::write(dir.join(format!("token-{}", i)), token).unwrap();
+        yaml.push_str(&format!(
+            "    - name: team-{}\n      auth:\n        oauth_token:\n          file: token-{}\n",
+            i, i
+        ));
+    }
+    fs::write(dir.join("clay.yaml"), yaml).unwrap();
+}
+
+fn chat(session: &str) -> Request<Body> {
+    Request::post("/v1/chat/completions")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("x-session-id", session)
+        .body(Body::from(r#"{"model":"claude","messages":[{"role":"user","content":"Hi"}]}"#))
+        .unwrap()
+}
+
+async fn json_body(response: axum::response::Response) -> serde_json::Value {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_usage_limit_fails_over_to_next_account() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    accounts_yaml(temp_dir.path(), &["limited", "spare"]);
+    install_fake_claude(temp_dir.path(), FAKE_LIMITED_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+
+    let response = app.clone().oneshot(chat("a")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["choices"][0]["message"]["content"], "served by spare");
+
+    // The limited account rests until the reset time the CLI reported
+    let local = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000)));
+    let request = Request::get("/admin/accounts").extension(local).body(Body::empty()).unwrap();
+    let accounts = json_body(app.clone().oneshot(request).await.unwrap()).await;
+    assert_eq!(accounts[0]["name"], "team-0");
+    assert_eq!(accounts[0]["available"], false);
+    assert!(accounts[0]["cooldown_until"].is_string());
+    assert_eq!(accounts[1]["available"], true);
+    assert!(temp_dir.path().join(".claude-accounts/team-1").is_dir());
+
+    // New sessions skip the cooling account
+    let response = app.oneshot(chat("b")).await.unwrap();
+    assert_eq!(json_body(response).await["choices"][0]["message"]["content"], "served by spare");
+}
+
+#[tokio::test]
+async fn test_all_accounts_limited_returns_retry_after() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    accounts_yaml(temp_dir.path(), &["limited", "limited"]);
+    install_fake_claude(temp_dir.path(), FAKE_LIMITED_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+
+    let response = app.oneshot(chat("a")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: i64 = response.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!(retry_after > 3000 && retry_after <= 3600, "{}", retry_after);
+}
+
+#[tokio::test]
+async fn test_health_reports_cached_auth_and_hides_details_from_other_hosts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("clay.yaml"), "auth:\n  oauth_token:\n    command: echo run >> checks; echo token\n").unwrap();
+    install_fake_claude(dir, FAKE_LIMITED_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(dir.to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+    let checks = || fs::read_to_string(dir.join("checks")).unwrap_or_default().lines().count();
+    let health = |peer: [u8; 4]| {
+        let peer = ConnectInfo(SocketAddr::from((peer, 40000)));
+        Request::get("/health").extension(peer).body(Body::empty()).unwrap()
+    };
+
+    let before = checks();
+    assert!(before > 0);
+    let remote = json_body(app.clone().oneshot(health([172, 17, 0, 1])).await.unwrap()).await;
+    let local = json_body(app.oneshot(health([127, 0, 0, 1])).await.unwrap()).await;
+    // Neither request runs the credential command again
+    assert_eq!(checks(), before);
+
+    assert_eq!(remote["accounts"][0]["authenticated"], true);
+    assert!(remote["accounts"][0].get("claude_home").is_none() && remote["accounts"][0].get("auth_detail").is_none());
+    assert!(remote["claude_cli"].get("path").is_none());
+    assert!(local["accounts"][0]["auth_detail"].as_str().unwrap().starts_with("OAuth token"), "{}", local);
+    assert!(local["accounts"][0]["claude_home"].is_string());
+}
+
+#[tokio::test]
+async fn test_slow_session_does_not_block_others() {
+    const FAKE_SLOW_CLAUDE: &str = r#"#!/bin/sh
+prompt=$(cat)
+case "$prompt" in *slow*) sleep 3;; esac
+printf '{"type":"result","result":"done","session_id":"s-%s","is_error":false}\n' "$$"
+"#;
+    let temp_dir = tempfile::tempdir().unwrap();
+    accounts_yaml(temp_dir.path(), &["token"]);
+    install_fake_claude(temp_dir.path(), FAKE_SLOW_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+
+    let slow = Request::post("/v1/chat/completions")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("x-session-id", "slow")
+        .body(Body::from(r#"{"model":"claude","messages":[{"role":"user","content":"take it slow"}]}"#))
+        .unwrap();
+    let slow = tokio::spawn(app.clone().oneshot(slow));
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+    // Another session and /health answer while the slow turn is still running
+    let quick = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+        let response = app.clone().oneshot(chat("quick")).await.unwrap();
+        let health = json_body(app.clone().oneshot(Request::get("/health").body(Body::empty()).unwrap()).await.unwrap()).await;
+        (response.status(), health["sessions"].clone())
+    });
+    assert_eq!(quick.await.expect("blocked behind the slow session"), (StatusCode::OK, serde_json::json!(2)));
+    assert!(!slow.is_finished());
+    assert_eq!(slow.await.unwrap().unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_usage_limit_moves_a_live_session_to_the_next_account() {
+    const FAKE_TIRING_CLAUDE: &str = r#"#!/bin/sh
+prompt=$(cat)
+case "$CLAUDE_CODE_OAUTH_TOKEN:$prompt" in first:*again*)
+  printf '{"type":"result","result":"Claude AI usage limit reached|%s","is_error":true}\n' "$(( $(date +%s) + 3600 ))"
+  exit 0;;
+esac
+printf '{"type":"result","result":"served by %s","session_id":"s-%s","is_error":false}\n' "$CLAUDE_CODE_OAUTH_TOKEN" "$$"
+"#;
+    let temp_dir = tempfile::tempdir().unwrap();
+    accounts_yaml(temp_dir.path(), &["first", "second"]);
+    install_fake_claude(temp_dir.path(), FAKE_TIRING_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+    let turn = |content: &str| {
+        Request::post("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-session-id", "a")
+            .body(Body::from(format!(r#"{{"model":"claude","messages":[{{"role":"user","content":"{}"}}]}}"#, content)))
+            .unwrap()
+    };
+
+    let first = json_body(app.clone().oneshot(turn("Hi")).await.unwrap()).await;
+    assert_eq!(first["choices"][0]["message"]["content"], "served by first");
+
+    // The limited account hands the session over within the same request
+    let second = json_body(app.clone().oneshot(turn("Hi again")).await.unwrap()).await;
+    assert_eq!(second["choices"][0]["message"]["content"], "served by second");
+    let health = json_body(app.oneshot(Request::get("/health").body(Body::empty()).unwrap()).await.unwrap()).await;
+    assert_eq!(health["sessions"], 1);
+}
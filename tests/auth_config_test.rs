@@ -0,0 +1,71 @@
+use clay::ClaudeSetup;
+use std::fs;
+use std::path::Path;
+
+fn setup_with_auth(dir: &Path, auth_yaml: &str) -> ClaudeSetup {
+    fs::write(dir.join("clay.yaml"), format!("auth:\n{}", auth_yaml)).unwrap();
+    ClaudeSetup::new(dir.to_str().unwrap()).unwrap()
+}
+
+fn env_value(setup: &ClaudeSetup, name: &str) -> Option<String> {
+    setup.get_claude_env().into_iter().find(|(k, _)| k == name).map(|(_, v)| v)
+}
+
+#[test]
+fn test_oauth_token_from_file_is_reread_on_each_spawn() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("token"), "sk-ant-oat01-first\n").unwrap();
+    let setup = setup_with_auth(temp_dir.path(), "  oauth_token:\n    file: token\n");
+
+    let (authenticated, detail) = setup.get_auth_status().unwrap();
+    assert!(authenticated);
+    assert_eq!(detail, "OAuth token from file token");
+    assert_eq!(env_value(&setup, "CLAUDE_CODE_OAUTH_TOKEN").as_deref(), Some("sk-ant-oat01-first"));
+
+    // A rotated secret is picked up without restarting
+    fs::write(temp_dir.path().join("token"), "sk-ant-oat01-second").unwrap();
+    assert_eq!(env_value(&setup, "CLAUDE_CODE_OAUTH_TOKEN").as_deref(), Some("sk-ant-oat01-second"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_api_key_from_command() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = setup_with_auth(temp_dir.path(), "  api_key:\n    command: \"echo sk-ant-api03-from-command\"\n");
+
+    assert!(setup.check_authentication().unwrap());
+    assert_eq!(env_value(&setup, "ANTHROPIC_API_KEY").as_deref(), Some("sk-ant-api03-from-command"));
+    assert_eq!(env_value(&setup, "CLAUDE_CODE_OAUTH_TOKEN"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_credential_command_output_is_reused_until_forgotten() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = setup_with_auth(temp_dir.path(), "  api_key:\n    command: \"echo run >> runs; echo sk-ant-api03-cached\"\n");
+    let runs = || fs::read_to_string(temp_dir.path().join("runs")).unwrap().lines().count();
+
+    for _ in 0..3 {
+        assert_eq!(env_value(&setup, "ANTHROPIC_API_KEY").as_deref(), Some("sk-ant-api03-cached"));
+    }
+    assert!(setup.check_authentication().unwrap());
+    assert_eq!(runs(), 1);
+
+    setup.forget_credentials();
+    env_value(&setup, "ANTHROPIC_API_KEY");
+    assert_eq!(runs(), 2);
+}
+
+#[test]
+fn test_missing_env_source_is_reported() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = setup_with_auth(temp_dir.path(), "  api_key:\n    env: CLAY_TEST_UNSET_API_KEY\n");
+
+    let (authenticated, detail) = setup.get_auth_status().unwrap();
+    assert!(!authenticated);
+    assert!(detail.contains("CLAY_TEST_UNSET_API_KEY"), "{}", detail);
+    assert!(setup.validate_auth_config().is_empty());
+
+    let setup = setup_with_auth(temp_dir.path(), "  api_key:\n    env: A\n    file: b\n");
+    assert_eq!(setup.validate_auth_config().len(), 1);
+}
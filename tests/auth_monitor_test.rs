@@ -0,0 +1,65 @@
+#![cfg(unix)]
+
+mod common;
+
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use clay::server::AppState;
+use clay::{AuthState, ClaudeSetup};
+use common::{clear_ambient_credentials, install_fake_claude, FAKE_LOGIN_CLAUDE};
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+type Alerts = Arc<Mutex<Vec<serde_json::Value>>>;
+
+/// Local webhook receiver that records every alert it is sent
+async fn start_webhook() -> (String, Alerts) {
+    let alerts = Alerts::default();
+    let app = Router::new()
+        .route("/hook", post(|State(alerts): State<Alerts>, Json(body): Json<serde_json::Value>| async move {
+            alerts.lock().unwrap().push(body);
+        }))
+        .with_state(alerts.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, alerts)
+}
+
+#[tokio::test]
+async fn test_monitor_alerts_on_expiring_and_rejected_credentials() {
+    clear_ambient_credentials();
+    let (webhook, alerts) = start_webhook().await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    // A fresh setup-token lasts a year, so a 400 day window flags it as expiring
+    fs::write(
+        temp_dir.path().join("clay.yaml"),
+        format!("server:\n  auth_monitor:\n    probe: true\n    expiry_warning_days: 400\n    webhook: {}\n", webhook),
+    ).unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_LOGIN_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    setup.set_auth_token("sk-ant-REDACTED").unwrap();
+
+    let state = AppState::new(setup.clone());
+    let monitor = state.get_auth_monitor();
+    monitor.check_now().await;
+    let health = monitor.health();
+    assert_eq!(health[0].state, AuthState::Expiring);
+    assert!(health[0].expires_at.is_some());
+    assert_eq!(alerts.lock().unwrap().len(), 1);
+    assert_eq!(alerts.lock().unwrap()[0]["state"], "expiring");
+
+    // The CLI starts rejecting the token: the account is taken out of rotation
+    setup.set_auth_token("sk-ant-REDACTED").unwrap();
+    monitor.check_now().await;
+    assert_eq!(monitor.health()[0].state, AuthState::Failing);
+    assert!(!state.is_authenticated());
+    assert_eq!(alerts.lock().unwrap()[1]["state"], "failing");
+
+    // Alerts fire on transitions, not on every check
+    monitor.check_now().await;
+    assert_eq!(alerts.lock().unwrap().len(), 2);
+    let metrics = monitor.metrics();
+    assert_eq!((metrics.checks, metrics.alerts, metrics.failures), (3, 2, 2));
+}
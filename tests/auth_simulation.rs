@@ -1,15 +1,26 @@
-use claude_relay::ClaudeSetup;
+use clay::ClaudeSetup;
 use std::process::{Command, Stdio};
-use std::io::{Write, BufRead, BufReader};
 use std::thread;
 use std::time::Duration;
-use portable_pty::{native_pty_system, PtySize, CommandBuilder};
+use portable_pty::{native_pty_system, PtySize};
 
-#[test]
-fn test_auth_simulation() {
+/// These tests download Bun and the Claude CLI, so they only run with CLAY_NETWORK_TESTS set
+fn network_available() -> bool {
+    let enabled = std::env::var_os("CLAY_NETWORK_TESTS").is_some();
+    if !enabled {
+        eprintln!("Skipping: set CLAY_NETWORK_TESTS=1 to run tests that download the Claude CLI");
+    }
+    enabled
+}
+
+#[tokio::test]
+async fn test_auth_simulation() {
+    if !network_available() {
+        return;
+    }
     // Skip this test if we can't create a pty
     let pty_system = native_pty_system();
-    let pty_pair = pty_system
+    let _pty_pair = pty_system
         .openpty(PtySize {
             rows: 24,
             cols: 80,
@@ -25,7 +36,7 @@ fn test_auth_simulation() {
     
     // First, set up Claude
     let setup = ClaudeSetup::new(test_dir.to_str().unwrap()).unwrap();
-    setup.setup().unwrap();
+    setup.setup().await.unwrap();
     
     // Verify Claude is installed but not authenticated
     assert!(setup.is_installed());
@@ -36,7 +47,7 @@ fn test_auth_simulation() {
     
     // Test that the CLI correctly detects missing authentication
     let output = Command::new("cargo")
-        .args(&["run", "--", "--dir", test_dir.to_str().unwrap()])
+        .args(["run", "--", "--dir", test_dir.to_str().unwrap()])
         .output()
         .expect("Failed to run claude-relay");
     
@@ -47,8 +58,11 @@ fn test_auth_simulation() {
     println!("✅ Status command shows correct authentication state");
 }
 
-#[test]
-fn test_message_auth_prompt() {
+#[tokio::test]
+async fn test_message_auth_prompt() {
+    if !network_available() {
+        return;
+    }
     // Create a test directory
     let temp_dir = tempfile::tempdir().unwrap();
     let test_dir = temp_dir.path().join("message_test");
@@ -56,11 +70,11 @@ fn test_message_auth_prompt() {
     
     // First, set up Claude
     let setup = ClaudeSetup::new(test_dir.to_str().unwrap()).unwrap();
-    setup.setup().unwrap();
+    setup.setup().await.unwrap();
     
     // Test that sending a message without auth triggers auth prompt
     let mut child = Command::new("cargo")
-        .args(&["run", "--", "--dir", test_dir.to_str().unwrap(), "--message", "Hello"])
+        .args(["run", "--", "--dir", test_dir.to_str().unwrap(), "--message", "Hello"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -89,24 +103,27 @@ fn test_message_auth_prompt() {
     println!("✅ Message command correctly prompts for authentication");
 }
 
-#[test] 
-fn test_auth_file_creation() {
+#[tokio::test]
+async fn test_auth_file_creation() {
+    if !network_available() {
+        return;
+    }
     let temp_dir = tempfile::tempdir().unwrap();
     let test_dir = temp_dir.path().join("auth_file_test");
     std::fs::create_dir_all(&test_dir).unwrap();
     
     let setup = ClaudeSetup::new(test_dir.to_str().unwrap()).unwrap();
-    setup.setup().unwrap();
+    setup.setup().await.unwrap();
     
     // Test setting auth token programmatically
     setup.set_auth_token("test_token_123").unwrap();
     
-    // Verify auth file was created
-    let auth_file = test_dir.join(".claude-home/.config/claude/auth.json");
-    assert!(auth_file.exists(), "Auth file should be created");
+    // Verify the token was saved where the CLI receives it from
+    let token_file = test_dir.join(".claude-home/.config/clay/oauth_token");
+    assert!(token_file.exists(), "Token file should be created");
     
-    let auth_content = std::fs::read_to_string(&auth_file).unwrap();
-    assert!(auth_content.contains("test_token_123"));
+    let env = setup.get_claude_env();
+    assert!(env.contains(&("CLAUDE_CODE_OAUTH_TOKEN".to_string(), "test_token_123".to_string())));
     
     println!("✅ Auth token setting works correctly");
     
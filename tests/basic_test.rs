@@ -1,4 +1,4 @@
-use claude_relay::{ClaudeSetup, Config};
+use clay::{ClaudeSetup, Config};
 
 #[test]
 fn test_config_default() {
@@ -0,0 +1,101 @@
+#[cfg(unix)]
+mod common;
+
+use clay::classify::{classify, cli_error, CliFailure};
+use clay::ClaudeRelayError;
+
+#[test]
+fn test_classifies_cli_failures() {
+    let cases = [
+        ("Invalid API key · Please run /login", CliFailure::Authentication),
+        (
+            r#"API Error: 529 {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            CliFailure::Overloaded,
+        ),
+        (
+            r#"{"type":"result","subtype":"success","is_error":true,"result":"Prompt is too long"}"#,
+            CliFailure::ContextTooLong,
+        ),
+        (
+            r#"API Error: 404 {"type":"error","error":{"type":"not_found_error","message":"model: claude-nope"}}"#,
+            CliFailure::InvalidModel,
+        ),
+        ("MCP server \"github\" failed to start: spawn npx ENOENT", CliFailure::McpFailure),
+        ("segmentation fault", CliFailure::Other),
+    ];
+    for (output, expected) in cases {
+        assert_eq!(classify(output), expected, "{}", output);
+    }
+}
+
+#[test]
+fn test_usage_limit_keeps_reset_time_and_message() {
+    let output = r#"{"type":"result","is_error":true,"result":"Claude AI usage limit reached|1760000000"}"#;
+    assert_eq!(
+        classify(output),
+        CliFailure::UsageLimit { reset_at: chrono::DateTime::from_timestamp(1760000000, 0) }
+    );
+
+    match cli_error(output) {
+        ClaudeRelayError::UsageLimit { message, reset_at } => {
+            assert_eq!(message, "Claude AI usage limit reached|1760000000");
+            assert_eq!(reset_at.unwrap().timestamp(), 1760000000);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(cli_error("Overloaded").retry_after(), Some(clay::error::OVERLOADED_RETRY_SECS));
+    assert_eq!(cli_error("Prompt is too long").retry_after(), None);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_failures_map_to_http_status() {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use clay::server::{router, AppState};
+    use clay::ClaudeSetup;
+    use std::fs;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    /// Stand-in for the Claude CLI that fails according to the prompt
+    const FAKE_FAILING_CLAUDE: &str = r#"#!/bin/sh
+prompt=$(cat)
+case "$prompt" in
+  *busy*)
+    echo 'API Error: 529 {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}' >&2
+    exit 1;;
+  *huge*)
+    echo '{"type":"result","is_error":true,"result":"Prompt is too long"}'
+    exit 1;;
+esac
+echo '{"type":"result","result":"OK","is_error":false}'
+"#;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("key"), "sk-ant-api03-test").unwrap();
+    fs::write(temp_dir.path().join("clay.yaml"), "auth:\n  api_key:\n    file: key\n").unwrap();
+    common::install_fake_claude(temp_dir.path(), FAKE_FAILING_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+
+    let chat = |session: &str, content: &str| {
+        let body = serde_json::json!({"model": "claude", "messages": [{"role": "user", "content": content}]});
+        Request::post("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-session-id", session)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(chat("a", "are you busy?")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+
+    let response = app.clone().oneshot(chat("b", "a huge prompt")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.headers().get(header::RETRY_AFTER).is_none());
+
+    let response = app.oneshot(chat("c", "hello")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,92 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Stand-in for the Claude CLI that records prompts per session and reports JSON results
+pub const FAKE_CLAUDE: &str = r#"#!/bin/sh
+prompt=$(cat)
+resume=""
+while [ $# -gt 0 ]; do
+  case "$1" in --resume) resume="$2"; shift;; esac
+  shift
+done
+sid=${resume:-session-$$}
+mkdir -p "$HOME/.claude/projects/test"
+echo "$prompt" >> "$HOME/.claude/projects/test/$sid.jsonl"
+printf '{"type":"result","result":"resumed=%s","session_id":"%s","is_error":false}\n' "$resume" "$sid"
+"#;
+
+/// Stand-in for a stream-json Claude CLI that answers every stdin line and exits after a "CRASH" turn
+pub const FAKE_STREAMING_CLAUDE: &str = r#"#!/bin/sh
+resume=""
+while [ $# -gt 0 ]; do
+  case "$1" in --resume) resume="$2"; shift;; esac
+  shift
+done
+sid=${resume:-stream-$$}
+echo "{\"type\":\"system\",\"subtype\":\"init\",\"session_id\":\"$sid\"}"
+n=0
+while read -r line; do
+  n=$((n+1))
+  echo "{\"type\":\"result\",\"result\":\"pid=$$ resumed=$resume turn=$n\",\"session_id\":\"$sid\",\"is_error\":false}"
+  case "$line" in *CRASH*) exit 1;; esac
+done
+"#;
+
+/// Stand-in for the Claude CLI whose `setup-token` trades "good-code" for a token
+/// and whose `--print` only answers when that token is in the environment
+pub const FAKE_LOGIN_CLAUDE: &str = r#"#!/bin/sh
+if [ "$1" = "setup-token" ]; then
+  echo "Visit https://claude.ai/oauth/authorize?code=true&state=abc to sign in"
+  printf "Paste code here if prompted > "
+  read -r code
+  if [ "$code" = "good-code" ]; then
+    echo "Your OAuth token: sk-ant-REDACTED"
+    exit 0
+  fi
+  echo "OAuth error: Invalid code"
+  exit 1
+fi
+cat > /dev/null
+if [ "$CLAUDE_CODE_OAUTH_TOKEN" != "sk-ant-REDACTED" ]; then
+  echo "Invalid API key · Please run /login"
+  exit 1
+fi
+printf '{"type":"result","result":"OK","session_id":"probe-%s","is_error":false}\n' "$$"
+"#;
+
+/// Keep credentials from the developer's own environment out of auth tests
+pub fn clear_ambient_credentials() {
+    std::env::remove_var("ANTHROPIC_API_KEY");
+    std::env::remove_var("CLAUDE_CODE_OAUTH_TOKEN");
+}
+
+/// Put a fake `claude` where `ClaudeSetup` expects the portable install
+pub fn install_fake_claude(base_dir: &Path, script: &str) {
+    let bin_dir = base_dir.join(".bun").join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    let claude = bin_dir.join("claude");
+    fs::write(&claude, script).unwrap();
+    fs::set_permissions(&claude, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Stand-in for `claude --output-format stream-json` that runs one tool call per message.
+/// A prompt containing "HANG" records the pid and never finishes.
+pub const FAKE_EVENTS_CLAUDE: &str = r#"#!/bin/sh
+prompt=$(cat)
+resume=""
+while [ $# -gt 0 ]; do
+  case "$1" in --resume) resume="$2"; shift;; esac
+  shift
+done
+sid=${resume:-events-$$}
+case "$prompt" in *HANG*) echo $$ > "$HOME/hang.pid";; esac
+echo "{\"type\":\"system\",\"subtype\":\"init\",\"session_id\":\"$sid\"}"
+echo '{"type":"assistant","message":{"content":[{"type":"text","text":"Let me look."},{"type":"tool_use","id":"toolu_1","name":"Read","input":{"path":"a.txt"}}]}}'
+case "$prompt" in *HANG*) sleep 60;; esac
+echo '{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"hello"}]}}'
+echo '{"type":"assistant","message":{"content":[{"type":"text","text":"It says hello."}]}}'
+echo "{\"type\":\"result\",\"subtype\":\"success\",\"result\":\"It says hello.\",\"session_id\":\"$sid\",\"is_error\":false,\"duration_ms\":12,\"usage\":{\"input_tokens\":10,\"output_tokens\":5}}"
+"#;
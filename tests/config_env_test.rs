@@ -0,0 +1,63 @@
+use clay::{ClaudeRelayError, Config};
+use std::collections::HashMap;
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |name| vars.get(name).cloned()
+}
+
+const YAML: &str = r#"
+context: "Project ${PROJECT:-demo} costs $$5"
+mcp:
+  servers:
+    db:
+      command: npx
+      args: ["--url", "${DATABASE_URL}"]
+      env:
+        DATABASE_URL: "${DATABASE_URL:?set DATABASE_URL to your database}"
+    api:
+      url: "https://${API_HOST:-api.example.com}/mcp"
+      headers:
+        Authorization: "Bearer ${API_TOKEN}"
+"#;
+
+#[test]
+fn test_expands_variables_in_every_string_field() {
+    let config = Config::from_yaml_str(YAML, env(&[
+        ("DATABASE_URL", "postgres://db"),
+        // Values are inserted literally, never expanded again
+        ("API_TOKEN", "${API_TOKEN}"),
+    ])).unwrap();
+
+    assert_eq!(config.context.as_deref(), Some("Project demo costs $5"));
+    let servers = &config.mcp.unwrap().servers;
+    assert_eq!(servers["db"].args, vec!["--url", "postgres://db"]);
+    assert_eq!(servers["db"].env["DATABASE_URL"], "postgres://db");
+    assert_eq!(servers["api"].url.as_deref(), Some("https://api.example.com/mcp"));
+    assert_eq!(servers["api"].headers["Authorization"], "Bearer ${API_TOKEN}");
+}
+
+#[test]
+fn test_missing_required_variable_names_the_field() {
+    let error = Config::from_yaml_str(YAML, env(&[])).unwrap_err();
+    let ClaudeRelayError::Config(message) = error else {
+        panic!("unexpected error: {:?}", error);
+    };
+    assert_eq!(
+        message,
+        "mcp.servers.db.env.DATABASE_URL: set DATABASE_URL to your database (DATABASE_URL)"
+    );
+
+    assert!(Config::from_yaml_str("context: \"${BROKEN\"", env(&[])).is_err());
+}
+
+#[test]
+fn test_expanded_values_fill_numeric_and_boolean_fields() {
+    let yaml = "server:\n  port: ${PORT:-8080}\n  max_processes: ${MAX_PROCESSES}\n  watch_config: ${WATCH:-false}\n";
+    let config = Config::from_yaml_str(yaml, env(&[("MAX_PROCESSES", "12")])).unwrap();
+
+    let server = config.server.unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.max_processes, 12);
+    assert!(!server.watch_config);
+}
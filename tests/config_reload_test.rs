@@ -0,0 +1,71 @@
+use clay::{AccountPool, ClaudeSetup, ConfigWatcher};
+use std::fs;
+use std::sync::Arc;
+
+const YAML: &str = "\
+context: first
+mcp:
+  servers:
+    files:
+      command: npx
+accounts:
+  list:
+    - name: team-1
+      auth:
+        oauth_token:
+          env: TEAM1_TOKEN
+";
+
+#[test]
+fn test_edits_are_applied_and_invalid_ones_rejected() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("clay.yaml"), YAML).unwrap();
+    let setup = Arc::new(ClaudeSetup::new(dir.to_str().unwrap()).unwrap());
+    let accounts = AccountPool::new(setup.clone());
+    let watcher = ConfigWatcher::new(setup.clone(), &accounts);
+    assert!(!watcher.check_now());
+
+    fs::write(dir.join("clay.yaml"), YAML.replace("first", "second").replace("    files:", "    db:\n      command: psql\n    files:")).unwrap();
+    assert!(watcher.check_now());
+    assert_eq!(setup.get_initial_context().as_deref(), Some("second"));
+    let account = accounts.get("team-1").unwrap();
+    let mcp = fs::read_to_string(account.setup().get_claude_home().join(".config/claude/mcp.json")).unwrap();
+    assert!(mcp.contains("psql"), "{}", mcp);
+    // The account keeps its own credentials after the swap
+    assert_eq!(account.setup().get_auth_config().unwrap().oauth_token.unwrap().env.as_deref(), Some("TEAM1_TOKEN"));
+
+    fs::write(dir.join("clay.yaml"), "context: third\nserver:\n  port: lots\n").unwrap();
+    assert!(!watcher.check_now());
+    assert_eq!(setup.get_initial_context().as_deref(), Some("second"));
+    let status = watcher.status();
+    assert_eq!((status.reloads, status.rejected), (1, 1));
+    assert!(status.last_error.unwrap().contains("server.port"));
+
+    // A new local override counts as an edit too
+    fs::write(dir.join("clay.yaml"), YAML).unwrap();
+    fs::write(dir.join("clay.local.yaml"), "context: mine\n").unwrap();
+    assert!(watcher.check_now());
+    assert_eq!(setup.get_initial_context().as_deref(), Some("mine"));
+    assert!(watcher.status().last_error.is_none());
+}
+
+#[test]
+fn test_reload_swaps_only_after_claude_config_is_written() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("clay.yaml"), "context: first\n").unwrap();
+    let setup = Arc::new(ClaudeSetup::new(dir.to_str().unwrap()).unwrap());
+    let snapshot = setup.get_config().unwrap();
+
+    // Without a clay binary, mcp.json can't be written for a remote server
+    fs::write(dir.join("clay.yaml"), "context: second\nmcp:\n  servers:\n    remote:\n      url: http://localhost:9/mcp\n").unwrap();
+    assert!(setup.reload_config().is_err());
+    assert_eq!(setup.get_initial_context().as_deref(), Some("first"));
+
+    fs::write(dir.join("clay.yaml"), "context: third\n").unwrap();
+    setup.reload_config().unwrap();
+    assert_eq!(setup.get_initial_context().as_deref(), Some("third"));
+    // Snapshots taken before a reload keep the configuration they were taken from
+    assert_eq!(snapshot.context.as_deref(), Some("first"));
+}
@@ -0,0 +1,49 @@
+use clay::{ClaudeRelayError, Config};
+
+fn no_env(_: &str) -> Option<String> {
+    None
+}
+
+#[test]
+fn test_unknown_keys_warn_with_suggestions() {
+    let yaml = "\
+servres:
+  port: 3000
+mcp:
+  servers:
+    api:
+      trasport: http
+      url: http://localhost:8080/mcp
+accounts:
+  list:
+    - name: team-1
+      auht: {}
+";
+    let report = Config::check_yaml_str(yaml, no_env).unwrap();
+    assert_eq!(report.warnings, vec![
+        "unknown key `servres` (line 1, column 1); did you mean `server`?",
+        "unknown key `mcp.servers.api.trasport` (line 6, column 7); did you mean `transport`?",
+        "unknown key `accounts.list.0.auht` (line 11, column 7); did you mean `auth`?",
+    ]);
+}
+
+#[test]
+fn test_errors_report_line_and_column() {
+    let error = Config::check_yaml_str("server:\n  port: [\n", no_env).unwrap_err();
+    assert!(matches!(error, ClaudeRelayError::Yaml(_)), "{:?}", error);
+    assert!(error.to_string().contains("line 3 column 1"), "{}", error);
+
+    let error = Config::check_yaml_str("context: hi\nserver:\n  port: lots\n", no_env).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid configuration: server.port at line 3 column 3: invalid type: string \"lots\", expected u16"
+    );
+}
+
+#[test]
+fn test_schema_describes_config() {
+    let schema = clay::schema::json_schema();
+    assert_eq!(schema["title"], "clay.yaml");
+    assert!(schema["properties"]["mcp"].is_object());
+    assert!(schema["properties"]["server"].is_object());
+}
@@ -0,0 +1,132 @@
+#![cfg(unix)]
+
+mod common;
+
+use clay::ClaudeSetup;
+use common::clear_ambient_credentials;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+const CLI_LOGIN: &str = r#"{"claudeAiOauth":{"accessToken":"sk-ant-oat01-x","refreshToken":"sk-ant-ort01-y"}}"#;
+
+fn project(dir: &Path, yaml: &str) -> ClaudeSetup {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("clay.yaml"), yaml).unwrap();
+    ClaudeSetup::new(dir.to_str().unwrap()).unwrap()
+}
+
+#[test]
+fn test_projects_share_one_login() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let store = temp_dir.path().join("store");
+    let yaml = format!("credentials:\n  shared: true\n  path: {}\n", store.display());
+
+    // A login made before sharing was turned on is moved into the store
+    let first = project(&temp_dir.path().join("first"), &yaml);
+    let local_login = first.get_claude_home().join(".claude/.credentials.json");
+    fs::create_dir_all(local_login.parent().unwrap()).unwrap();
+    fs::write(&local_login, CLI_LOGIN).unwrap();
+    first.setup_claude_home().unwrap();
+    assert_eq!(fs::read_to_string(store.join(".credentials.json")).unwrap(), CLI_LOGIN);
+
+    let second = project(&temp_dir.path().join("second"), &yaml);
+    second.setup_claude_home().unwrap();
+    let (authenticated, detail) = second.get_auth_status().unwrap();
+    assert!(authenticated);
+    assert_eq!(detail, "Claude account login");
+    assert!(fs::symlink_metadata(second.get_claude_home().join(".claude/.credentials.json"))
+        .unwrap().file_type().is_symlink());
+
+    // Tokens saved by one project are visible to the other
+    first.set_auth_token("sk-ant-oat01-shared").unwrap();
+    assert_eq!(second.stored_oauth_token().as_deref(), Some("sk-ant-oat01-shared"));
+    assert_eq!(second.get_oauth_token_path(), store.join("oauth_token"));
+}
+
+#[test]
+fn test_copy_auth_from_claude_home() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = project(&temp_dir.path().join("project"), "");
+    assert!(setup.copy_auth_from(&temp_dir.path().join("empty")).is_err());
+
+    // Same layout as a system-wide `~/.claude`
+    let home = temp_dir.path().join("home");
+    fs::create_dir_all(home.join(".claude")).unwrap();
+    fs::write(home.join(".claude/.credentials.json"), CLI_LOGIN).unwrap();
+
+    assert_eq!(setup.copy_auth_from(&home).unwrap(), vec![".credentials.json"]);
+    let copied = setup.get_claude_home().join(".claude/.credentials.json");
+    assert_eq!(fs::read_to_string(&copied).unwrap(), CLI_LOGIN);
+    assert!(setup.check_authentication().unwrap());
+    assert_eq!(fs::metadata(&copied).unwrap().permissions().mode() & 0o777, 0o600);
+}
+
+#[test]
+fn test_tokens_are_encrypted_at_rest() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("project");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("key"), "correct horse battery staple").unwrap();
+
+    // A token saved before encryption was turned on is encrypted by the next setup
+    let plain = project(&dir, "");
+    plain.set_auth_token("sk-ant-oat01-secret").unwrap();
+    let setup = project(&dir, "credentials:\n  encryption_key:\n    file: key\n");
+    setup.setup_claude_home().unwrap();
+
+    let on_disk = fs::read_to_string(setup.get_oauth_token_path()).unwrap();
+    assert!(on_disk.starts_with("clay-encrypted:v1:"), "{}", on_disk);
+    assert!(!on_disk.contains("sk-ant-oat01-secret"));
+    assert_eq!(setup.stored_oauth_token().as_deref(), Some("sk-ant-oat01-secret"));
+    assert!(setup.get_claude_env().contains(&("CLAUDE_CODE_OAUTH_TOKEN".to_string(), "sk-ant-oat01-secret".to_string())));
+
+    // Without the key the token cannot be read back
+    fs::write(dir.join("key"), "wrong key").unwrap();
+    assert_eq!(setup.stored_oauth_token(), None);
+
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&setup.get_oauth_token_path()), 0o600);
+    assert_eq!(mode(setup.get_oauth_token_path().parent().unwrap()), 0o700);
+}
+
+#[test]
+fn test_claude_home_is_private_and_ignored_by_git() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("repo");
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(dir.join(".gitignore"), "target\n/.bun\n").unwrap();
+    let setup = project(&dir, "");
+
+    setup.setup_claude_home().unwrap();
+    setup.setup_claude_home().unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join(".gitignore")).unwrap(),
+        "target\n/.bun\n# Clay: Claude CLI install, logins and local config\n.claude-home/\n.claude-accounts/\nclay.local.yaml\n"
+    );
+    assert_eq!(fs::metadata(setup.get_claude_home()).unwrap().permissions().mode() & 0o777, 0o700);
+    assert!(setup.check_credential_permissions().is_empty());
+
+    fs::set_permissions(setup.get_claude_home(), fs::Permissions::from_mode(0o755)).unwrap();
+    let warnings = setup.check_credential_permissions();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("chmod 700"), "{}", warnings[0]);
+}
+
+#[test]
+fn test_saved_tokens_are_private_even_over_a_readable_file() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = project(&temp_dir.path().join("project"), "");
+    let path = setup.get_oauth_token_path();
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, "old").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    setup.set_auth_token("sk-ant-oat01-secret").unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "sk-ant-oat01-secret");
+}
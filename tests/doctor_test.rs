@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+use clay::doctor::leaked_env_vars;
+use clay::{CheckStatus, ClaudeSetup};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn test_leaked_env_vars_skip_clays_own_credentials() {
+    let env: Vec<(String, String)> = [
+        ("PATH", "/usr/bin"),
+        ("HOME", "/home/me"),
+        ("ANTHROPIC_API_KEY", "sk-ant"),
+        ("CLAUDE_RELAY", "true"),
+        ("ANTHROPIC_BASE_URL", "https://proxy.example"),
+        ("GITHUB_TOKEN", "ghp"),
+        ("CLAUDE_CODE_USE_BEDROCK", "1"),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+
+    assert_eq!(leaked_env_vars(&env), vec!["ANTHROPIC_BASE_URL", "CLAUDE_CODE_USE_BEDROCK", "GITHUB_TOKEN"]);
+}
+
+#[tokio::test]
+async fn test_doctor_reports_each_problem_with_a_fix() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let cli = dir.join("claude");
+    fs::write(&cli, "#!/bin/sh\necho '1.0.58 (Claude Code)'\n").unwrap();
+    fs::set_permissions(&cli, fs::Permissions::from_mode(0o755)).unwrap();
+    let mcp_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    fs::write(dir.join("clay.yaml"), format!("\
+install:
+  strategy: claude
+mcp:
+  servers:
+    shell:
+      command: sh
+    missing:
+      command: no-such-mcp-server
+    api:
+      url: http://{}/mcp
+", mcp_listener.local_addr().unwrap())).unwrap();
+    // Something already listens on the port
+    let busy = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    let report = setup.doctor(busy.local_addr().unwrap().port()).await;
+    let status = |name: &str| report.get(name).unwrap_or_else(|| panic!("no {} check", name)).status;
+
+    assert_eq!(status("bun"), CheckStatus::Pass);
+    assert_eq!(status("claude_cli"), CheckStatus::Pass);
+    assert!(report.get("claude_cli").unwrap().detail.contains("version 1.0.58"));
+    assert_eq!(status("claude_home"), CheckStatus::Pass);
+    assert_eq!(status("config"), CheckStatus::Pass);
+    assert_eq!(status("mcp:shell"), CheckStatus::Pass);
+    assert_eq!(status("mcp:api"), CheckStatus::Pass);
+    assert_eq!(status("mcp:missing"), CheckStatus::Fail);
+    assert_eq!(status("port"), CheckStatus::Fail);
+    assert!(report.get("port").unwrap().fix.as_deref().unwrap().contains("--port"));
+    assert!(!report.passed());
+
+    let json = serde_json::to_value(&report).unwrap();
+    let missing = json["checks"].as_array().unwrap().iter().find(|check| check["name"] == "mcp:missing").unwrap();
+    assert_eq!(missing["status"], "fail");
+    assert_eq!(missing["fix"], "Install no-such-mcp-server or fix mcp.servers.missing.command");
+
+    // A missing CLI is a failure that points at setup
+    fs::remove_file(&cli).unwrap();
+    let report = setup.doctor(0).await;
+    assert_eq!(report.get("claude_cli").unwrap().fix.as_deref(), Some("Fix install.strategy or install the CLI there"));
+}
@@ -0,0 +1,158 @@
+#![cfg(unix)]
+
+use axum::extract::{Path as UrlPath, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use base64::Engine;
+use clay::cache::{claude_tarball_name, current_bun_asset};
+use clay::download::{find_checksum, sha256_file, Downloader};
+use clay::ClaudeSetup;
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Default)]
+struct Mirror {
+    files: HashMap<String, Vec<u8>>,
+    /// Requests to fail with a 500 before serving, by file
+    failures: HashMap<String, usize>,
+    /// `(file, Range header)` of every request
+    requests: Vec<(String, Option<String>)>,
+}
+
+type SharedMirror = Arc<Mutex<Mirror>>;
+
+/// Static file server with Range support, standing in for GitHub and the npm registry
+async fn start_mirror(mirror: SharedMirror) -> String {
+    let app = Router::new()
+        .route("/*file", get(|State(mirror): State<SharedMirror>, UrlPath(file): UrlPath<String>, headers: HeaderMap| async move {
+            let mut mirror = mirror.lock().unwrap();
+            let range = headers.get("range").map(|value| value.to_str().unwrap().to_string());
+            mirror.requests.push((file.clone(), range.clone()));
+            if let Some(failures) = mirror.failures.get_mut(&file).filter(|failures| **failures > 0) {
+                *failures -= 1;
+                return (StatusCode::INTERNAL_SERVER_ERROR, Vec::new());
+            }
+            let Some(body) = mirror.files.get(&file) else {
+                return (StatusCode::NOT_FOUND, Vec::new());
+            };
+            match range.and_then(|range| range.strip_prefix("bytes=")?.strip_suffix('-')?.parse::<usize>().ok()) {
+                Some(offset) => (StatusCode::PARTIAL_CONTENT, body[offset..].to_vec()),
+                None => (StatusCode::OK, body.clone()),
+            }
+        }))
+        .with_state(mirror);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+fn bun_zip(asset: &str) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file(format!("{}/bun", asset), zip::write::SimpleFileOptions::default()).unwrap();
+    zip.write_all(b"#!/bin/sh\necho \"$@\" >> \"$BUN_INSTALL/args\"\n").unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    sha2::Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[tokio::test]
+async fn test_download_resumes_retries_and_verifies() {
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let mirror = SharedMirror::default();
+    {
+        let mut mirror = mirror.lock().unwrap();
+        mirror.files.insert("big.zip".to_string(), data.clone());
+        mirror.failures.insert("big.zip".to_string(), 1);
+    }
+    let url = format!("{}/big.zip", start_mirror(mirror.clone()).await);
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dest = temp_dir.path().join("big.zip");
+    // Left over from an interrupted download
+    fs::write(temp_dir.path().join("big.zip.part"), &data[..40_000]).unwrap();
+
+    let downloader = Downloader::new(false).retry_delay(Duration::from_millis(10));
+    downloader.download(&url, &dest, Some(&sha256_hex(&data))).await.unwrap();
+    assert_eq!(fs::read(&dest).unwrap(), data);
+    let requests = mirror.lock().unwrap().requests.clone();
+    assert_eq!(requests, vec![
+        ("big.zip".to_string(), Some("bytes=40000-".to_string())),
+        ("big.zip".to_string(), Some("bytes=40000-".to_string())),
+    ]);
+
+    let error = downloader.download(&url, &dest, Some(&"0".repeat(64))).await.unwrap_err();
+    assert!(error.to_string().contains("Checksum mismatch"), "{}", error);
+    assert!(!temp_dir.path().join("big.zip.part").exists());
+
+    assert_eq!(find_checksum("abc  bun-linux-x64.zip\nDEF  bun-darwin-x64.zip\n", "bun-darwin-x64.zip").as_deref(), Some("def"));
+
+    // Verified downloads hand back the bytes they checked and leave nothing behind
+    let bytes = downloader.download_verified(&url, temp_dir.path(), &sha256_hex(&data)).await.unwrap();
+    assert_eq!(bytes, data);
+    let error = downloader.download_verified(&url, temp_dir.path(), &"0".repeat(64)).await.unwrap_err();
+    assert!(error.to_string().contains("Checksum mismatch"), "{}", error);
+    assert!(!temp_dir.path().join("big.zip.part").exists());
+}
+
+#[tokio::test]
+async fn test_bun_mirror_registry_and_bundle() {
+    let asset = current_bun_asset().unwrap();
+    let zip_name = format!("{}.zip", asset);
+    let zip = bun_zip(asset);
+    let tarball = b"packed claude cli".to_vec();
+    let integrity = format!("sha512-{}", base64::engine::general_purpose::STANDARD.encode(Sha512::digest(&tarball)));
+
+    let mirror = SharedMirror::default();
+    let base = start_mirror(mirror.clone()).await;
+    {
+        let mut mirror = mirror.lock().unwrap();
+        mirror.files.insert(zip_name.clone(), zip.clone());
+        mirror.files.insert("SHASUMS256.txt".to_string(), format!("{}  {}\n", sha256_hex(&zip), zip_name).into_bytes());
+        mirror.files.insert("claude-code-1.0.58.tgz".to_string(), tarball.clone());
+        mirror.files.insert("@anthropic-ai/claude-code".to_string(), serde_json::to_vec(&serde_json::json!({
+            "dist-tags": { "latest": "1.0.58" },
+            "versions": { "1.0.58": { "dist": { "tarball": format!("{}/claude-code-1.0.58.tgz", base), "integrity": integrity } } }
+        })).unwrap());
+    }
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("clay.yaml"), format!("install:\n  bun_mirror: {}/\n  npm_registry: {}\n", base, base)).unwrap();
+    let setup = ClaudeSetup::builder(dir).quiet(true).build().unwrap();
+    setup.install_bun().await.unwrap();
+    assert!(dir.join(".bun/bin/bun").exists());
+
+    // The bundle is verified and lists its own checksums
+    let cache = dir.join("cache");
+    let bundle = setup.bundle(&cache, &[asset.to_string()]).await.unwrap();
+    assert_eq!(bundle.claude_version, "1.0.58");
+    let tarball_path = cache.join(claude_tarball_name("1.0.58"));
+    assert_eq!(fs::read(&tarball_path).unwrap(), tarball);
+    let shasums = fs::read_to_string(cache.join("SHASUMS256.txt")).unwrap();
+    assert_eq!(find_checksum(&shasums, &claude_tarball_name("1.0.58")), Some(sha256_file(&tarball_path).unwrap()));
+
+    // A tampered cache is refused
+    fs::write(&tarball_path, "tampered").unwrap();
+    fs::write(dir.join("clay.yaml"), "install:\n  cache_dir: cache\n").unwrap();
+    let setup = ClaudeSetup::builder(dir).quiet(true).build().unwrap();
+    let error = setup.install_claude().unwrap_err();
+    assert!(error.to_string().contains("Checksum mismatch"), "{}", error);
+
+    // A pinned hash wins over SHASUMS256.txt
+    let other = tempfile::tempdir().unwrap();
+    fs::write(
+        other.path().join("clay.yaml"),
+        format!("install:\n  bun_mirror: {}\n  sha256:\n    {}: {}\n", base, zip_name, "0".repeat(64)),
+    ).unwrap();
+    let setup = ClaudeSetup::builder(other.path()).quiet(true).build().unwrap();
+    let error = setup.install_bun().await.unwrap_err();
+    assert!(error.to_string().contains("Checksum mismatch"), "{}", error);
+    assert!(!other.path().join(".bun").exists());
+}
@@ -0,0 +1,114 @@
+#![cfg(unix)]
+
+mod common;
+
+use clay::{ClaudeEvent, ClaudeProcess, ClaudeSetup, TokenUsage};
+use common::{install_fake_claude, FAKE_EVENTS_CLAUDE};
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_stream_message_yields_typed_events() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_EVENTS_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let mut process = ClaudeProcess::new(setup).unwrap();
+
+    let events: Vec<ClaudeEvent> = process
+        .stream_message("What is in a.txt?")
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(events[0], ClaudeEvent::TextDelta { text: "Let me look.".into() });
+    assert!(matches!(&events[1], ClaudeEvent::ToolUseStarted { name, .. } if name == "Read"));
+    assert_eq!(
+        events[2],
+        ClaudeEvent::ToolUseFinished { id: "toolu_1".into(), output: "hello".into(), is_error: false }
+    );
+    assert_eq!(events[3], ClaudeEvent::TextDelta { text: "It says hello.".into() });
+    assert_eq!(
+        events[4],
+        ClaudeEvent::Usage(TokenUsage { input_tokens: 10, output_tokens: 5, ..Default::default() })
+    );
+    assert!(matches!(&events[5], ClaudeEvent::Result { text, .. } if text == "It says hello."));
+
+    let (user, claude) = process.get_last_exchange().unwrap();
+    assert_eq!(user, "What is in a.txt?");
+    assert_eq!(claude, "It says hello.");
+
+    // The next turn resumes the CLI session reported by the first
+    assert!(process.get_cli_session_id().unwrap().starts_with("events-"));
+    assert_eq!(process.send_message_async("And now?").await.unwrap(), "It says hello.");
+}
+
+#[tokio::test]
+async fn test_dropping_stream_cancels_child() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_EVENTS_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let mut process = ClaudeProcess::new(setup.clone()).unwrap();
+    let history_before = process.get_conversation_history().len();
+
+    {
+        let mut stream = process.stream_message("HANG please").unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(ClaudeEvent::TextDelta { .. }))));
+    }
+
+    // Cancelled turns leave no trace in the history
+    assert_eq!(process.get_conversation_history().len(), history_before);
+
+    let pid_file = setup.get_claude_home().join("hang.pid");
+    let mut killed = false;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let Ok(pid) = std::fs::read_to_string(&pid_file) else { continue };
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success();
+        if !alive {
+            killed = true;
+            break;
+        }
+    }
+    assert!(killed, "Claude child should be killed when the stream is dropped");
+}
+
+/// Emits partial text deltas only when asked for them with --include-partial-messages.
+/// A prompt containing "FAIL" closes stdout, then explains on stderr a moment later.
+const FAKE_PARTIAL_CLAUDE: &str = r#"#!/bin/sh
+prompt=$(cat)
+case "$prompt" in *FAIL*)
+  exec 1>&-
+  sleep 0.3
+  echo "Invalid API key · Please run /login" >&2
+  exit 1;;
+esac
+case " $* " in *" --include-partial-messages "*) ;; *) echo "partial messages not requested" >&2; exit 1;; esac
+echo '{"type":"stream_event","event":{"type":"content_block_delta","delta":{"type":"text_delta","text":"Hel"}}}'
+echo '{"type":"stream_event","event":{"type":"content_block_delta","delta":{"type":"text_delta","text":"lo"}}}'
+echo '{"type":"assistant","message":{"content":[{"type":"text","text":"Hello"}]}}'
+echo '{"type":"result","subtype":"success","result":"Hello","session_id":"partial-1","is_error":false}'
+"#;
+
+#[tokio::test]
+async fn test_stream_message_delivers_partial_text() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_PARTIAL_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let mut process = ClaudeProcess::new(setup).unwrap();
+
+    let events: Vec<ClaudeEvent> = process.stream_message("Hi").unwrap().map(|event| event.unwrap()).collect().await;
+    // The whole block repeats the deltas, so it isn't emitted again
+    assert_eq!(events[..2], [ClaudeEvent::TextDelta { text: "Hel".into() }, ClaudeEvent::TextDelta { text: "lo".into() }]);
+    assert!(matches!(&events[2], ClaudeEvent::Result { text, .. } if text == "Hello"), "{:?}", events);
+
+    // Errors carry what the CLI wrote to stderr after stdout closed
+    let error = process.stream_message("FAIL").unwrap().next().await.unwrap().unwrap_err();
+    assert!(error.to_string().to_lowercase().contains("login"), "{}", error);
+}
@@ -0,0 +1,76 @@
+use clay::export::{export_process, import_process};
+use clay::{ClaudeSetup, ConversationExport, ExportFormat};
+use std::sync::Arc;
+
+const OPENAI_MESSAGES: &str = r#"[
+    {"role": "system", "content": "You are terse."},
+    {"role": "user", "content": "Hello"},
+    {"role": "assistant", "content": "Hi there"},
+    {"role": "user", "content": "Bye"},
+    {"role": "assistant", "content": "Goodbye"}
+]"#;
+
+#[test]
+fn test_openai_import_export_roundtrip() {
+    let conversation = ConversationExport::parse(OPENAI_MESSAGES, None).unwrap();
+    assert_eq!(conversation.history.len(), 5);
+    assert_eq!(conversation.history[0], "System Context: You are terse.");
+    assert_eq!(conversation.history[2], "Claude: Hi there");
+
+    let rendered = conversation.render(ExportFormat::OpenAi).unwrap();
+    let reparsed = ConversationExport::parse(&rendered, Some(ExportFormat::OpenAi)).unwrap();
+    assert_eq!(conversation.history, reparsed.history);
+}
+
+#[test]
+fn test_markdown_roundtrip() {
+    let conversation = ConversationExport::parse(OPENAI_MESSAGES, None).unwrap();
+    let markdown = conversation.render(ExportFormat::Markdown).unwrap();
+    assert!(markdown.contains("## Assistant\n\nHi there"));
+
+    let reparsed = ConversationExport::parse(&markdown, None).unwrap();
+    assert_eq!(conversation.history, reparsed.history);
+}
+
+#[test]
+fn test_json_export_keeps_states() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+
+    let mut process = import_process(setup.clone(), OPENAI_MESSAGES, None).unwrap();
+    process.undo_to_index(1).unwrap();
+    process.save_state();
+
+    let json = export_process(&process, Some("session-1"), ExportFormat::Json).unwrap();
+    let conversation = ConversationExport::parse(&json, None).unwrap();
+    assert_eq!(conversation.session_id.as_deref(), Some("session-1"));
+    assert_eq!(conversation.history, process.get_conversation_history());
+    assert_eq!(conversation.last_undone_history.as_ref().map(|h| h.len()), Some(5));
+
+    let restored = import_process(setup, &json, Some(ExportFormat::Json)).unwrap();
+    assert!(restored.can_restore());
+    assert_eq!(
+        restored.get_conversation_states()[0].timestamp,
+        process.get_conversation_states()[0].timestamp
+    );
+}
+
+#[test]
+fn test_unknown_format_is_rejected() {
+    assert!("yaml".parse::<ExportFormat>().is_err());
+    assert!(ConversationExport::parse(r#"{"foo": 1}"#, None).is_err());
+}
+
+#[test]
+fn test_markdown_roundtrip_keeps_heading_lines_in_content() {
+    let messages = r#"[
+        {"role": "user", "content": "Format a transcript:\n## Assistant\nlike this"},
+        {"role": "assistant", "content": "Sure:\n## User\n\\## System\nDone"}
+    ]"#;
+    let conversation = ConversationExport::parse(messages, None).unwrap();
+    let markdown = conversation.render(ExportFormat::Markdown).unwrap();
+    assert!(markdown.contains("\\## Assistant\nlike this"), "{}", markdown);
+
+    let reparsed = ConversationExport::parse(&markdown, None).unwrap();
+    assert_eq!(conversation.history, reparsed.history);
+}
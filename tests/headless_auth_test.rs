@@ -0,0 +1,124 @@
+#![cfg(unix)]
+
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{header, Request, StatusCode};
+use clay::server::{router, AppState};
+use clay::ClaudeSetup;
+use common::{clear_ambient_credentials, install_fake_claude, FAKE_LOGIN_CLAUDE};
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn post_json(uri: &str, body: &str, token: Option<&str>) -> Request<Body> {
+    let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+async fn json_body(response: axum::response::Response) -> serde_json::Value {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_unauthenticated_server_completes_login_over_http() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("clay.yaml"), "server:\n  admin_token: secret\n").unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_LOGIN_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+
+    let state = Arc::new(AppState::new(setup));
+    assert!(!state.is_authenticated());
+    let app = router(state.clone());
+
+    let chat = r#"{"model":"claude","messages":[{"role":"user","content":"Hello"}]}"#;
+    let response = app.clone().oneshot(post_json("/v1/chat/completions", chat, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(json_body(response).await["error"]["type"], "authentication_error");
+
+    // Admin endpoints require the configured bearer token
+    let response = app.clone().oneshot(post_json("/admin/auth/start", "", None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app.clone().oneshot(post_json("/admin/auth/start", "", Some("secret"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let auth_url = json_body(response).await["auth_url"].as_str().unwrap().to_string();
+    assert!(auth_url.starts_with("https://claude.ai/oauth/authorize"), "{}", auth_url);
+
+    // A wrong code is reported and the next attempt needs a fresh URL
+    let bad = r#"{"code":"bad-code"}"#;
+    let response = app.clone().oneshot(post_json("/admin/auth/complete", bad, Some("secret"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(!state.is_authenticated());
+
+    app.clone().oneshot(post_json("/admin/auth/start", "", Some("secret"))).await.unwrap();
+    let good = r#"{"code":"good-code"}"#;
+    let response = app.clone().oneshot(post_json("/admin/auth/complete", good, Some("secret"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["login"]["status"], "authenticated");
+    assert!(state.is_authenticated());
+
+    let response = app.oneshot(post_json("/v1/chat/completions", chat, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["choices"][0]["message"]["content"], "OK");
+}
+
+#[tokio::test]
+async fn test_admin_endpoints_without_a_token_only_answer_this_machine() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_LOGIN_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+    let accounts = |peer: Option<[u8; 4]>| {
+        let mut request = Request::get("/admin/accounts");
+        if let Some(ip) = peer {
+            request = request.extension(ConnectInfo(SocketAddr::from((ip, 40000))));
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let response = app.clone().oneshot(accounts(Some([172, 17, 0, 1]))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.clone().oneshot(accounts(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.oneshot(accounts(Some([127, 0, 0, 1]))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_session_endpoints_need_an_admin() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    install_fake_claude(dir, FAKE_LOGIN_CLAUDE);
+    fs::write(dir.join("clay.yaml"), "server:\n  admin_token: secret\n").unwrap();
+    let setup = Arc::new(ClaudeSetup::new(dir.to_str().unwrap()).unwrap());
+    let app = router(Arc::new(AppState::new(setup)));
+    let messages = r#"[{"role": "user", "content": "Hello"}]"#;
+
+    let response = app.clone().oneshot(post_json("/v1/sessions/import?session_id=s", messages, Some("wrong"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.clone().oneshot(post_json("/v1/sessions/import?session_id=s", messages, Some("secret"))).await.unwrap();
+    assert_eq!(json_body(response).await["session_id"], "s");
+
+    for (method, uri) in [("GET", "/v1/sessions/s/export"), ("DELETE", "/v1/sessions/s")] {
+        let request = |token: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(request("wrong")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{} {}", method, uri);
+        let response = app.clone().oneshot(request("secret")).await.unwrap();
+        assert!(response.status().is_success(), "{} {}: {}", method, uri, response.status());
+    }
+}
@@ -0,0 +1,78 @@
+#![cfg(unix)]
+
+use clay::cache::{cached_claude_tarball, claude_tarball_name, current_bun_asset, pick_release};
+use clay::{ClaudeSetup, VersionReq};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+#[test]
+fn test_releases_are_picked_from_the_registry_and_cache() {
+    let packument = serde_json::json!({
+        "dist-tags": { "latest": "1.0.60" },
+        "versions": {
+            "1.0.58": { "dist": { "tarball": "https://registry.example/claude-code-1.0.58.tgz" } },
+            "1.0.60": { "dist": { "tarball": "https://registry.example/claude-code-1.0.60.tgz" } },
+            "1.1.0-beta.1": { "dist": { "tarball": "https://registry.example/claude-code-1.1.0-beta.1.tgz" } },
+            "2.0.0": { "dist": { "tarball": "https://registry.example/claude-code-2.0.0.tgz" } }
+        }
+    });
+    let req = |text: &str| text.parse::<VersionReq>().unwrap();
+    assert_eq!(pick_release(&packument, None).unwrap().version, "1.0.60");
+    let release = pick_release(&packument, Some(&req("^1.0"))).unwrap();
+    assert_eq!(release.version, "1.0.60");
+    assert_eq!(release.tarball, "https://registry.example/claude-code-1.0.60.tgz");
+    assert_eq!(pick_release(&packument, Some(&req("1.0.58"))).unwrap().version, "1.0.58");
+    assert!(pick_release(&packument, Some(&req("^3.0"))).is_none());
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cache = temp_dir.path();
+    assert_eq!(claude_tarball_name("1.0.58"), "anthropic-ai-claude-code-1.0.58.tgz");
+    for version in ["1.0.58", "1.0.60", "2.0.0"] {
+        fs::write(cache.join(claude_tarball_name(version)), "").unwrap();
+    }
+    assert_eq!(cached_claude_tarball(cache, None).unwrap().1.to_string(), "2.0.0");
+    let (path, version) = cached_claude_tarball(cache, Some(&req("~1.0.58"))).unwrap();
+    assert_eq!((path, version.to_string()), (cache.join(claude_tarball_name("1.0.60")), "1.0.60".to_string()));
+    let error = cached_claude_tarball(cache, Some(&req("^3.0"))).unwrap_err();
+    assert!(error.to_string().contains("matching claude.version ^3.0"), "{}", error);
+}
+
+/// A Bun release zip whose `bun` records its arguments and "installs" a CLI
+fn write_bun_zip(path: &Path, asset: &str) {
+    let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    zip.start_file(format!("{}/bun", asset), zip::write::SimpleFileOptions::default()).unwrap();
+    zip.write_all(b"#!/bin/sh\necho \"$@\" >> \"$BUN_INSTALL/args\"\nprintf '#!/bin/sh\\necho 1.0.58\\n' > \"$BUN_INSTALL/bin/claude\"\nchmod +x \"$BUN_INSTALL/bin/claude\"\n").unwrap();
+    zip.finish().unwrap();
+}
+
+#[tokio::test]
+async fn test_portable_install_from_cache_without_network() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let cache = dir.join("cache");
+    fs::create_dir_all(&cache).unwrap();
+    let asset = current_bun_asset().unwrap();
+    write_bun_zip(&cache.join(format!("{}.zip", asset)), asset);
+    fs::write(cache.join(claude_tarball_name("1.0.58")), "").unwrap();
+    fs::write(cache.join(claude_tarball_name("2.0.0")), "").unwrap();
+    fs::write(dir.join("clay.yaml"), "install:\n  cache_dir: cache\nclaude:\n  version: ^1.0\n").unwrap();
+
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    assert_eq!(setup.get_install_cache(), Some(dir.canonicalize().unwrap().join("cache")));
+    setup.install_cli().await.unwrap();
+
+    assert!(setup.is_installed());
+    let args = fs::read_to_string(dir.join(".bun/args")).unwrap();
+    let tarball = dir.canonicalize().unwrap().join("cache").join(claude_tarball_name("1.0.58"));
+    assert_eq!(args.trim(), format!("install -g {}", tarball.display()));
+
+    // A cache without a zip for this machine says how to make one
+    let empty = dir.join("empty");
+    fs::create_dir_all(&empty).unwrap();
+    fs::write(dir.join("clay.yaml"), "install:\n  cache_dir: empty\n").unwrap();
+    fs::remove_dir_all(dir.join(".bun")).unwrap();
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    let error = setup.install_cli().await.unwrap_err();
+    assert!(error.to_string().contains("clay --bundle"), "{}", error);
+}
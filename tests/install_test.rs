@@ -0,0 +1,123 @@
+#![cfg(unix)]
+
+use clay::install::find_executable;
+use clay::{ClaudeSetup, Config, InstallStrategy, Version, VersionReq};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn fake_cli(dir: &Path, version_output: &str) -> PathBuf {
+    let path = dir.join("bin").join("claude");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, format!("#!/bin/sh\necho '{}'\n", version_output)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn test_versions_and_strategies_parse() {
+    let dev = Version::find_in("2.1.280-dev.20260921 (Claude Code) (v2.1.280 release candidate)").unwrap();
+    assert_eq!(dev.to_string(), "2.1.280");
+    assert_eq!(Version::find_in("v1.0 (Claude Code)").unwrap().to_string(), "1.0.0");
+    assert!(Version::find_in("command not found").is_none());
+    assert!("0.2.9".parse::<Version>().unwrap() < "1.0.0".parse().unwrap());
+
+    let strategy = |yaml: &str| Config::from_yaml_str(yaml, |_| None).unwrap().install_strategy();
+    assert_eq!(strategy(""), InstallStrategy::Portable);
+    assert_eq!(strategy("install:\n  strategy: system\n"), InstallStrategy::System);
+    assert_eq!(strategy("install:\n  strategy: /opt/claude\n"), InstallStrategy::Path("/opt/claude".into()));
+    // The legacy top-level setting still works
+    assert_eq!(strategy("claude_path: ~/bin/claude\n"), InstallStrategy::Path("~/bin/claude".into()));
+}
+
+#[test]
+fn test_explicit_path_is_used_and_version_checked() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fake_cli(&dir.join("tools"), "0.2.9 (Claude Code)");
+    fs::write(dir.join("clay.yaml"), "install:\n  strategy: tools/bin/claude\n").unwrap();
+
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    assert!(setup.is_installed());
+    assert!(!dir.join(".bun").exists());
+    let binary = setup.get_claude_binary();
+    assert_eq!(binary.path, dir.canonicalize().unwrap().join("tools/bin/claude"));
+    assert_eq!(binary.version.as_deref(), Some("0.2.9"));
+    let error = setup.check_claude_version().unwrap_err();
+    assert!(error.to_string().contains("too old"), "{}", error);
+
+    fake_cli(&dir.join("tools"), "1.0.58 (Claude Code)");
+    assert_eq!(setup.check_claude_version().unwrap().unwrap().to_string(), "1.0.58");
+
+    let missing = ClaudeSetup::builder(dir).install_strategy(InstallStrategy::Path("nowhere/claude".into())).build().unwrap();
+    assert!(!missing.is_installed());
+}
+
+#[test]
+fn test_system_cli_is_found_on_path() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let first = temp_dir.path().join("first");
+    fs::create_dir_all(&first).unwrap();
+    let second = fake_cli(&temp_dir.path().join("second"), "1.0.0");
+    let search_path = std::env::join_paths([first, second.parent().unwrap().to_path_buf()]).unwrap();
+
+    assert_eq!(find_executable("claude", Some(&search_path)), Some(second));
+    assert_eq!(find_executable("no-such-tool", Some(&search_path)), None);
+}
+
+#[test]
+fn test_version_requirements_match_like_npm() {
+    let matches = |req: &str, version: &str| req.parse::<VersionReq>().unwrap().matches(&version.parse().unwrap());
+    assert!(matches("1.0.58", "1.0.58") && !matches("1.0.58", "1.0.59"));
+    assert!(matches("^1.0", "1.9.0") && !matches("^1.0", "2.0.0"));
+    assert!(matches("^0.2.3", "0.2.9") && !matches("^0.2.3", "0.3.0"));
+    assert!(matches("~1.2.3", "1.2.9") && !matches("~1.2.3", "1.3.0"));
+    assert!(matches("1.x", "1.4.2") && !matches("1.x", "2.0.0"));
+    assert!(matches(">=1.0.0, <2.0.0", "1.5.0") && !matches(">=1.0.0 <2.0.0", "2.0.0"));
+    assert!(matches("latest", "0.1.0"));
+    assert!("^one".parse::<VersionReq>().is_err());
+    assert_eq!(">=1.0.0, <2.0.0".parse::<VersionReq>().unwrap().npm_spec(), ">=1.0.0 <2.0.0");
+
+    let config = Config::from_yaml_str("claude:\n  version: ^1.0\n", |_| None).unwrap();
+    assert_eq!(config.claude_version().unwrap().to_string(), "^1.0");
+    assert!(Config::from_yaml_str("claude:\n  version: newest\n", |_| None).is_err());
+}
+
+#[tokio::test]
+async fn test_pin_change_reinstalls_and_upgrade_uses_pin() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let install_dir = dir.join(".bun");
+    fake_cli(&install_dir, "1.0.58 (Claude Code)");
+    // Records its arguments and "installs" 2.1.0
+    let bun = install_dir.join("bin/bun");
+    fs::write(&bun, "#!/bin/sh\necho \"$@\" >> \"$BUN_INSTALL/args\"\nprintf '#!/bin/sh\\necho 2.1.0\\n' > \"$BUN_INSTALL/bin/claude\"\n").unwrap();
+    fs::set_permissions(&bun, fs::Permissions::from_mode(0o755)).unwrap();
+
+    fs::write(dir.join("clay.yaml"), "claude:\n  version: ^1.0\n").unwrap();
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    assert!(!setup.needs_reinstall());
+    assert_eq!(setup.get_claude_binary().pinned.as_deref(), Some("^1.0"));
+
+    fs::write(dir.join("clay.yaml"), "claude:\n  version: ^2.0\n").unwrap();
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    assert!(setup.needs_reinstall());
+    assert!(setup.check_claude_version().unwrap_err().to_string().contains("run clay --upgrade"));
+    setup.install_claude().unwrap();
+    assert!(!setup.needs_reinstall());
+    assert_eq!(setup.check_claude_version().unwrap().unwrap().to_string(), "2.1.0");
+
+    let (before, after) = setup.upgrade_cli(true).await.unwrap();
+    assert_eq!((before.unwrap().to_string(), after.to_string()), ("2.1.0".to_string(), "2.1.0".to_string()));
+    let args = fs::read_to_string(install_dir.join("args")).unwrap();
+    assert_eq!(
+        args,
+        "install -g @anthropic-ai/claude-code@^2.0 --registry https://registry.npmjs.org\n\
+         install -g @anthropic-ai/claude-code@^2.0 --force --registry https://registry.npmjs.org\n"
+    );
+
+    // Clay leaves a CLI it didn't install alone
+    let explicit = ClaudeSetup::builder(dir).install_strategy(InstallStrategy::Path(".bun/bin/claude".into())).build().unwrap();
+    assert!(!explicit.needs_reinstall());
+    assert!(explicit.upgrade_cli(false).await.is_err());
+}
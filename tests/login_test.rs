@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+mod common;
+
+use clay::{ClaudeSetup, LoginResult};
+use common::{clear_ambient_credentials, install_fake_claude, FAKE_LOGIN_CLAUDE};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+const TOKEN: &str = "sk-ant-REDACTED";
+
+#[test]
+fn test_login_through_setup_token_session() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_LOGIN_CLAUDE);
+    let setup = ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap();
+    assert!(!setup.check_authentication().unwrap());
+
+    let session = setup.start_login().unwrap();
+    assert!(session.auth_url().contains("state=abc"));
+
+    // The part after '#' is OAuth state and is stripped before it reaches the CLI
+    assert_eq!(setup.complete_login(Some(session), "good-code#state").unwrap(), LoginResult::Authenticated);
+    assert!(setup.check_authentication().unwrap());
+    assert_eq!(setup.stored_oauth_token().as_deref(), Some(TOKEN));
+
+    let mode = fs::metadata(setup.get_oauth_token_path()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(setup.get_claude_env().contains(&("CLAUDE_CODE_OAUTH_TOKEN".to_string(), TOKEN.to_string())));
+}
+
+#[test]
+fn test_rejected_token_is_not_kept_or_echoed() {
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_LOGIN_CLAUDE);
+    let setup = ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+    let wrong = "sk-ant-REDACTED";
+    match setup.complete_auth(wrong).unwrap() {
+        LoginResult::Rejected { reason } => assert!(!reason.contains(wrong), "{}", reason),
+        other => panic!("expected rejection, got {:?}", other),
+    }
+    assert!(setup.stored_oauth_token().is_none());
+    assert!(!setup.check_authentication().unwrap());
+
+    // A bare code cannot be redeemed without the session that issued its URL
+    let error = setup.complete_auth("good-code").unwrap_err().to_string();
+    assert!(!error.contains("good-code"), "{}", error);
+}
+
+#[test]
+fn test_pasted_api_key_is_stored_as_an_api_key() {
+    const FAKE_API_KEY_CLAUDE: &str = r#"#!/bin/sh
+cat > /dev/null
+if [ "$ANTHROPIC_API_KEY" != "sk-ant-REDACTED" ]; then
+  echo "Invalid API key · Please run /login"
+  exit 1
+fi
+printf '{"type":"result","result":"OK","session_id":"probe-%s","is_error":false}\n' "$$"
+"#;
+    clear_ambient_credentials();
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_API_KEY_CLAUDE);
+    let setup = ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+    assert_eq!(setup.complete_auth("sk-ant-REDACTED").unwrap(), LoginResult::Authenticated);
+    assert_eq!(setup.stored_api_key().as_deref(), Some("sk-ant-REDACTED"));
+    assert!(setup.stored_oauth_token().is_none());
+
+    // Other sk-ant- strings are neither kind of credential, so they need a login session
+    assert!(setup.complete_auth("sk-ant-REDACTED").is_err());
+}
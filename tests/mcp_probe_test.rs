@@ -0,0 +1,135 @@
+#![cfg(unix)]
+
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use clay::mcp::{probe_table, sse_data};
+use clay::{ClaudeSetup, ProbeStatus};
+use serde_json::{json, Value};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+/// A stdio MCP server with two tools and one resource, answering line by line
+const MOCK_SERVER: &str = r#"#!/bin/sh
+echo "mock server starting" >&2
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{},"resources":{}},"serverInfo":{"name":"mock","version":"1.2.3"}}}\n' "$id";;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info"}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo"},{"name":"add"}]}}\n' "$id";;
+    *'"method":"resources/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"resources":[{"uri":"file:///readme","name":"readme"}]}}\n' "$id";;
+  esac
+done
+"#;
+
+/// Streamable HTTP server answering with an event stream and requiring its session id
+async fn start_http_server() -> String {
+    let app = Router::new().route("/mcp", post(|headers: HeaderMap, Json(request): Json<Value>| async move {
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        if method != "initialize" && headers.get("mcp-session-id").and_then(|id| id.to_str().ok()) != Some("session-1") {
+            return (StatusCode::BAD_REQUEST, "missing session").into_response();
+        }
+        let result = match method.as_str() {
+            "initialize" => json!({ "protocolVersion": "2025-06-18", "capabilities": { "prompts": {} }, "serverInfo": { "name": "remote" } }),
+            "prompts/list" if request["params"]["cursor"].is_null() => json!({ "prompts": [{ "name": "review" }], "nextCursor": "2" }),
+            "prompts/list" => json!({ "prompts": [{ "name": "summarize" }] }),
+            _ => return StatusCode::ACCEPTED.into_response(),
+        };
+        let body = format!("event: message\ndata: {}\n\n", json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }));
+        ([("content-type", "text/event-stream"), ("mcp-session-id", "session-1")], body).into_response()
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+#[test]
+fn test_sse_data_joins_multiline_events() {
+    let body = "event: message\r\ndata: {\"a\":\r\ndata: 1}\r\n\r\n: comment\n\ndata:{\"b\":2}\n";
+    assert_eq!(sse_data(body), vec!["{\"a\":\n1}".to_string(), "{\"b\":2}".to_string()]);
+}
+
+#[tokio::test]
+async fn test_probe_reports_each_server() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let script = |name: &str, body: &str| {
+        let path = dir.join(name);
+        fs::write(&path, body).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.display().to_string()
+    };
+    let mock = script("mock.sh", MOCK_SERVER);
+    let crash = script("crash.sh", "#!/bin/sh\necho 'Error: DATABASE_URL is not set' >&2\nexit 3\n");
+    let hang = script("hang.sh", "#!/bin/sh\nsleep 30\n");
+    let garbage = script("garbage.sh", "#!/bin/sh\nread line\necho 'Listening on stdio'\n");
+    let env_dump = script("env.sh", "#!/bin/sh\nenv >&2\nexit 1\n");
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    fs::write(dir.join("clay.yaml"), format!("\
+auth:
+  api_key:
+    command: echo sk-ant-probe-secret
+mcp:
+  servers:
+    env:
+      command: {env_dump}
+      env:
+        FROM_CONFIG: yes
+    mock:
+      command: {mock}
+    crash:
+      command: {crash}
+    hang:
+      command: {hang}
+      timeout: 1
+    garbage:
+      command: {garbage}
+    remote:
+      url: {remote}
+    down:
+      url: http://{closed}/mcp
+", remote = start_http_server().await)).unwrap();
+
+    let setup = ClaudeSetup::new(dir.to_str().unwrap()).unwrap();
+    let probes = setup.probe_mcp_servers().await;
+    let probe = |name: &str| probes.iter().find(|probe| probe.name == name).unwrap();
+
+    let mock = probe("mock");
+    assert_eq!(mock.status, ProbeStatus::Ok, "{:?}", mock.error);
+    assert_eq!(mock.server.as_deref(), Some("mock 1.2.3"));
+    assert_eq!(mock.tools, vec!["echo", "add"]);
+    assert_eq!(mock.resources, vec!["readme"]);
+    assert!(mock.prompts.is_empty());
+    assert_eq!(mock.stderr.as_deref(), Some("mock server starting"));
+
+    let remote = probe("remote");
+    assert_eq!(remote.status, ProbeStatus::Ok, "{:?}", remote.error);
+    assert_eq!(remote.transport, "http");
+    assert_eq!(remote.prompts, vec!["review", "summarize"]);
+
+    let crash = probe("crash");
+    assert_eq!(crash.status, ProbeStatus::Crashed);
+    assert_eq!(crash.stderr.as_deref(), Some("Error: DATABASE_URL is not set"));
+    assert_eq!(probe("hang").status, ProbeStatus::Timeout);
+    assert_eq!(probe("garbage").status, ProbeStatus::ProtocolError);
+    assert!(probe("garbage").error.as_deref().unwrap().contains("Listening on stdio"));
+    assert_eq!(probe("down").status, ProbeStatus::Unreachable);
+
+    // Servers see the SDK's default variables and their own env:, never Clay's credentials
+    let env = probe("env").stderr.clone().unwrap();
+    assert!(env.lines().any(|line| line.starts_with("PATH=")), "{}", env);
+    assert!(env.lines().any(|line| line == "FROM_CONFIG=yes"), "{}", env);
+    assert!(!env.contains("sk-ant-probe-secret") && !env.contains("ANTHROPIC_API_KEY"), "{}", env);
+
+    let table = probe_table(&probes);
+    let header = table.lines().next().unwrap();
+    assert!(header.starts_with("SERVER"), "{}", table);
+    assert!(table.lines().any(|line| line.starts_with("mock ") && line.contains(" ok ") && line.contains(" 2 ")), "{}", table);
+    assert!(table.contains("  stderr: Error: DATABASE_URL is not set"), "{}", table);
+}
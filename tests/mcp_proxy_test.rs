@@ -0,0 +1,245 @@
+#![cfg(unix)]
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use clay::{ClaudeSetup, McpProxy};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Default)]
+struct HttpMock {
+    /// Sessions the server still knows
+    sessions: HashSet<String>,
+    initializes: usize,
+    authorization: Vec<String>,
+}
+
+type SharedHttpMock = Arc<Mutex<HttpMock>>;
+
+fn result(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+/// Streamable HTTP server with sessions; `tools/call` streams a progress
+/// notification before its result, and `slow` never answers in time
+async fn start_http_mock(mock: SharedHttpMock) -> String {
+    let app = Router::new()
+        .route("/mcp", post(|State(mock): State<SharedHttpMock>, headers: HeaderMap, Json(request): Json<Value>| async move {
+            let method = request["method"].as_str().unwrap_or_default().to_string();
+            let session = headers.get("mcp-session-id").and_then(|id| id.to_str().ok()).map(str::to_string);
+            let id = request["id"].clone();
+            let session = {
+                let mut mock = mock.lock().unwrap();
+                if let Some(value) = headers.get("authorization") {
+                    mock.authorization.push(value.to_str().unwrap().to_string());
+                }
+                if method == "initialize" {
+                    mock.initializes += 1;
+                    let session = format!("session-{}", mock.initializes);
+                    mock.sessions.insert(session.clone());
+                    session
+                } else {
+                    match session.filter(|session| mock.sessions.contains(session)) {
+                        Some(session) => session,
+                        None => return (StatusCode::NOT_FOUND, "unknown session").into_response(),
+                    }
+                }
+            };
+            let events = match method.as_str() {
+                "initialize" => vec![result(&id, json!({ "protocolVersion": "2025-06-18", "capabilities": { "tools": {} }, "serverInfo": { "name": "remote" } }))],
+                "tools/list" => vec![result(&id, json!({ "tools": [{ "name": "search" }] }))],
+                "tools/call" => vec![
+                    json!({ "jsonrpc": "2.0", "method": "notifications/progress", "params": { "progress": 1 } }),
+                    result(&id, json!({ "content": [{ "type": "text", "text": "found" }] })),
+                ],
+                "slow" => {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    vec![result(&id, json!({}))]
+                }
+                _ => return (StatusCode::ACCEPTED, [("mcp-session-id", session)], String::new()).into_response(),
+            };
+            let body: String = events.iter().map(|event| format!("event: message\ndata: {}\n\n", event)).collect();
+            ([("content-type", "text/event-stream".to_string()), ("mcp-session-id", session)], body).into_response()
+        }).get(|| async { StatusCode::METHOD_NOT_ALLOWED }))
+        .with_state(mock);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+/// WebSocket server that drops the first connection when `tools/call` arrives and
+/// records every method it receives
+async fn start_ws_mock(methods: Arc<Mutex<Vec<String>>>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/mcp", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut connection = 0;
+        while let Ok((stream, _)) = listener.accept().await {
+            connection += 1;
+            let methods = methods.clone();
+            // Agree to the mcp subprotocol, as MCP WebSocket servers do; tungstenite fixes the error type
+            #[allow(clippy::result_large_err)]
+            let accept = |_: &Request, mut response: Response| {
+                response.headers_mut().insert("Sec-WebSocket-Protocol", "mcp".parse().unwrap());
+                Ok(response)
+            };
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, accept).await.unwrap();
+            tokio::spawn(async move {
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let request: Value = serde_json::from_str(&text).unwrap();
+                    let method = request["method"].as_str().unwrap_or_default().to_string();
+                    methods.lock().unwrap().push(format!("{}:{}:{}", connection, method, request["id"]));
+                    let answer = match method.as_str() {
+                        "initialize" => result(&request["id"], json!({ "protocolVersion": "2025-06-18", "capabilities": {} })),
+                        "tools/list" => result(&request["id"], json!({ "tools": [{ "name": "live" }] })),
+                        "tools/call" if connection == 1 => return,
+                        _ => continue,
+                    };
+                    ws.send(Message::Text(answer.to_string())).await.unwrap();
+                }
+            });
+        }
+    });
+    url
+}
+
+struct Client<W, R> {
+    writer: W,
+    reader: tokio::io::Lines<BufReader<R>>,
+}
+
+impl<W: tokio::io::AsyncWrite + Unpin, R: tokio::io::AsyncRead + Unpin> Client<W, R> {
+    async fn send(&mut self, message: Value) {
+        self.writer.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Value {
+        let line = tokio::time::timeout(Duration::from_secs(10), self.reader.next_line()).await.unwrap().unwrap().unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_mcp_json_runs_the_proxy_for_http_servers() {
+    let mock = SharedHttpMock::default();
+    let url = start_http_mock(mock.clone()).await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("clay.yaml"), format!("\
+mcp:
+  servers:
+    remote:
+      url: {}
+      headers:
+        Authorization: Bearer secret
+      timeout: 1
+", url)).unwrap();
+    let setup = ClaudeSetup::builder(dir).clay_binary(env!("CARGO_BIN_EXE_clay")).build().unwrap();
+    setup.setup_mcp_config().unwrap();
+
+    let mcp: Value = serde_json::from_str(&fs::read_to_string(setup.get_claude_home().join(".config/claude/mcp.json")).unwrap()).unwrap();
+    let entry = &mcp["mcpServers"]["remote"];
+    assert_eq!(entry["command"], env!("CARGO_BIN_EXE_clay"));
+    assert_eq!(entry["args"], json!(["mcp-proxy", "--name", "remote", "--url", url, "--transport", "http", "--timeout", "1"]));
+    // Secrets stay out of the arguments
+    assert!(!entry["args"].to_string().contains("secret"));
+
+    // Run it the way the Claude CLI would
+    let mut command = tokio::process::Command::new(entry["command"].as_str().unwrap());
+    command.args(entry["args"].as_array().unwrap().iter().map(|arg| arg.as_str().unwrap()));
+    for (name, value) in entry["env"].as_object().unwrap() {
+        command.env(name, value.as_str().unwrap());
+    }
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).kill_on_drop(true).spawn().unwrap();
+    let mut client = Client { writer: child.stdin.take().unwrap(), reader: BufReader::new(child.stdout.take().unwrap()).lines() };
+
+    client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-06-18" } })).await;
+    assert_eq!(client.recv().await["result"]["serverInfo"]["name"], "remote");
+    client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+    client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "search" } })).await;
+    assert_eq!(client.recv().await["method"], "notifications/progress");
+    assert_eq!(client.recv().await["result"]["content"][0]["text"], "found");
+
+    // An answer slower than timeout: 1 becomes an error
+    client.send(json!({ "jsonrpc": "2.0", "id": "slow-1", "method": "slow" })).await;
+    let error = client.recv().await;
+    assert_eq!(error["id"], "slow-1");
+    assert!(error["error"]["message"].as_str().unwrap().contains("within 1s"), "{}", error);
+
+    // An expired session is replaced by replaying the handshake
+    mock.lock().unwrap().sessions.clear();
+    client.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/list" })).await;
+    let answer = client.recv().await;
+    assert_eq!((answer["id"].clone(), answer["result"]["tools"][0]["name"].clone()), (json!(3), json!("search")));
+
+    drop(client);
+    assert!(tokio::time::timeout(Duration::from_secs(10), child.wait()).await.unwrap().unwrap().success());
+    let mock = mock.lock().unwrap();
+    assert_eq!(mock.initializes, 2);
+    assert!(!mock.authorization.is_empty() && mock.authorization.iter().all(|value| value == "Bearer secret"));
+}
+
+#[test]
+fn test_remote_servers_need_the_clay_binary() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("clay.yaml"), "mcp:\n  servers:\n    remote:\n      url: http://localhost:9/mcp\n").unwrap();
+    let setup = ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+    let error = setup.setup_mcp_config().unwrap_err().to_string();
+    assert!(error.contains("'remote'") && error.contains("clay_binary"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_websocket_proxy_reconnects_and_replays_initialize() {
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let url = start_ws_mock(methods.clone()).await;
+    let proxy = McpProxy {
+        name: "live".to_string(),
+        url,
+        websocket: true,
+        headers: Vec::new(),
+        timeout: Duration::from_secs(5),
+        reconnect: true,
+        retry_delay: Duration::from_millis(20),
+    };
+    let (client_side, proxy_side) = tokio::io::duplex(64 * 1024);
+    let (proxy_in, proxy_out) = tokio::io::split(proxy_side);
+    let running = tokio::spawn(async move { proxy.run(proxy_in, proxy_out).await });
+    let (reader, writer) = tokio::io::split(client_side);
+    let mut client = Client { writer, reader: BufReader::new(reader).lines() };
+
+    client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })).await;
+    assert_eq!(client.recv().await["id"], 1);
+    client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).await;
+    assert_eq!(client.recv().await["result"]["tools"][0]["name"], "live");
+
+    // The server drops the connection mid-call
+    client.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call" })).await;
+    let error = client.recv().await;
+    assert_eq!(error["id"], 3);
+    assert!(error["error"]["message"].as_str().unwrap().contains("lost"), "{}", error);
+
+    client.send(json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/list" })).await;
+    assert_eq!(client.recv().await["id"], 4);
+    let methods = methods.lock().unwrap().clone();
+    assert_eq!(&methods[3..], &[
+        "2:initialize:\"clay-proxy-reinitialize\"".to_string(),
+        "2:notifications/initialized:null".to_string(),
+        "2:tools/list:4".to_string(),
+    ]);
+
+    drop(client);
+    running.await.unwrap().unwrap();
+}
@@ -0,0 +1,67 @@
+#![cfg(unix)]
+
+mod common;
+
+use clay::config::PoolConfig;
+use clay::{AccountPool, ClaudeProcess, ClaudeSetup, ConfigWatcher, ProcessPool};
+use common::{install_fake_claude, FAKE_STREAMING_CLAUDE};
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn test_pool_hands_out_and_recycles_children() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_STREAMING_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+
+    let pool = Arc::new(ProcessPool::new(
+        setup.clone(),
+        PoolConfig { size: 1, max_uses: 2, max_age_minutes: 30 },
+    ));
+    pool.replenish();
+    assert_eq!(pool.metrics().idle, 1);
+    assert_eq!(pool.metrics().spawned, 1);
+
+    let mut process = ClaudeProcess::new(setup).unwrap();
+    process.set_pool(pool.clone());
+    let first = process.send_message("Hello").unwrap();
+    assert!(first.ends_with("resumed= turn=1"), "{}", first);
+    assert_eq!(pool.metrics().hits, 1);
+    assert_eq!(pool.metrics().idle, 0);
+
+    // The second turn reaches max_uses, so the child is retired afterwards
+    process.send_message("Again").unwrap();
+    assert_eq!(pool.metrics().recycled, 1);
+    assert!(process.streaming_health().is_none());
+
+    // Resuming an existing CLI session needs a dedicated spawn, not a pooled child
+    let session_id = process.get_cli_session_id().unwrap().to_string();
+    let resumed = process.send_message("Still there?").unwrap();
+    assert!(resumed.ends_with(&format!("resumed={} turn=1", session_id)), "{}", resumed);
+    assert_eq!(pool.metrics().hits, 1);
+
+    assert!(pool.acquire().is_none());
+    assert_eq!(pool.metrics().misses, 1);
+}
+
+#[test]
+fn test_reload_drains_idle_children() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    install_fake_claude(dir, FAKE_STREAMING_CLAUDE);
+    fs::write(dir.join("clay.yaml"), "context: first\nprocess:\n  pool:\n    size: 1\n").unwrap();
+    let setup = Arc::new(ClaudeSetup::new(dir.to_str().unwrap()).unwrap());
+    let accounts = AccountPool::new(setup.clone());
+    let watcher = ConfigWatcher::new(setup, &accounts);
+    let pool = accounts.accounts()[0].pool().unwrap().clone();
+    pool.replenish();
+    assert_eq!(pool.metrics().idle, 1);
+
+    // The warm child was started with the old MCP config, so it must not serve new sessions
+    fs::write(dir.join("clay.yaml"), "context: second\nprocess:\n  pool:\n    size: 1\n").unwrap();
+    assert!(watcher.check_now());
+    assert_eq!((pool.metrics().idle, pool.metrics().recycled), (0, 1));
+
+    pool.replenish();
+    assert_eq!((pool.metrics().idle, pool.metrics().spawned), (1, 2));
+}
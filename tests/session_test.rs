@@ -0,0 +1,77 @@
+#![cfg(unix)]
+
+mod common;
+
+use clay::{ClaudeProcess, ClaudeSetup};
+use common::{install_fake_claude, FAKE_CLAUDE, FAKE_STREAMING_CLAUDE};
+use std::fs;
+use std::sync::Arc;
+
+#[test]
+fn test_native_session_is_resumed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_CLAUDE);
+    fs::write(temp_dir.path().join("clay.yaml"), "context: You are testing Clay.\n").unwrap();
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+
+    let mut process = ClaudeProcess::new(setup.clone()).unwrap();
+    assert_eq!(process.send_message("Hello").unwrap(), "resumed=");
+
+    let session_id = process.get_cli_session_id().unwrap().to_string();
+    assert_eq!(process.send_message("Again").unwrap(), format!("resumed={}", session_id));
+
+    let transcript = setup
+        .get_cli_projects_dir()
+        .join("test")
+        .join(format!("{}.jsonl", session_id));
+    let recorded = fs::read_to_string(&transcript).unwrap();
+    // Only the first turn carries the history preamble; resumed turns send the bare message
+    assert!(recorded.contains("Latest message: Hello"));
+    assert!(recorded.ends_with("Again\n"));
+
+    process.cleanup_session_files().unwrap();
+    assert!(!transcript.exists());
+    assert!(process.get_cli_session_id().is_none());
+}
+
+#[test]
+fn test_undo_starts_a_fresh_session() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+
+    let mut process = ClaudeProcess::new(setup).unwrap();
+    process.send_message("Hello").unwrap();
+    process.save_state();
+    process.send_message("Second").unwrap();
+
+    process.undo_last_exchange().unwrap();
+    assert!(process.get_cli_session_id().is_none());
+    assert_eq!(process.send_message("Replacement").unwrap(), "resumed=");
+}
+
+#[test]
+fn test_persistent_process_survives_crash() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("clay.yaml"), "process:\n  persistent: true\n").unwrap();
+    install_fake_claude(temp_dir.path(), FAKE_STREAMING_CLAUDE);
+    let setup = Arc::new(ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap());
+
+    let mut process = ClaudeProcess::new(setup).unwrap();
+    let first = process.send_message("Hello").unwrap();
+    assert!(first.ends_with("resumed= turn=1"), "{}", first);
+    let pid = first.split_whitespace().next().unwrap().to_string();
+
+    // The same child answers the next turn
+    let second = process.send_message("Again").unwrap();
+    assert!(second.starts_with(&pid) && second.ends_with("turn=2"), "{}", second);
+    assert!(process.streaming_health().unwrap().alive);
+
+    // After the child dies, the next turn respawns it and resumes the CLI session
+    process.send_message("CRASH after answering").unwrap();
+
+    let session_id = process.get_cli_session_id().unwrap().to_string();
+    let resumed = process.send_message("Still there?").unwrap();
+    assert!(!resumed.starts_with(&pid), "{}", resumed);
+    assert!(resumed.ends_with(&format!("resumed={} turn=1", session_id)), "{}", resumed);
+}
@@ -0,0 +1,55 @@
+use clay::{ClaudeSetup, Config};
+use std::fs;
+
+#[test]
+fn test_new_leaves_the_directory_untouched() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = ClaudeSetup::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    assert!(setup.get_initial_context().is_none());
+
+    ClaudeSetup::builder(temp_dir.path()).generate_config(true).quiet(true).build().unwrap();
+    assert!(temp_dir.path().join("clay.yaml").exists());
+}
+
+#[test]
+fn test_builder_options() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::create_dir_all(dir.join("conf")).unwrap();
+    fs::write(dir.join("conf/team.yaml"), "context: from file\n").unwrap();
+    fs::write(dir.join("conf/clay.local.yaml"), "server:\n  port: 4100\n").unwrap();
+
+    let setup = ClaudeSetup::builder(dir)
+        .config_path("conf/team.yaml")
+        .install_dir("tools")
+        .claude_home(dir.join("home"))
+        .build()
+        .unwrap();
+    let base = dir.canonicalize().unwrap();
+    assert_eq!(setup.get_initial_context().as_deref(), Some("from file"));
+    assert_eq!(setup.get_config().unwrap().server.as_ref().unwrap().port, 4100);
+    assert_eq!(setup.get_claude_path(), base.join("tools/bin/claude"));
+    assert_eq!(setup.get_claude_home(), dir.join("home"));
+    assert_eq!(setup.get_config_files(), vec![base.join("conf/team.yaml"), base.join("conf/clay.local.yaml")]);
+
+    let config = Config::from_yaml_str("context: in memory\n", |_| None).unwrap();
+    let setup = ClaudeSetup::builder(dir).config_path("conf/team.yaml").config(config).build().unwrap();
+    assert_eq!(setup.get_initial_context().as_deref(), Some("in memory"));
+    assert!(setup.get_config_files().is_empty());
+    assert_eq!(setup.reload_config().unwrap().config.context.as_deref(), Some("in memory"));
+}
+
+#[test]
+fn test_required_config_must_load() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("clay.yaml"), "server:\n  port: [\n").unwrap();
+
+    let error = ClaudeSetup::builder(temp_dir.path()).require_config(true).build().err().unwrap();
+    assert!(error.to_string().to_lowercase().contains("yaml"), "{}", error);
+    // Without it the setup runs on defaults so the config can still be inspected
+    let setup = ClaudeSetup::builder(temp_dir.path()).build().unwrap();
+    assert!(setup.get_config().is_none());
+    assert_eq!(setup.validate_config().unwrap().len(), 1);
+}